@@ -1,59 +1,346 @@
-#[derive(Debug, Clone)]
+/// How a file changed, from the extended header lines git writes after
+/// `diff --git`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileStatus {
+    #[default]
+    Modified,
+    Added,
+    Deleted,
+    Renamed,
+    Copied,
+}
+
+impl FileStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileStatus::Modified => "modified",
+            FileStatus::Added => "added",
+            FileStatus::Deleted => "deleted",
+            FileStatus::Renamed => "renamed",
+            FileStatus::Copied => "copied",
+        }
+    }
+}
+
+/// Mode git records for a submodule (a "gitlink").
+const SUBMODULE_MODE: &str = "160000";
+const SUBPROJECT_COMMIT: &str = "Subproject commit ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    /// Line content without the leading marker.
+    pub text: String,
+    /// 1-based line number in the old file; None for added lines.
+    pub old_line: Option<u32>,
+    /// 1-based line number in the new file; None for removed lines.
+    pub new_line: Option<u32>,
+    /// Followed by `\ No newline at end of file`.
+    pub no_newline: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Hunk {
+    pub old_start: u32,
+    pub old_count: u32,
+    pub new_start: u32,
+    pub new_count: u32,
+    /// Text git prints after the closing `@@`, usually the enclosing definition.
+    pub section: String,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct DiffFile {
+    /// Path after the change; for a deleted file, the path it had.
     pub path: String,
+    /// Path before the change. Differs from `path` only for renames and copies.
+    pub old_path: String,
+    pub status: FileStatus,
+    /// Similarity index of a rename or copy, in percent.
+    pub similarity: Option<u8>,
+    /// Octal modes such as `100644`, when the diff states them. Both are set,
+    /// and differ, when only the mode changed.
+    pub old_mode: Option<String>,
+    pub new_mode: Option<String>,
+    pub binary: bool,
+    /// A submodule pointer; its one-line hunks read `Subproject commit <sha>`.
+    pub submodule: bool,
     pub added_lines: usize,
     pub removed_lines: usize,
-    pub hunks: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl DiffFile {
+    pub fn mode_changed(&self) -> bool {
+        matches!((&self.old_mode, &self.new_mode), (Some(old), Some(new)) if old != new)
+    }
+
+    /// Commits a submodule pointed at before and after the change.
+    pub fn submodule_commits(&self) -> (Option<&str>, Option<&str>) {
+        let commit = |kind: LineKind| {
+            self.hunks
+                .iter()
+                .flat_map(|h| &h.lines)
+                .find(|l| l.kind == kind)
+                .and_then(|l| l.text.strip_prefix(SUBPROJECT_COMMIT))
+        };
+        (commit(LineKind::Removed), commit(LineKind::Added))
+    }
 }
 
+/// Parse a unified diff as printed by `git diff`. Paths come from the
+/// `---`/`+++` and rename/copy lines where present, so names containing
+/// spaces or ` b/` survive; quoted names are unescaped. Hunk line counts are
+/// honoured, so a removed line that reads `-- x` is not taken for a header.
 pub fn parse_diff(raw: &str) -> Vec<DiffFile> {
     let mut files: Vec<DiffFile> = Vec::new();
     let mut current: Option<DiffFile> = None;
-    let mut current_hunk = String::new();
+    // Lines of the current hunk still to come on each side, and the numbers
+    // the next old and new lines will have.
+    let (mut old_left, mut new_left) = (0u32, 0u32);
+    let (mut old_no, mut new_no) = (0u32, 0u32);
 
     for line in raw.lines() {
-        if line.starts_with("--- ") || line.starts_with("+++ ") {
+        if let Some(names) = line.strip_prefix("diff --git ") {
+            files.extend(current.take());
+            let (old_path, path) = split_git_header(names);
+            current = Some(DiffFile { path, old_path, ..DiffFile::default() });
+            (old_left, new_left) = (0, 0);
             continue;
         }
-        if line.starts_with("diff --git ") {
-            if let Some(mut f) = current.take() {
-                if !current_hunk.is_empty() {
-                    f.hunks.push(std::mem::take(&mut current_hunk));
-                }
-                files.push(f);
+        let Some(file) = current.as_mut() else { continue };
+
+        let in_hunk = old_left > 0 || new_left > 0;
+        let kind = match line.as_bytes().first() {
+            Some(b'+') => Some(LineKind::Added),
+            Some(b'-') => Some(LineKind::Removed),
+            Some(b' ') | None => Some(LineKind::Context),
+            _ => None,
+        };
+        // Past the stated counts, still accept body lines that cannot be
+        // headers: hand-written diffs often get the counts wrong.
+        let loose = !in_hunk
+            && !file.hunks.is_empty()
+            && !line.is_empty()
+            && !line.starts_with("--- ")
+            && !line.starts_with("+++ ");
+        if let Some(kind) = kind.filter(|_| in_hunk || loose) {
+            let text = if line.is_empty() { "" } else { &line[1..] };
+            let (old_line, new_line) = match kind {
+                LineKind::Added => (None, Some(new_no)),
+                LineKind::Removed => (Some(old_no), None),
+                LineKind::Context => (Some(old_no), Some(new_no)),
+            };
+            if old_line.is_some() {
+                old_no += 1;
+                old_left = old_left.saturating_sub(1);
+            }
+            if new_line.is_some() {
+                new_no += 1;
+                new_left = new_left.saturating_sub(1);
+            }
+            match kind {
+                LineKind::Added => file.added_lines += 1,
+                LineKind::Removed => file.removed_lines += 1,
+                LineKind::Context => {}
             }
-            let path = line.split(" b/").nth(1).unwrap_or("unknown").to_string();
-            current = Some(DiffFile {
-                path,
-                added_lines: 0,
-                removed_lines: 0,
-                hunks: Vec::new(),
-            });
+            file.submodule |= text.starts_with(SUBPROJECT_COMMIT);
+            let hunk = file.hunks.last_mut().expect("body lines follow a hunk header");
+            hunk.lines.push(DiffLine { kind, text: text.to_string(), old_line, new_line, no_newline: false });
             continue;
         }
-        if let Some(ref mut f) = current {
-            if line.starts_with("@@") {
-                if !current_hunk.is_empty() {
-                    f.hunks.push(std::mem::take(&mut current_hunk));
-                }
-            } else if line.starts_with('+') && !line.starts_with("+++") {
-                f.added_lines += 1;
-            } else if line.starts_with('-') && !line.starts_with("---") {
-                f.removed_lines += 1;
+
+        if line.starts_with('\\') {
+            // "\ No newline at end of file" belongs to the line before it.
+            if let Some(last) = file.hunks.last_mut().and_then(|h| h.lines.last_mut()) {
+                last.no_newline = true;
             }
-            current_hunk.push_str(line);
-            current_hunk.push('\n');
+        } else if let Some(hunk) = parse_hunk_header(line) {
+            (old_left, new_left) = (hunk.old_count, hunk.new_count);
+            (old_no, new_no) = (hunk.old_start, hunk.new_start);
+            file.hunks.push(hunk);
+        } else {
+            apply_header(file, line);
+            file.submodule |= [&file.old_mode, &file.new_mode].into_iter().flatten().any(|m| m == SUBMODULE_MODE);
         }
     }
 
-    if let Some(mut f) = current {
-        if !current_hunk.is_empty() {
-            f.hunks.push(current_hunk);
+    files.extend(current);
+    files
+}
+
+/// Fold one extended header line (`new file mode`, `rename from`, `---`, ...)
+/// into `file`. Unknown lines are ignored.
+fn apply_header(file: &mut DiffFile, line: &str) {
+    let (key, value) = HEADERS
+        .iter()
+        .find_map(|key| line.strip_prefix(key).map(|value| (*key, value)))
+        .unwrap_or(("", line));
+    match key {
+        "--- " => match value.trim_end_matches('\t') {
+            "/dev/null" => file.status = FileStatus::Added,
+            name => file.old_path = strip_prefix(&unquote_name(name), "a/"),
+        },
+        "+++ " => match value.trim_end_matches('\t') {
+            "/dev/null" => {
+                file.status = FileStatus::Deleted;
+                file.path = file.old_path.clone();
+            }
+            name => file.path = strip_prefix(&unquote_name(name), "b/"),
+        },
+        "old mode " => file.old_mode = Some(value.to_string()),
+        "new mode " => file.new_mode = Some(value.to_string()),
+        "new file mode " => {
+            file.status = FileStatus::Added;
+            file.new_mode = Some(value.to_string());
+        }
+        "deleted file mode " => {
+            file.status = FileStatus::Deleted;
+            file.old_mode = Some(value.to_string());
+        }
+        "similarity index " => file.similarity = value.trim_end_matches('%').parse().ok(),
+        "rename from " | "copy from " => file.old_path = unquote_name(value),
+        "rename to " | "copy to " => {
+            file.path = unquote_name(value);
+            file.status = if key == "rename to " { FileStatus::Renamed } else { FileStatus::Copied };
+        }
+        "index " => {
+            // `index abc..def 100644` states the mode when it did not change.
+            if let Some((_, mode)) = value.split_once(' ') {
+                file.old_mode.get_or_insert_with(|| mode.to_string());
+                file.new_mode.get_or_insert_with(|| mode.to_string());
+            }
         }
-        files.push(f);
+        "Binary files " | "GIT binary patch" => file.binary = true,
+        _ => {}
     }
+}
 
-    files
+const HEADERS: &[&str] = &[
+    "--- ",
+    "+++ ",
+    "old mode ",
+    "new mode ",
+    "new file mode ",
+    "deleted file mode ",
+    "similarity index ",
+    "rename from ",
+    "rename to ",
+    "copy from ",
+    "copy to ",
+    "index ",
+    "Binary files ",
+    "GIT binary patch",
+];
+
+/// `@@ -12,5 +12,7 @@ fn run()`; a missing count means one line.
+fn parse_hunk_header(line: &str) -> Option<Hunk> {
+    let (ranges, section) = line.strip_prefix("@@ -")?.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let range = |r: &str| -> Option<(u32, u32)> {
+        match r.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((r.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = range(old)?;
+    let (new_start, new_count) = range(new)?;
+    Some(Hunk {
+        old_start,
+        old_count,
+        new_start,
+        new_count,
+        section: section.strip_prefix(' ').unwrap_or(section).to_string(),
+        lines: Vec::new(),
+    })
+}
+
+/// Old and new path from the names after `diff --git`. Only a fallback: the
+/// `---`/`+++` and rename lines that follow are unambiguous and win.
+fn split_git_header(names: &str) -> (String, String) {
+    let (old, new) = if names.starts_with('"') {
+        match unquote(names) {
+            Some((old, rest)) => (old, unquote_name(rest.trim_start())),
+            None => (names.to_string(), String::new()),
+        }
+    } else if let Some(i) = names.rfind(" \"").filter(|_| names.ends_with('"')) {
+        (names[..i].to_string(), unquote_name(&names[i + 1..]))
+    } else {
+        // Unquoted and not renamed: `a/<p> b/<p>`, so split in the middle.
+        let mid = names.len() / 2;
+        match (names.get(..mid), names.get(mid + 1..)) {
+            (Some(old), Some(new)) if names.as_bytes()[mid] == b' ' && old.get(2..) == new.get(2..) => {
+                (old.to_string(), new.to_string())
+            }
+            _ => match names.split_once(" b/") {
+                Some((old, new)) => (old.to_string(), format!("b/{}", new)),
+                None => (names.to_string(), names.to_string()),
+            },
+        }
+    };
+    (strip_prefix(&old, "a/"), strip_prefix(&new, "b/"))
+}
+
+fn strip_prefix(name: &str, prefix: &str) -> String {
+    name.strip_prefix(prefix).unwrap_or(name).to_string()
+}
+
+/// A path as git prints it: C-quoted when it has unusual characters.
+fn unquote_name(name: &str) -> String {
+    match unquote(name) {
+        Some((name, _)) => name,
+        None => name.to_string(),
+    }
+}
+
+/// Decode a leading C-style quoted string; returns it and what follows.
+fn unquote(s: &str) -> Option<(String, &str)> {
+    let body = s.strip_prefix('"')?;
+    let mut bytes = Vec::new();
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((String::from_utf8_lossy(&bytes).into_owned(), &body[i + 1..])),
+            '\\' => {
+                let (_, escaped) = chars.next()?;
+                let byte = match escaped {
+                    'a' => 0x07,
+                    'b' => 0x08,
+                    't' => b'\t',
+                    'n' => b'\n',
+                    'v' => 0x0b,
+                    'f' => 0x0c,
+                    'r' => b'\r',
+                    '0'..='7' => {
+                        let digits: String = std::iter::once(escaped)
+                            .chain(chars.clone().take(2).map(|(_, d)| d))
+                            .collect();
+                        chars.nth(1);
+                        u8::from_str_radix(&digits, 8).ok()?
+                    }
+                    other => {
+                        let mut buf = [0; 4];
+                        bytes.extend_from_slice(other.encode_utf8(&mut buf).as_bytes());
+                        continue;
+                    }
+                };
+                bytes.push(byte);
+            }
+            other => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(other.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+    None
 }
 
 #[cfg(test)]
@@ -165,4 +452,125 @@ mod tests {
         assert_eq!(files[0].added_lines, 1);
         assert_eq!(files[0].removed_lines, 1);
     }
+
+    #[test]
+    fn test_parse_diff_hunk_ranges_and_typed_lines() {
+        let diff = "diff --git a/f.rs b/f.rs\n--- a/f.rs\n+++ b/f.rs\n@@ -10,3 +10,3 @@ fn run() {\n keep\n-old\n+new\n tail\n\\ No newline at end of file\n";
+        let hunk = &parse_diff(diff)[0].hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_count, hunk.new_start, hunk.new_count), (10, 3, 10, 3));
+        assert_eq!(hunk.section, "fn run() {");
+        let kinds: Vec<_> = hunk.lines.iter().map(|l| (l.kind, l.text.as_str(), l.old_line, l.new_line)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (LineKind::Context, "keep", Some(10), Some(10)),
+                (LineKind::Removed, "old", Some(11), None),
+                (LineKind::Added, "new", None, Some(11)),
+                (LineKind::Context, "tail", Some(12), Some(12)),
+            ]
+        );
+        assert!(hunk.lines[3].no_newline);
+        assert!(!hunk.lines[2].no_newline);
+    }
+
+    #[test]
+    fn test_parse_diff_removed_line_that_looks_like_a_header() {
+        // SQL comment "-- x" removed: the body line reads "--- x".
+        let diff = "diff --git a/q.sql b/q.sql\n--- a/q.sql\n+++ b/q.sql\n@@ -1,2 +1,1 @@\n--- x\n select 1;\n";
+        let files = parse_diff(diff);
+        assert_eq!(files[0].path, "q.sql");
+        assert_eq!(files[0].removed_lines, 1);
+        assert_eq!(files[0].hunks[0].lines[0].text, "-- x");
+    }
+
+    #[test]
+    fn test_parse_diff_path_containing_b_slash() {
+        let diff = "diff --git a/lib b/x.rs b/lib b/x.rs\nindex 1..2 100644\n--- a/lib b/x.rs\n+++ b/lib b/x.rs\n@@ -1 +1 @@\n-a\n+b\n";
+        let files = parse_diff(diff);
+        assert_eq!(files[0].path, "lib b/x.rs");
+        assert_eq!(files[0].old_path, "lib b/x.rs");
+        assert_eq!(files[0].old_mode.as_deref(), Some("100644"));
+        assert!(!files[0].mode_changed());
+    }
+
+    #[test]
+    fn test_parse_diff_header_only_path_is_split_in_the_middle() {
+        let diff = "diff --git a/my b/dir/run.sh b/my b/dir/run.sh\nold mode 100644\nnew mode 100755\n";
+        let files = parse_diff(diff);
+        assert_eq!(files[0].path, "my b/dir/run.sh");
+        assert!(files[0].mode_changed());
+        assert!(files[0].hunks.is_empty());
+    }
+
+    #[test]
+    fn test_parse_diff_quoted_paths() {
+        let diff = "diff --git \"a/caf\\303\\251 \\\"x\\\".txt\" \"b/caf\\303\\251 \\\"x\\\".txt\"\nnew file mode 100644\n--- /dev/null\n+++ \"b/caf\\303\\251 \\\"x\\\".txt\"\n@@ -0,0 +1 @@\n+hi\n";
+        let files = parse_diff(diff);
+        assert_eq!(files[0].path, "café \"x\".txt");
+        assert_eq!(files[0].status, FileStatus::Added);
+        assert_eq!(files[0].new_mode.as_deref(), Some("100644"));
+    }
+
+    #[test]
+    fn test_parse_diff_rename_with_similarity() {
+        let diff = concat!(
+            "diff --git a/src/old name.rs b/src/new name.rs\n",
+            "similarity index 87%\n",
+            "rename from src/old name.rs\n",
+            "rename to src/new name.rs\n",
+            "--- a/src/old name.rs\t\n",
+            "+++ b/src/new name.rs\t\n",
+            "@@ -1 +1 @@\n-a\n+b\n",
+        );
+        let f = &parse_diff(diff)[0];
+        assert_eq!(f.status, FileStatus::Renamed);
+        assert_eq!(f.similarity, Some(87));
+        assert_eq!(f.old_path, "src/old name.rs");
+        assert_eq!(f.path, "src/new name.rs");
+    }
+
+    #[test]
+    fn test_parse_diff_copy() {
+        let diff = "diff --git a/a.rs b/b.rs\nsimilarity index 100%\ncopy from a.rs\ncopy to b.rs\n";
+        let f = &parse_diff(diff)[0];
+        assert_eq!(f.status, FileStatus::Copied);
+        assert_eq!((f.old_path.as_str(), f.path.as_str()), ("a.rs", "b.rs"));
+    }
+
+    #[test]
+    fn test_parse_diff_deleted_and_binary() {
+        let diff = concat!(
+            "diff --git a/gone.rs b/gone.rs\ndeleted file mode 100644\nindex abc..000\n--- a/gone.rs\n+++ /dev/null\n@@ -1 +0,0 @@\n-x\n",
+            "diff --git a/logo.png b/logo.png\nnew file mode 100644\nindex 000..abc\nBinary files /dev/null and b/logo.png differ\n",
+            "diff --git a/icon.png b/icon.png\nindex 1..2 100644\nGIT binary patch\nliteral 5\nMcmZ?wbhEHbGXMgD\n\nliteral 0\nHcmV?d00001\n\n",
+        );
+        let files = parse_diff(diff);
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].status, FileStatus::Deleted);
+        assert_eq!(files[0].path, "gone.rs");
+        assert_eq!(files[0].old_mode.as_deref(), Some("100644"));
+        assert_eq!(files[1].status, FileStatus::Added);
+        assert!(files[1].binary);
+        assert_eq!(files[1].path, "logo.png");
+        assert!(files[2].binary);
+        assert!(files[2].hunks.is_empty());
+        assert_eq!((files[2].added_lines, files[2].removed_lines), (0, 0));
+    }
+
+    #[test]
+    fn test_parse_diff_submodule_update() {
+        let diff = "diff --git a/vendor/lib b/vendor/lib\nindex 1111111..2222222 160000\n--- a/vendor/lib\n+++ b/vendor/lib\n@@ -1 +1 @@\n-Subproject commit 1111111aaaa\n+Subproject commit 2222222bbbb-dirty\n";
+        let f = &parse_diff(diff)[0];
+        assert!(f.submodule);
+        assert_eq!(f.submodule_commits(), (Some("1111111aaaa"), Some("2222222bbbb-dirty")));
+    }
+
+    #[test]
+    fn test_parse_diff_new_submodule_by_mode() {
+        let diff = "diff --git a/ext b/ext\nnew file mode 160000\nindex 0000000..3333333\n--- /dev/null\n+++ b/ext\n@@ -0,0 +1 @@\n+Subproject commit 3333333\n";
+        let f = &parse_diff(diff)[0];
+        assert!(f.submodule);
+        assert_eq!(f.status, FileStatus::Added);
+        assert_eq!(f.submodule_commits(), (None, Some("3333333")));
+    }
 }
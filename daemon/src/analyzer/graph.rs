@@ -0,0 +1,614 @@
+use super::symbols::is_source;
+use crate::protocol::{AnalysisResult, ImpactedFile};
+use crate::store::AuditLog;
+use anyhow::Result;
+use regex::Regex;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// A file imported directly by this many others is a high blast radius change.
+const HIGH_FAN_IN: usize = 10;
+/// Likewise for a file this many others depend on through any chain of imports.
+const HIGH_TRANSITIVE: usize = 25;
+/// Score of a deleted file that is still imported, and of each importer:
+/// neither will build.
+const DELETED_BUT_IMPORTED: f32 = 0.95;
+
+static TS_FROM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?:import|export)\s[^'";]*?from\s*['"]([^'"]+)['"]"#).unwrap());
+static TS_BARE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?:\bimport\s*\(?|\brequire\s*\()\s*['"]([^'"]+)['"]"#).unwrap());
+static PY_IMPORT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^\s*import\s+([^#\n]+)").unwrap());
+static PY_FROM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*from\s+(\S+)\s+import\s+\(?([^#\n)]*)").unwrap());
+static GO_SINGLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?m)^\s*import\s+(?:[\w.]+\s+)?"([^"]+)""#).unwrap());
+static GO_BLOCK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)\bimport\s*\((.*?)\)").unwrap());
+static GO_QUOTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""([^"]+)""#).unwrap());
+
+/// Raw import specifiers in `source`, as written. Rust specs are prefixed
+/// with `mod ` or `use `; Python `from` imports are `module:name,name`.
+/// Resolution to files happens later, against the whole workspace.
+pub fn imports(path: &str, source: &str) -> Vec<String> {
+    match path.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("rs") => rust_imports(source),
+        Some("ts" | "tsx" | "mts" | "cts" | "js" | "jsx" | "mjs" | "cjs") => TS_FROM
+            .captures_iter(source)
+            .chain(TS_BARE.captures_iter(source))
+            .map(|c| c[1].to_string())
+            .collect(),
+        Some("py") => {
+            let mut specs: Vec<String> = PY_IMPORT
+                .captures_iter(source)
+                .flat_map(|c| c[1].split(',').map(|m| strip_alias(m).to_string()).collect::<Vec<_>>())
+                .filter(|m| !m.is_empty())
+                .collect();
+            specs.extend(PY_FROM.captures_iter(source).map(|c| {
+                let names: Vec<&str> = c[2].split(',').map(strip_alias).filter(|n| !n.is_empty()).collect();
+                format!("{}:{}", &c[1], names.join(","))
+            }));
+            specs
+        }
+        Some("go") => {
+            let mut specs: Vec<String> = GO_SINGLE.captures_iter(source).map(|c| c[1].to_string()).collect();
+            for block in GO_BLOCK.captures_iter(source) {
+                specs.extend(GO_QUOTED.captures_iter(&block[1]).map(|c| c[1].to_string()));
+            }
+            specs
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// `a.b as c` → `a.b`.
+fn strip_alias(s: &str) -> &str {
+    s.split(" as ").next().unwrap_or("").trim()
+}
+
+fn rust_imports(source: &str) -> Vec<String> {
+    let mut specs = Vec::new();
+    let mut pending: Option<String> = None;
+    for line in source.lines() {
+        let line = line.split("//").next().unwrap_or("").trim();
+        if let Some(buf) = pending.as_mut() {
+            buf.push_str(line);
+        } else {
+            let rest = strip_visibility(line);
+            if let Some(tree) = rest.strip_prefix("use ") {
+                pending = Some(tree.to_string());
+            } else if let Some(name) = rest.strip_prefix("mod ").and_then(|m| m.strip_suffix(';')) {
+                specs.push(format!("mod {}", name.trim()));
+                continue;
+            } else {
+                continue;
+            }
+        }
+        if let Some(buf) = pending.take_if(|b| b.contains(';')) {
+            let tree = buf.split(';').next().unwrap_or("");
+            specs.extend(expand_use_tree(tree).into_iter().map(|p| format!("use {}", p)));
+        }
+    }
+    specs
+}
+
+fn strip_visibility(line: &str) -> &str {
+    let Some(rest) = line.strip_prefix("pub") else { return line };
+    let rest = match rest.strip_prefix('(') {
+        Some(r) => r.split_once(')').map(|(_, after)| after).unwrap_or(r),
+        None => rest,
+    };
+    rest.trim_start()
+}
+
+/// `crate::a::{b, c::{d, self}}` → `crate::a::b`, `crate::a::c::d`, `crate::a::c`.
+fn expand_use_tree(tree: &str) -> Vec<String> {
+    let tree: String = tree.chars().filter(|c| !c.is_whitespace() || *c == ' ').collect();
+    let tree = tree.trim();
+    let Some(open) = tree.find('{') else {
+        let path = strip_alias(tree).trim_end_matches("::*").trim_end_matches("::self");
+        return if path.is_empty() { vec![] } else { vec![path.to_string()] };
+    };
+    let prefix = &tree[..open];
+    let inner = tree[open + 1..].strip_suffix('}').unwrap_or(&tree[open + 1..]);
+
+    let mut items = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&inner[start..]);
+
+    items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .flat_map(|item| expand_use_tree(&format!("{}{}", prefix, item)))
+        .collect()
+}
+
+/// Turns import specifiers into workspace-relative file paths.
+struct Resolver<'a> {
+    files: &'a HashSet<String>,
+    /// Module path from the workspace's go.mod, if any.
+    go_module: Option<&'a str>,
+    /// Directory → the Go files in it, for package imports.
+    go_dirs: HashMap<&'a str, Vec<&'a str>>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(files: &'a HashSet<String>, go_module: Option<&'a str>) -> Self {
+        let mut go_dirs: HashMap<&str, Vec<&str>> = HashMap::new();
+        for f in files.iter().filter(|f| f.ends_with(".go") && !f.ends_with("_test.go")) {
+            go_dirs.entry(parent(f)).or_default().push(f);
+        }
+        Self { files, go_module, go_dirs }
+    }
+
+    fn exists(&self, path: &str) -> Option<String> {
+        self.files.contains(path).then(|| path.to_string())
+    }
+
+    fn resolve(&self, from: &str, spec: &str) -> Vec<String> {
+        let resolved = match from.rsplit_once('.').map(|(_, ext)| ext) {
+            Some("rs") => self.rust(from, spec).into_iter().collect(),
+            Some("py") => self.python(from, spec),
+            Some("go") => self.go(spec),
+            _ => self.script(from, spec).into_iter().collect(),
+        };
+        resolved.into_iter().filter(|p: &String| p != from).collect()
+    }
+
+    fn rust_module_file(&self, dir: &str) -> Option<String> {
+        self.exists(&format!("{}.rs", dir)).or_else(|| self.exists(&join(dir, "mod.rs")))
+    }
+
+    /// Directory holding the crate root (`lib.rs` / `main.rs`) above `from`.
+    fn crate_root(&self, from: &str) -> Option<(String, String)> {
+        let mut dir = parent(from);
+        loop {
+            for root in ["lib.rs", "main.rs"] {
+                if let Some(file) = self.exists(&join(dir, root)) {
+                    return Some((dir.to_string(), file));
+                }
+            }
+            if dir.is_empty() {
+                return None;
+            }
+            dir = parent(dir);
+        }
+    }
+
+    fn rust(&self, from: &str, spec: &str) -> Option<String> {
+        let (dir, file) = from.rsplit_once('/').unwrap_or(("", from));
+        let owns_dir = matches!(file, "mod.rs" | "lib.rs" | "main.rs");
+        // Where this file's child modules live.
+        let self_dir = if owns_dir { dir.to_string() } else { join(dir, file.trim_end_matches(".rs")) };
+
+        if let Some(name) = spec.strip_prefix("mod ") {
+            return self.rust_module_file(&join(&self_dir, name));
+        }
+        let path = spec.strip_prefix("use ")?;
+        let mut segments = path.split("::").peekable();
+
+        let (mut dir, mut file) = match *segments.peek()? {
+            "crate" => {
+                segments.next();
+                self.crate_root(from)?
+            }
+            "self" => {
+                segments.next();
+                (self_dir, from.to_string())
+            }
+            "super" => {
+                let mut dir = self_dir;
+                while segments.next_if_eq(&"super").is_some() {
+                    dir = parent(&dir).to_string();
+                }
+                let file = self
+                    .rust_module_file(&dir)
+                    .or_else(|| self.crate_root(from).filter(|(root, _)| *root == dir).map(|(_, f)| f))?;
+                (dir, file)
+            }
+            // 2018-style paths to top-level modules, e.g. `analyzer::diff` in main.rs.
+            _ => self.crate_root(from)?,
+        };
+        let start = file.clone();
+        for segment in segments {
+            let next = join(&dir, segment);
+            match self.rust_module_file(&next) {
+                Some(f) => {
+                    file = f;
+                    dir = next;
+                }
+                None => break,
+            }
+        }
+        // A bare external path (`std::fmt`) resolves no further than the crate root.
+        (file != start || path.starts_with("crate::") || path.starts_with("super::") || path.starts_with("self::"))
+            .then_some(file)
+    }
+
+    fn script(&self, from: &str, spec: &str) -> Option<String> {
+        if !spec.starts_with('.') {
+            return None; // package import
+        }
+        let base = normalize(&join(parent(from), spec))?;
+        let stem = base
+            .strip_suffix(".js")
+            .or_else(|| base.strip_suffix(".jsx"))
+            .or_else(|| base.strip_suffix(".mjs"))
+            .unwrap_or(&base);
+        const EXTS: [&str; 6] = [".ts", ".tsx", ".js", ".jsx", ".mjs", ".cjs"];
+        self.exists(&base)
+            .or_else(|| EXTS.iter().find_map(|ext| self.exists(&format!("{}{}", stem, ext))))
+            .or_else(|| EXTS.iter().find_map(|ext| self.exists(&format!("{}/index{}", base, ext))))
+    }
+
+    fn python(&self, from: &str, spec: &str) -> Vec<String> {
+        let (module, names) = spec.split_once(':').unwrap_or((spec, ""));
+        let dots = module.chars().take_while(|c| *c == '.').count();
+        let rel = module[dots..].replace('.', "/");
+
+        // Relative imports climb from the importing package; absolute ones may
+        // be rooted at any ancestor (plain layout, `src/` layout, ...).
+        let bases: Vec<String> = if dots > 0 {
+            let mut dir = parent(from);
+            for _ in 1..dots {
+                dir = parent(dir);
+            }
+            vec![dir.to_string()]
+        } else {
+            let mut bases = Vec::new();
+            let mut dir = parent(from);
+            loop {
+                bases.push(dir.to_string());
+                if dir.is_empty() {
+                    break;
+                }
+                dir = parent(dir);
+            }
+            bases
+        };
+
+        let module_file = |path: &str| {
+            self.exists(&format!("{}.py", path)).or_else(|| self.exists(&join(path, "__init__.py")))
+        };
+        for base in bases {
+            let target = join(&base, &rel);
+            let mut found: Vec<String> = names
+                .split(',')
+                .filter(|n| !n.is_empty() && *n != "*")
+                .filter_map(|n| module_file(&join(&target, n)))
+                .collect();
+            if found.is_empty() {
+                found.extend(module_file(&target));
+            }
+            if !found.is_empty() {
+                return found;
+            }
+        }
+        Vec::new()
+    }
+
+    fn go(&self, spec: &str) -> Vec<String> {
+        let Some(module) = self.go_module else { return Vec::new() };
+        let dir = if spec == module {
+            ""
+        } else {
+            match spec.strip_prefix(module).and_then(|rest| rest.strip_prefix('/')) {
+                Some(dir) => dir,
+                None => return Vec::new(),
+            }
+        };
+        self.go_dirs.get(dir).map(|fs| fs.iter().map(|f| f.to_string()).collect()).unwrap_or_default()
+    }
+}
+
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("")
+}
+
+fn join(dir: &str, name: &str) -> String {
+    match (dir.is_empty(), name.is_empty()) {
+        (true, _) => name.to_string(),
+        (_, true) => dir.to_string(),
+        _ => format!("{}/{}", dir, name),
+    }
+}
+
+/// Collapse `.` and `..`; None if the path climbs out of the workspace.
+fn normalize(path: &str) -> Option<String> {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            p => parts.push(p),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// File-level import graph of a workspace, stored as reverse edges.
+#[derive(Debug, Default)]
+pub struct ImportGraph {
+    importers: HashMap<String, HashSet<String>>,
+}
+
+impl ImportGraph {
+    /// Build from `(importing file, raw spec)` pairs. `files` is every file
+    /// an import may resolve to.
+    pub fn build(files: &HashSet<String>, specs: &[(String, String)], go_module: Option<&str>) -> Self {
+        let resolver = Resolver::new(files, go_module);
+        let mut importers: HashMap<String, HashSet<String>> = HashMap::new();
+        for (from, spec) in specs {
+            for target in resolver.resolve(from, spec) {
+                importers.entry(target).or_default().insert(from.clone());
+            }
+        }
+        Self { importers }
+    }
+
+    /// Files that import `path` directly.
+    pub fn direct_dependents(&self, path: &str) -> usize {
+        self.importers.get(path).map_or(0, HashSet::len)
+    }
+
+    /// The files counted by `direct_dependents`, sorted.
+    pub fn importers_of(&self, path: &str) -> Vec<&str> {
+        let mut importers: Vec<&str> = self.importers.get(path).into_iter().flatten().map(String::as_str).collect();
+        importers.sort_unstable();
+        importers
+    }
+
+    /// Files that reach `path` through any chain of imports.
+    pub fn transitive_dependents(&self, path: &str) -> usize {
+        let mut seen: HashSet<&str> = HashSet::from([path]);
+        let mut queue: VecDeque<&str> = VecDeque::from([path]);
+        while let Some(next) = queue.pop_front() {
+            for importer in self.importers.get(next).into_iter().flatten() {
+                if seen.insert(importer.as_str()) {
+                    queue.push_back(importer.as_str());
+                }
+            }
+        }
+        seen.len() - 1
+    }
+}
+
+/// Module path declared in the workspace's `go.mod`, if there is one.
+fn go_module(root: &Path) -> Option<String> {
+    let gomod = std::fs::read_to_string(root.join("go.mod")).ok()?;
+    gomod
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("module "))
+        .map(|m| m.trim().trim_matches('"').to_string())
+}
+
+/// The more files depend on a change, the further it can break things.
+fn blast_score(transitive: usize) -> f32 {
+    match transitive {
+        0 => 0.2,
+        1..=2 => 0.4,
+        3..=9 => 0.6,
+        10..=24 => 0.8,
+        _ => 0.95,
+    }
+}
+
+/// Re-score each changed source file in `result` by how many workspace files
+/// depend on it, using the import graph from the index in `db` (refresh it
+/// first). Widely imported files are called out in `risk_reasons`; a deleted
+/// file that is still imported makes the change high risk, and its importers
+/// are added to `impacted_files`. Blocking.
+pub fn apply_blast_radius(db: &AuditLog, root: &Path, result: &mut AnalysisResult) -> Result<()> {
+    let mut files: HashSet<String> = db.indexed_files(root)?.into_iter().collect();
+    // Files deleted by the diff are gone from disk but still have importers.
+    files.extend(result.impacted_files.iter().map(|f| f.path.clone()));
+    let module = go_module(root);
+    let graph = ImportGraph::build(&files, &db.import_specs(root)?, module.as_deref());
+
+    let mut broken: Vec<ImpactedFile> = Vec::new();
+    for file in result.impacted_files.iter_mut().filter(|f| is_source(&f.path)) {
+        let direct = graph.direct_dependents(&file.path);
+        let transitive = graph.transitive_dependents(&file.path);
+        file.score = blast_score(transitive);
+        if transitive > 0 {
+            file.why.push(format!("imported by {} file(s), {} transitively", direct, transitive));
+        }
+        if file.change == "deleted" && direct > 0 {
+            file.score = file.score.max(DELETED_BUT_IMPORTED);
+            result.risk_level = "high".to_string();
+            result.risk_reasons.push(format!(
+                "Deleted file {} is still imported by {} file(s) ({} transitively)",
+                file.path, direct, transitive
+            ));
+            for importer in graph.importers_of(&file.path) {
+                match broken.iter_mut().find(|b| b.path == importer) {
+                    Some(b) => b.why.push(format!("imports {}, which was deleted", file.path)),
+                    None => broken.push(ImpactedFile {
+                        path: importer.to_string(),
+                        score: DELETED_BUT_IMPORTED,
+                        why: vec![format!("imports {}, which was deleted", file.path)],
+                        change: String::new(),
+                    }),
+                }
+            }
+        } else if direct >= HIGH_FAN_IN || transitive >= HIGH_TRANSITIVE {
+            result.risk_reasons.push(format!(
+                "High blast radius: {} is imported by {} file(s) ({} transitively)",
+                file.path, direct, transitive
+            ));
+        }
+    }
+    broken.retain(|b| !result.impacted_files.iter().any(|f| f.path == b.path));
+    result.impacted_files.extend(broken);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{modified, result_with, TempDir};
+
+    fn files(paths: &[&str]) -> HashSet<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn resolve(paths: &[&str], from: &str, spec: &str) -> Vec<String> {
+        let files = files(paths);
+        Resolver::new(&files, Some("example.com/app")).resolve(from, spec)
+    }
+
+    #[test]
+    fn rust_use_and_mod_statements() {
+        let src = "mod diff;\npub mod impact;\n#[cfg(test)]\nmod tests {\n}\nuse crate::protocol::{AnalysisResult,\n    ImpactedFile};\nuse super::symbols::{self, is_source};\npub(crate) use std::fmt; // comment\n";
+        assert_eq!(
+            imports("src/a.rs", src),
+            vec![
+                "mod diff",
+                "mod impact",
+                "use crate::protocol::AnalysisResult",
+                "use crate::protocol::ImpactedFile",
+                "use super::symbols",
+                "use super::symbols::is_source",
+                "use std::fmt",
+            ]
+        );
+    }
+
+    #[test]
+    fn rust_resolution() {
+        let tree = [
+            "src/main.rs",
+            "src/protocol.rs",
+            "src/analyzer/mod.rs",
+            "src/analyzer/diff.rs",
+            "src/analyzer/impact.rs",
+        ];
+        assert_eq!(resolve(&tree, "src/main.rs", "mod protocol"), vec!["src/protocol.rs"]);
+        assert_eq!(resolve(&tree, "src/analyzer/mod.rs", "mod diff"), vec!["src/analyzer/diff.rs"]);
+        assert_eq!(resolve(&tree, "src/analyzer/impact.rs", "use crate::protocol::AnalysisResult"), vec!["src/protocol.rs"]);
+        assert_eq!(resolve(&tree, "src/analyzer/impact.rs", "use super::diff::DiffFile"), vec!["src/analyzer/diff.rs"]);
+        assert_eq!(resolve(&tree, "src/main.rs", "use analyzer::impact::Progress"), vec!["src/analyzer/impact.rs"]);
+        assert!(resolve(&tree, "src/main.rs", "use std::path::Path").is_empty());
+    }
+
+    #[test]
+    fn script_imports_and_resolution() {
+        let src = "import * as vscode from 'vscode';\nimport { DaemonClient } from './daemon/client';\nexport { x } from \"../lib/x.js\";\nconst y = require('./y');\nimport './side-effect';\n";
+        assert_eq!(
+            imports("src/commands.ts", src),
+            vec!["vscode", "./daemon/client", "../lib/x.js", "./y", "./side-effect"]
+        );
+        let tree = ["src/commands.ts", "src/daemon/client.ts", "lib/x.ts", "src/y/index.js"];
+        assert_eq!(resolve(&tree, "src/commands.ts", "./daemon/client"), vec!["src/daemon/client.ts"]);
+        assert_eq!(resolve(&tree, "src/commands.ts", "../lib/x.js"), vec!["lib/x.ts"]);
+        assert_eq!(resolve(&tree, "src/commands.ts", "./y"), vec!["src/y/index.js"]);
+        assert!(resolve(&tree, "src/commands.ts", "vscode").is_empty());
+        assert!(resolve(&tree, "src/commands.ts", "../../outside").is_empty());
+    }
+
+    #[test]
+    fn python_imports_and_resolution() {
+        let src = "import os, pkg.util as u\nfrom . import models\nfrom ..core import (api, db)\n";
+        assert_eq!(imports("pkg/sub/mod.py", src), vec!["os", "pkg.util", ".:models", "..core:api,db"]);
+        let tree = ["pkg/__init__.py", "pkg/util.py", "pkg/sub/models.py", "pkg/sub/mod.py", "pkg/core/__init__.py", "pkg/core/api.py"];
+        assert_eq!(resolve(&tree, "pkg/sub/mod.py", "pkg.util"), vec!["pkg/util.py"]);
+        assert_eq!(resolve(&tree, "pkg/sub/mod.py", ".:models"), vec!["pkg/sub/models.py"]);
+        assert_eq!(resolve(&tree, "pkg/sub/mod.py", "..core:api,db"), vec!["pkg/core/api.py"]);
+        assert!(resolve(&tree, "pkg/sub/mod.py", "os").is_empty());
+    }
+
+    #[test]
+    fn go_imports_and_resolution() {
+        let src = "package main\n\nimport (\n\t\"fmt\"\n\tsrv \"example.com/app/server\"\n)\nimport \"example.com/app\"\n";
+        assert_eq!(imports("cmd/main.go", src), vec!["example.com/app", "fmt", "example.com/app/server"]);
+        let tree = ["cmd/main.go", "server/a.go", "server/b.go", "server/a_test.go", "root.go"];
+        let mut got = resolve(&tree, "cmd/main.go", "example.com/app/server");
+        got.sort();
+        assert_eq!(got, vec!["server/a.go", "server/b.go"]);
+        assert_eq!(resolve(&tree, "cmd/main.go", "example.com/app"), vec!["root.go"]);
+        assert!(resolve(&tree, "cmd/main.go", "fmt").is_empty());
+    }
+
+    #[test]
+    fn transitive_dependents_follow_chains_and_ignore_cycles() {
+        let all = files(&["a.ts", "b.ts", "c.ts", "d.ts"]);
+        let specs: Vec<(String, String)> = [("b.ts", "./a"), ("c.ts", "./b"), ("d.ts", "./c"), ("a.ts", "./d")]
+            .iter()
+            .map(|(f, s)| (f.to_string(), s.to_string()))
+            .collect();
+        let graph = ImportGraph::build(&all, &specs, None);
+        assert_eq!(graph.direct_dependents("a.ts"), 1);
+        assert_eq!(graph.transitive_dependents("a.ts"), 3);
+    }
+
+    #[test]
+    fn blast_score_grows_with_dependents() {
+        assert!(blast_score(0) < blast_score(1));
+        assert!(blast_score(5) < blast_score(12));
+        assert!(blast_score(12) < blast_score(100));
+    }
+
+    /// Throwaway workspace under the system temp dir, removed on drop.
+    fn changed(paths: &[&str]) -> AnalysisResult {
+        result_with(paths.iter().map(|p| ImpactedFile { why: vec!["+1 -0 lines".into()], ..modified(p, 0.9) }).collect())
+    }
+
+    #[test]
+    fn widely_imported_file_is_flagged_as_high_blast_radius() {
+        let tree = TempDir::new("graph-fan-in");
+        tree.write("core.ts", "export const x = 1;\n");
+        tree.write("leaf.ts", "export const y = 2;\n");
+        for i in 0..HIGH_FAN_IN {
+            tree.write(&format!("users/u{}.ts", i), "import { x } from '../core';\n");
+        }
+        let db = AuditLog::open(":memory:").unwrap();
+        db.refresh_index(&tree.root, is_source, imports).unwrap();
+        let mut result = changed(&["core.ts", "leaf.ts", "README.md"]);
+
+        apply_blast_radius(&db, &tree.root, &mut result).unwrap();
+
+        let core = &result.impacted_files[0];
+        assert_eq!(core.score, blast_score(HIGH_FAN_IN));
+        assert_eq!(core.why[1], format!("imported by {0} file(s), {0} transitively", HIGH_FAN_IN));
+        assert_eq!(result.impacted_files[1].score, blast_score(0));
+        assert_eq!(result.impacted_files[2].score, 0.9, "non-source files keep their score");
+        assert_eq!(result.risk_reasons.len(), 1);
+        assert!(result.risk_reasons[0].starts_with("High blast radius: core.ts"), "got: {:?}", result.risk_reasons);
+    }
+
+    #[test]
+    fn deleted_file_still_imported_is_high_risk() {
+        let tree = TempDir::new("graph-deleted");
+        tree.write("src/main.rs", "mod util;\nmod cli;\nfn main() {}\n");
+        tree.write("src/cli.rs", "use crate::util::helper;\n");
+        let db = AuditLog::open(":memory:").unwrap();
+        db.refresh_index(&tree.root, is_source, imports).unwrap();
+        let mut result = changed(&["src/util.rs"]);
+        result.impacted_files[0].change = "deleted".into();
+
+        apply_blast_radius(&db, &tree.root, &mut result).unwrap();
+
+        assert_eq!(result.risk_level, "high");
+        assert_eq!(result.risk_reasons, vec!["Deleted file src/util.rs is still imported by 2 file(s) (2 transitively)"]);
+        let paths: Vec<_> = result.impacted_files.iter().map(|f| (f.path.as_str(), f.score)).collect();
+        assert_eq!(
+            paths,
+            vec![("src/util.rs", DELETED_BUT_IMPORTED), ("src/cli.rs", DELETED_BUT_IMPORTED), ("src/main.rs", DELETED_BUT_IMPORTED)]
+        );
+        assert_eq!(result.impacted_files[1].why, vec!["imports src/util.rs, which was deleted"]);
+    }
+}
@@ -1,7 +1,9 @@
-use crate::llm::CactusLlm;
-use crate::protocol::{AnalysisResult, ImpactedFile, SuggestedAction};
-use anyhow::Result;
-use super::diff::DiffFile;
+use crate::llm::LlmBackend;
+use crate::protocol::{risk_rank, AnalysisResult, ImpactedFile, SuggestedAction};
+use anyhow::{anyhow, Result};
+use super::diff::{DiffFile, FileStatus, LineKind};
+use super::{pack, symbols};
+use sha2::{Digest, Sha256};
 use tracing::{debug, warn};
 
 /// Tool schema for function-calling mode (no force_tools — model uses fine-tuning to call this).
@@ -32,34 +34,372 @@ summary (array of strings), risk_level (low|med|high), \
 risk_reasons (array of strings), suggested_actions (array of objects with label and explanation). \
 No markdown. No prose. Just the JSON object.";
 
-pub fn build_prompt(files: &[DiffFile], raw_diff: &str) -> String {
-    let file_summary: Vec<String> = files
+/// What shapes the prompt besides the diff itself.
+#[derive(Debug, Clone, Default)]
+pub struct PromptOptions {
+    /// Tokens the whole user prompt may use.
+    pub budget: usize,
+    /// File open in the editor, absolute or repo-relative; its hunks go first.
+    pub active_file: Option<String>,
+}
+
+/// Every changed file with its stats, the definitions the hunks touch (see
+/// [`symbols::extract`]), then as many hunks as fit the token budget (see
+/// [`pack::pack`]), then a stat line for each file whose hunks were left out,
+/// so the model knows what it is not seeing.
+pub fn build_prompt(files: &[DiffFile], options: &PromptOptions, count_tokens: &dyn Fn(&str) -> usize) -> String {
+    let file_summary: Vec<String> = files.iter().map(describe_file).collect();
+    let definitions = describe_symbols(files);
+    let frame = |diff: &str, omitted: &[String]| {
+        let mut prompt = format!("Files changed:\n{}\n\n", file_summary.join("\n"));
+        if !definitions.is_empty() {
+            prompt.push_str(&format!("Definitions changed:\n{}\n\n", definitions.join("\n")));
+        }
+        prompt.push_str(&format!("Diff:\n```\n{}```", diff));
+        if !omitted.is_empty() {
+            prompt.push_str(&format!("\n\nLeft out to fit the context:\n{}", omitted.join("\n")));
+        }
+        prompt
+    };
+
+    // Charge the frame, with every file listed as omitted, before any hunk.
+    let worst_case: Vec<String> = files
         .iter()
-        .map(|f| format!("{} (+{} -{})", f.path, f.added_lines, f.removed_lines))
+        .filter(|f| !f.hunks.is_empty())
+        .map(|f| pack::omitted_stat(f, 0))
         .collect();
+    let overhead = count_tokens(&frame("", &worst_case));
+    let packed = pack::pack(
+        files,
+        options.budget.saturating_sub(overhead),
+        options.active_file.as_deref(),
+        count_tokens,
+    );
+    frame(&packed.diff, &packed.omitted)
+}
 
-    let diff_excerpt = if raw_diff.chars().count() > 3000 {
-        let truncated: String = raw_diff.chars().take(3000).collect();
-        format!("{}...[truncated]", truncated)
-    } else {
-        raw_diff.to_string()
+/// `modified function parse (src/a.rs)` for each definition the hunks touch,
+/// at most `MAX_PROMPT_SYMBOLS` of them, so the model knows what the change
+/// is about even when hunks are left out.
+fn describe_symbols(files: &[DiffFile]) -> Vec<String> {
+    let symbols = symbols::extract(files);
+    let mut lines: Vec<String> = symbols
+        .iter()
+        .take(MAX_PROMPT_SYMBOLS)
+        .map(|s| format!("{} {} {} ({})", s.change, s.kind, s.name, s.file))
+        .collect();
+    if symbols.len() > MAX_PROMPT_SYMBOLS {
+        lines.push(format!("and {} more", symbols.len() - MAX_PROMPT_SYMBOLS));
+    }
+    lines
+}
+
+/// Hash of what `files` change, without hunk positions or blob ids, so an
+/// unchanged save or a return to an earlier state hashes the same.
+pub fn diff_hash(files: &[DiffFile]) -> String {
+    let mut hasher = Sha256::new();
+    for f in files {
+        hasher.update(format!(
+            "{} {} {} {:?} {:?} {} {}\n",
+            f.status.as_str(),
+            f.old_path,
+            f.path,
+            f.old_mode,
+            f.new_mode,
+            f.binary,
+            f.submodule
+        ));
+        for hunk in &f.hunks {
+            hasher.update(format!("@@ {}\n", hunk.section));
+            for line in &hunk.lines {
+                let marker = match line.kind {
+                    LineKind::Context => ' ',
+                    LineKind::Added => '+',
+                    LineKind::Removed => '-',
+                };
+                hasher.update(format!("{}{}\n", marker, line.text));
+            }
+        }
+    }
+    hex(hasher)
+}
+
+/// Key under which the review of `files` by `model` is cached: the diff hash
+/// plus everything else that shapes the prompt. The active file is left out;
+/// it only reorders hunks that do not all fit.
+pub fn cache_key(files: &[DiffFile], model: &str, options: &PromptOptions) -> String {
+    digest("diff", files, model, options)
+}
+
+/// Key under which one file's own review is kept for `analyze_incremental`;
+/// built like `cache_key` but never equal to it.
+pub fn file_key(file: &DiffFile, model: &str, options: &PromptOptions) -> String {
+    digest("file", std::slice::from_ref(file), model, options)
+}
+
+fn digest(scope: &str, files: &[DiffFile], model: &str, options: &PromptOptions) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!(
+        "{} v{}\n{}\n{}\n{}",
+        scope,
+        PROMPT_VERSION,
+        model,
+        options.budget,
+        diff_hash(files)
+    ));
+    hex(hasher)
+}
+
+fn hex(hasher: Sha256) -> String {
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `src/a.rs (+3 -1)`, plus what the line counts alone do not say:
+/// `[renamed from src/b.rs, mode 100644 -> 100755]`.
+fn describe_file(f: &DiffFile) -> String {
+    let notes = change_kinds(f);
+    let counts = format!("{} (+{} -{})", f.path, f.added_lines, f.removed_lines);
+    if notes.is_empty() { counts } else { format!("{} [{}]", counts, notes.join(", ")) }
+}
+
+/// The entry for a file in the diff, explained by how it changed.
+pub fn impacted_file(f: &DiffFile, score: f32) -> ImpactedFile {
+    let mut why = change_kinds(f);
+    if !(f.binary || f.submodule) && (f.added_lines + f.removed_lines > 0 || why.is_empty()) {
+        why.push(format!("+{} -{} lines", f.added_lines, f.removed_lines));
+    }
+    ImpactedFile {
+        path: f.path.clone(),
+        score,
+        why,
+        change: f.status.as_str().to_string(),
+    }
+}
+
+/// Every file in the diff, scored by `normalize_score` of its changed lines.
+/// That score is what a review without a workspace reports (`--once`, or
+/// `analyze_diff` with no `workspace`); with one, `graph::apply_blast_radius`
+/// re-scores source files by how many others import them.
+fn scored_by_size(files: &[DiffFile]) -> Vec<ImpactedFile> {
+    files.iter().map(|f| impacted_file(f, normalize_score(f.added_lines + f.removed_lines))).collect()
+}
+
+/// How a file changed beyond its line counts: created, deleted, renamed,
+/// binary, a submodule bump or a mode change.
+fn change_kinds(f: &DiffFile) -> Vec<String> {
+    let mut kinds = Vec::new();
+    let from = |verb: &str| match f.similarity {
+        Some(n) if n < 100 => format!("{} from {} ({}% similar)", verb, f.old_path, n),
+        _ => format!("{} from {}", verb, f.old_path),
     };
+    if f.submodule {
+        kinds.push(match f.submodule_commits() {
+            (Some(old), Some(new)) => format!("submodule moved from {} to {}", short_sha(old), short_sha(new)),
+            (None, Some(new)) => format!("submodule added at {}", short_sha(new)),
+            (Some(_), None) => "submodule removed".to_string(),
+            (None, None) => "submodule changed".to_string(),
+        });
+    } else {
+        match f.status {
+            FileStatus::Modified => {}
+            FileStatus::Added => kinds.push("new file".to_string()),
+            FileStatus::Deleted => kinds.push("file deleted".to_string()),
+            FileStatus::Renamed => kinds.push(from("renamed")),
+            FileStatus::Copied => kinds.push(from("copied")),
+        }
+        if f.binary {
+            kinds.push("binary file".to_string());
+        }
+    }
+    if let (true, Some(old), Some(new)) = (f.mode_changed(), &f.old_mode, &f.new_mode) {
+        kinds.push(format!("mode {} -> {}", old, new));
+    }
+    kinds
+}
 
-    format!(
-        "Files changed:\n{}\n\nDiff:\n```\n{}\n```",
-        file_summary.join("\n"),
-        diff_excerpt
-    )
+/// `1a2b3c4d5e…-dirty` → `1a2b3c4-dirty`.
+fn short_sha(commit: &str) -> String {
+    let (sha, suffix) = commit.split_at(commit.find('-').unwrap_or(commit.len()));
+    format!("{}{}", sha.get(..7).unwrap_or(sha), suffix)
 }
 
-pub fn analyze(llm: &CactusLlm, files: &[DiffFile], raw_diff: &str) -> Result<AnalysisResult> {
-    let prompt = build_prompt(files, raw_diff);
+/// Bump whenever the prompt, tool schema or result parsing changes in a way
+/// that makes results cached under the old version wrong.
+pub const PROMPT_VERSION: u32 = 2;
+
+/// Definitions listed in the prompt before the rest are summed up as a count.
+const MAX_PROMPT_SYMBOLS: usize = 20;
+
+/// Most per-chunk model calls one analysis may make.
+const MAX_CHUNKS: usize = 6;
+/// Suggested actions kept after merging chunk reviews.
+const MAX_MERGED_ACTIONS: usize = 5;
+/// Files named in the reason added for parts the model failed to review.
+const MAX_NAMED_UNREVIEWED: usize = 3;
+
+/// Intermediate events reported while `analyze` runs, for streaming clients.
+pub enum Progress<'a> {
+    /// The analysis moved to a new stage, e.g. `"reviewing"`.
+    Stage { stage: &'static str, detail: String },
+    /// A chunk of raw model output.
+    Token(&'a str),
+}
+
+/// Review `files` with the model. A diff too big for one prompt is split
+/// into chunks of whole files, one directory kept together where possible;
+/// each chunk is reviewed on its own and the partial results are merged. A
+/// chunk whose review fails is left out and flagged by `mark_unreviewed`.
+/// `is_cancelled` is checked between chunks so a dropped analysis frees the
+/// model early.
+pub fn analyze(
+    llm: &dyn LlmBackend,
+    files: &[DiffFile],
+    options: &PromptOptions,
+    is_cancelled: &dyn Fn() -> bool,
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<AnalysisResult> {
+    analyze_chunks(llm, files, options, is_cancelled, on_progress).map(|(result, _)| result)
+}
+
+/// `analyze`, also saying whether every chunk was reviewed.
+fn analyze_chunks(
+    llm: &dyn LlmBackend,
+    files: &[DiffFile],
+    options: &PromptOptions,
+    is_cancelled: &dyn Fn() -> bool,
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<(AnalysisResult, bool)> {
+    let chunks = chunk_files(files, options, &|text| llm.count_tokens(text));
+    if chunks.len() <= 1 {
+        let result = review(llm, files, options, format!("{} file(s) changed", files.len()), on_progress)?;
+        return Ok((result, true));
+    }
+
+    debug!("diff split into {} chunks", chunks.len());
+    let mut partials = Vec::new();
+    let mut unreviewed = Vec::new();
+    let mut first_error = None;
+    for (i, chunk) in chunks.iter().enumerate() {
+        if is_cancelled() {
+            return Err(anyhow!("analysis cancelled"));
+        }
+        let detail = format!("part {} of {}: {} file(s)", i + 1, chunks.len(), chunk.len());
+        match review(llm, chunk, options, detail, on_progress) {
+            Ok(partial) => partials.push(partial),
+            Err(e) => {
+                warn!("review of part {} failed: {}", i + 1, e);
+                unreviewed.extend(chunk.iter().map(|f| f.path.as_str()));
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    if partials.is_empty() {
+        return Err(first_error.unwrap_or_else(|| anyhow!("no part of the diff could be reviewed")));
+    }
+    on_progress(Progress::Stage {
+        stage: "merging",
+        detail: format!("{} partial review(s)", partials.len()),
+    });
+    let mut result = merge(partials, files);
+    mark_unreviewed(&mut result, &unreviewed, files.len());
+    Ok((result, unreviewed.is_empty()))
+}
+
+/// Per-file reviews kept between analyses, looked up and stored by `file_key`.
+pub struct Pieces<'a> {
+    pub model: &'a str,
+    pub lookup: &'a dyn Fn(&str) -> Option<AnalysisResult>,
+    pub save: &'a dyn Fn(&str, &AnalysisResult),
+}
+
+/// Like `analyze`, but each file is reviewed on its own and its review kept
+/// in `pieces`, so a later save sends only the files whose hunks changed and
+/// merges their reviews with the kept ones. A diff with more unreviewed files
+/// than `MAX_CHUNKS` goes to `analyze` whole, as one call per file would be
+/// too many. The result is marked `cached` when no file needed the model.
+/// Also returns whether every file was reviewed: a result missing files whose
+/// review failed (see `mark_unreviewed`) should not be kept for the whole diff.
+pub fn analyze_incremental(
+    llm: &dyn LlmBackend,
+    files: &[DiffFile],
+    options: &PromptOptions,
+    pieces: &Pieces,
+    is_cancelled: &dyn Fn() -> bool,
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<(AnalysisResult, bool)> {
+    let mut partials = Vec::new();
+    let mut fresh = Vec::new();
+    for file in files {
+        let key = file_key(file, pieces.model, options);
+        match (pieces.lookup)(&key) {
+            Some(piece) => partials.push(piece),
+            None => fresh.push((file, key)),
+        }
+    }
+    if files.is_empty() || fresh.len() > MAX_CHUNKS {
+        return analyze_chunks(llm, files, options, is_cancelled, on_progress);
+    }
+
+    let reused = partials.len();
+    if reused > 0 {
+        on_progress(Progress::Stage {
+            stage: "reused",
+            detail: format!("{} of {} file(s) unchanged since last reviewed", reused, files.len()),
+        });
+    }
+    let mut unreviewed = Vec::new();
+    let mut first_error = None;
+    for (i, (file, key)) in fresh.iter().enumerate() {
+        if is_cancelled() {
+            return Err(anyhow!("analysis cancelled"));
+        }
+        let detail = format!("file {} of {}: {}", i + 1, fresh.len(), file.path);
+        match review(llm, std::slice::from_ref(*file), options, detail, on_progress) {
+            Ok(piece) => {
+                (pieces.save)(key, &piece);
+                partials.push(piece);
+            }
+            Err(e) => {
+                warn!("review of {} failed: {}", file.path, e);
+                unreviewed.push(file.path.as_str());
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    if partials.is_empty() {
+        return Err(first_error.unwrap_or_else(|| anyhow!("no file of the diff could be reviewed")));
+    }
+    if partials.len() == 1 && reused == 0 && unreviewed.is_empty() {
+        return Ok((partials.remove(0), true));
+    }
+    on_progress(Progress::Stage {
+        stage: "merging",
+        detail: format!("{} fresh and {} reused review(s)", partials.len() - reused, reused),
+    });
+    let mut result = merge(partials, files);
+    mark_unreviewed(&mut result, &unreviewed, files.len());
+    result.cached = reused == files.len();
+    Ok((result, unreviewed.is_empty()))
+}
+
+/// One model review of `files`: tool call first, plain JSON as the fallback.
+fn review(
+    llm: &dyn LlmBackend,
+    files: &[DiffFile],
+    options: &PromptOptions,
+    detail: String,
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<AnalysisResult> {
+    let prompt = build_prompt(files, options, &|text| llm.count_tokens(text));
 
     // Use function-calling mode: the functiongemma model is fine-tuned to emit
     // <start_function_call>call:submit_review{...}<end_function_call> when tools
     // are present in the prompt. We do NOT use force_tools (that hangs via
     // set_tool_constraints). The model naturally invokes the tool via fine-tuning.
-    let calls = llm.complete_with_tools(&prompt, REVIEW_TOOL_JSON)?;
+    on_progress(Progress::Stage { stage: "reviewing", detail });
+    let calls = llm.complete_with_tools_streaming(&prompt, REVIEW_TOOL_JSON, &mut |t| {
+        on_progress(Progress::Token(t))
+    })?;
     debug!("tool calls returned: {}", calls.len());
 
     if let Some(call) = calls.iter().find(|call| call["name"].as_str() == Some("submit_review")) {
@@ -69,21 +409,121 @@ pub fn analyze(llm: &CactusLlm, files: &[DiffFile], raw_diff: &str) -> Result<An
 
     // Fallback: model didn't produce a function call — try plain text parse.
     warn!("LLM returned no function calls; trying plain text fallback");
-    let raw = llm.complete(SYSTEM_PROMPT, &prompt)?;
+    on_progress(Progress::Stage {
+        stage: "fallback",
+        detail: "model made no tool call; asking for plain JSON".to_string(),
+    });
+    let raw = llm.complete_streaming(SYSTEM_PROMPT, &prompt, &mut |t| {
+        on_progress(Progress::Token(t))
+    })?;
     debug!("llm text output (fallback): {}", raw);
     Ok(parse_analysis_json(&raw, files))
 }
 
+/// Split `files` into chunks whose prompts each fit the budget, keeping the
+/// files of one directory next to each other. Returns a single chunk when the
+/// whole diff fits. Past `MAX_CHUNKS`, the rest goes into the last chunk and
+/// the packer reports what it had to leave out.
+fn chunk_files(
+    files: &[DiffFile],
+    options: &PromptOptions,
+    count_tokens: &dyn Fn(&str) -> usize,
+) -> Vec<Vec<DiffFile>> {
+    let room = options.budget.saturating_sub(count_tokens(&build_prompt(&[], options, count_tokens)));
+    let size = |f: &DiffFile| {
+        pack::file_tokens(f, count_tokens) + count_tokens(&describe_file(f)) + count_tokens(&pack::omitted_stat(f, 0))
+    };
+    if files.iter().map(size).sum::<usize>() <= room {
+        return vec![files.to_vec()];
+    }
+
+    let mut ordered: Vec<&DiffFile> = files.iter().collect();
+    ordered.sort_by_key(|f| f.path.rsplit_once('/').map_or("", |(dir, _)| dir));
+    let mut chunks: Vec<Vec<DiffFile>> = Vec::new();
+    let mut used = 0;
+    for file in ordered {
+        let size = size(file);
+        let full = chunks.last().is_none_or(|c| !c.is_empty() && used + size > room);
+        if full && chunks.len() < MAX_CHUNKS {
+            chunks.push(Vec::new());
+            used = 0;
+        }
+        chunks.last_mut().expect("a chunk was just pushed").push(file.clone());
+        used += size;
+    }
+    chunks
+}
+
+/// Fold partial reviews into one: the highest risk level wins, the riskiest
+/// parts speak first, repeated points and actions are dropped, and files and
+/// symbols are recomputed over the whole diff.
+fn merge(mut partials: Vec<AnalysisResult>, files: &[DiffFile]) -> AnalysisResult {
+    partials.sort_by_key(|p| std::cmp::Reverse(risk_rank(&p.risk_level)));
+    let risk_level = partials[0].risk_level.clone();
+    let confidence = partials.iter().map(|p| p.confidence).fold(1.0, f32::min);
+
+    let mut summary = Vec::new();
+    let mut risk_reasons = Vec::new();
+    let mut suggested_actions: Vec<SuggestedAction> = Vec::new();
+    for partial in partials {
+        push_unique(&mut summary, partial.summary);
+        push_unique(&mut risk_reasons, partial.risk_reasons);
+        for action in partial.suggested_actions {
+            if !suggested_actions.iter().any(|a| same_text(&a.label, &action.label)) {
+                suggested_actions.push(action);
+            }
+        }
+    }
+    suggested_actions.truncate(MAX_MERGED_ACTIONS);
+
+    AnalysisResult {
+        summary,
+        risk_level,
+        risk_reasons,
+        impacted_files: scored_by_size(files),
+        impacted_symbols: symbols::extract(files),
+        suggested_actions,
+        confidence,
+        cached: false,
+    }
+}
+
+/// Flag a merged review that is missing the `unreviewed` files, whose review
+/// failed: they are named in `risk_reasons`, the level is raised to at least
+/// med since nothing is known about them, and confidence shrinks by the share
+/// of the `total` files they make up.
+fn mark_unreviewed(result: &mut AnalysisResult, unreviewed: &[&str], total: usize) {
+    if unreviewed.is_empty() {
+        return;
+    }
+    let mut named = unreviewed.iter().take(MAX_NAMED_UNREVIEWED).copied().collect::<Vec<_>>().join(", ");
+    if unreviewed.len() > MAX_NAMED_UNREVIEWED {
+        named = format!("{} and {} more", named, unreviewed.len() - MAX_NAMED_UNREVIEWED);
+    }
+    result.risk_reasons.push(format!("Not reviewed, the model failed on: {}", named));
+    if risk_rank(&result.risk_level) < risk_rank("med") {
+        result.risk_level = "med".to_string();
+    }
+    result.confidence *= 1.0 - unreviewed.len() as f32 / total.max(1) as f32;
+}
+
+fn push_unique(into: &mut Vec<String>, items: Vec<String>) {
+    for item in items {
+        if !into.iter().any(|seen| same_text(seen, &item)) {
+            into.push(item);
+        }
+    }
+}
+
+/// Equal ignoring case, surrounding whitespace and a trailing full stop.
+fn same_text(a: &str, b: &str) -> bool {
+    let norm = |s: &str| s.trim().trim_end_matches('.').to_lowercase();
+    norm(a) == norm(b)
+}
+
 /// Build an AnalysisResult from a parsed tool-call arguments object.
 pub fn parse_tool_args(args: &serde_json::Value, files: &[DiffFile]) -> AnalysisResult {
-    let impacted_files = files
-        .iter()
-        .map(|f| ImpactedFile {
-            path: f.path.clone(),
-            score: normalize_score(f.added_lines + f.removed_lines),
-            why: vec![format!("+{} -{} lines", f.added_lines, f.removed_lines)],
-        })
-        .collect();
+    let impacted_files = scored_by_size(files);
 
     AnalysisResult {
         summary: args["summary"]
@@ -96,7 +536,7 @@ pub fn parse_tool_args(args: &serde_json::Value, files: &[DiffFile]) -> Analysis
             .map(|a| a.iter().filter_map(|v| v.as_str().map(String::from)).collect())
             .unwrap_or_default(),
         impacted_files,
-        impacted_symbols: vec![],
+        impacted_symbols: symbols::extract(files),
         suggested_actions: args["suggested_actions"]
             .as_array()
             .map(|a| {
@@ -111,6 +551,7 @@ pub fn parse_tool_args(args: &serde_json::Value, files: &[DiffFile]) -> Analysis
             })
             .unwrap_or_default(),
         confidence: 0.85,
+        cached: false,
     }
 }
 
@@ -127,14 +568,7 @@ pub fn parse_analysis_json(text: &str, files: &[DiffFile]) -> AnalysisResult {
         })
     });
 
-    let impacted_files: Vec<ImpactedFile> = files
-        .iter()
-        .map(|f| ImpactedFile {
-            path: f.path.clone(),
-            score: normalize_score(f.added_lines + f.removed_lines),
-            why: vec![format!("+{} -{} lines", f.added_lines, f.removed_lines)],
-        })
-        .collect();
+    let impacted_files = scored_by_size(files);
 
     AnalysisResult {
         summary: parsed["summary"]
@@ -150,7 +584,7 @@ pub fn parse_analysis_json(text: &str, files: &[DiffFile]) -> AnalysisResult {
             .map(|a| a.iter().filter_map(|v| v.as_str().map(String::from)).collect())
             .unwrap_or_default(),
         impacted_files,
-        impacted_symbols: vec![],
+        impacted_symbols: symbols::extract(files),
         suggested_actions: parsed["suggested_actions"]
             .as_array()
             .map(|a| {
@@ -165,6 +599,7 @@ pub fn parse_analysis_json(text: &str, files: &[DiffFile]) -> AnalysisResult {
             })
             .unwrap_or_default(),
         confidence: parsed["confidence"].as_f64().unwrap_or(0.75) as f32,
+        cached: false,
     }
 }
 
@@ -178,7 +613,8 @@ fn extract_json(raw: &str) -> &str {
     raw
 }
 
-fn normalize_score(lines: usize) -> f32 {
+/// 0.3, 0.6 or 0.9 for a change of `lines` lines: a small, medium or large edit.
+pub fn normalize_score(lines: usize) -> f32 {
     match lines {
         0..=10 => 0.3,
         11..=50 => 0.6,
@@ -189,6 +625,57 @@ fn normalize_score(lines: usize) -> f32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::analyzer::diff::parse_diff;
+    use crate::llm::backend::estimate_tokens;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Answers every review with a tool call: high risk for prompts that
+    /// mention `danger`, low otherwise, and an error for prompts that mention
+    /// `fail_on`. Records each prompt it was sent.
+    #[derive(Default)]
+    struct ChunkBackend {
+        prompts: Mutex<Vec<String>>,
+        fail_on: Option<&'static str>,
+    }
+
+    impl LlmBackend for ChunkBackend {
+        fn complete(&self, _system_prompt: &str, _user_message: &str) -> Result<String> {
+            Err(anyhow!("not used"))
+        }
+
+        fn complete_with_tools(&self, user_message: &str, _tools_json: &str) -> Result<Vec<serde_json::Value>> {
+            self.prompts.lock().unwrap().push(user_message.to_string());
+            if self.fail_on.is_some_and(|marker| user_message.contains(marker)) {
+                return Err(anyhow!("model exploded"));
+            }
+            let risky = user_message.contains("danger");
+            Ok(vec![serde_json::json!({
+                "name": "submit_review",
+                "arguments": {
+                    "summary": [if risky { "rewrote the parser" } else { "tidied helpers" }, "Touched several files."],
+                    "risk_level": if risky { "high" } else { "low" },
+                    "risk_reasons": if risky { vec!["parser rewrite"] } else { vec![] },
+                    "suggested_actions": [{"label": "Run the tests", "explanation": "from a part"}]
+                }
+            })])
+        }
+    }
+
+    /// One file per directory, each about `lines` added lines long.
+    fn spread_diff(paths: &[&str], lines: usize) -> Vec<DiffFile> {
+        let body: String = (0..lines).map(|i| format!("+let v{} = {};\n", i, i)).collect();
+        let diff: String = paths
+            .iter()
+            .map(|p| format!("diff --git a/{0} b/{0}\n@@ -0,0 +1,{1} @@\n{2}", p, lines, body))
+            .collect();
+        parse_diff(&diff)
+    }
+
+    fn prompt_for(files: &[DiffFile]) -> String {
+        let options = PromptOptions { budget: 2048, active_file: None };
+        build_prompt(files, &options, &estimate_tokens)
+    }
 
     #[test]
     fn test_build_prompt_includes_file_names() {
@@ -196,18 +683,246 @@ mod tests {
             path: "src/foo.ts".to_string(),
             added_lines: 5,
             removed_lines: 2,
-            hunks: vec![],
+            ..DiffFile::default()
         }];
-        let prompt = build_prompt(&files, "diff content");
+        let prompt = prompt_for(&files);
         assert!(prompt.contains("src/foo.ts"));
         assert!(prompt.contains("+5 -2"));
     }
 
     #[test]
-    fn test_build_prompt_truncates_large_diff() {
-        let files: Vec<DiffFile> = vec![];
-        let prompt = build_prompt(&files, &"x".repeat(5000));
-        assert!(prompt.contains("[truncated]"));
+    fn test_build_prompt_lists_changed_definitions_before_the_diff() {
+        let files = parse_diff(
+            "diff --git a/src/a.rs b/src/a.rs\n@@ -1,3 +1,3 @@\n fn parse() {\n-    1\n+    2\n }\n",
+        );
+        let prompt = prompt_for(&files);
+        assert!(
+            prompt.contains("\n\nDefinitions changed:\nmodified function parse (src/a.rs)\n\nDiff:\n"),
+            "got: {}",
+            prompt
+        );
+        let plain = prompt_for(&parse_diff("diff --git a/notes.txt b/notes.txt\n@@ -1 +1 @@\n-a\n+b\n"));
+        assert!(!plain.contains("Definitions changed"), "got: {}", plain);
+    }
+
+    #[test]
+    fn test_build_prompt_notes_renames_and_modes() {
+        let files = parse_diff(concat!(
+            "diff --git a/old.rs b/new.rs\nsimilarity index 100%\nrename from old.rs\nrename to new.rs\n",
+            "diff --git a/run.sh b/run.sh\nold mode 100644\nnew mode 100755\n",
+        ));
+        let prompt = prompt_for(&files);
+        assert!(prompt.contains("new.rs (+0 -0) [renamed from old.rs]"), "got: {}", prompt);
+        assert!(prompt.contains("run.sh (+0 -0) [mode 100644 -> 100755]"), "got: {}", prompt);
+    }
+
+    #[test]
+    fn test_impacted_files_explain_change_kinds() {
+        let files = parse_diff(concat!(
+            "diff --git a/gone.rs b/gone.rs\ndeleted file mode 100644\n--- a/gone.rs\n+++ /dev/null\n@@ -1,2 +0,0 @@\n-a\n-b\n",
+            "diff --git a/a.rs b/b.rs\nsimilarity index 90%\nrename from a.rs\nrename to b.rs\n--- a/a.rs\n+++ b/b.rs\n@@ -1 +1 @@\n-x\n+y\n",
+            "diff --git a/img.png b/img.png\nnew file mode 100644\nBinary files /dev/null and b/img.png differ\n",
+            "diff --git a/lib b/lib\nindex 1..2 160000\n--- a/lib\n+++ b/lib\n@@ -1 +1 @@\n-Subproject commit 0123456789\n+Subproject commit abcdef0123-dirty\n",
+        ));
+        let json = r#"{"summary":[],"risk_level":"low","risk_reasons":[],"suggested_actions":[]}"#;
+        let result = parse_analysis_json(json, &files);
+        let why: Vec<_> = result.impacted_files.iter().map(|f| (f.change.as_str(), f.why.clone())).collect();
+        assert_eq!(
+            why,
+            vec![
+                ("deleted", vec!["file deleted".to_string(), "+0 -2 lines".to_string()]),
+                ("renamed", vec!["renamed from a.rs (90% similar)".to_string(), "+1 -1 lines".to_string()]),
+                ("added", vec!["new file".to_string(), "binary file".to_string()]),
+                ("modified", vec!["submodule moved from 0123456 to abcdef0-dirty".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_prompt_fits_budget_and_lists_omitted_files() {
+        let big: String = (0..400).map(|i| format!("+let v{} = {};\n", i, i)).collect();
+        let diff = format!(
+            "diff --git a/big.rs b/big.rs\n@@ -0,0 +1,400 @@\n{}diff --git a/small.rs b/small.rs\n@@ -1 +1 @@\n-a\n+b\n",
+            big
+        );
+        let files = parse_diff(&diff);
+        let options = PromptOptions { budget: 300, active_file: None };
+        let prompt = build_prompt(&files, &options, &estimate_tokens);
+
+        assert!(estimate_tokens(&prompt) <= 300, "prompt is {} tokens", estimate_tokens(&prompt));
+        assert!(prompt.contains("big.rs (+400 -0)"), "omitted files stay in the file list");
+        assert!(prompt.contains("diff --git a/small.rs b/small.rs\n@@ -1,1 +1,1 @@\n-a\n+b\n"), "got: {}", prompt);
+        assert!(prompt.ends_with("Left out to fit the context:\nbig.rs: 1 of 1 hunk(s) omitted, +400 -0 lines"));
+    }
+
+    #[test]
+    fn test_small_diff_is_reviewed_in_one_call() {
+        let llm = ChunkBackend::default();
+        let files = spread_diff(&["a/one.rs", "b/two.rs"], 5);
+        let options = PromptOptions { budget: 2048, active_file: None };
+        let result = analyze(&llm, &files, &options, &|| false, &mut |_| {}).unwrap();
+        assert_eq!(llm.prompts.lock().unwrap().len(), 1);
+        assert_eq!(result.summary, vec!["tidied helpers", "Touched several files."]);
+    }
+
+    #[test]
+    fn test_large_diff_is_reviewed_in_parts_and_merged() {
+        let llm = ChunkBackend::default();
+        let files = spread_diff(&["src/a.rs", "danger/parser.rs", "src/b.rs", "lib/c.rs"], 120);
+        let options = PromptOptions { budget: 1200, active_file: None };
+        let mut stages = Vec::new();
+        let result = analyze(&llm, &files, &options, &|| false, &mut |p| {
+            if let Progress::Stage { stage, detail } = p {
+                stages.push(format!("{}: {}", stage, detail));
+            }
+        })
+        .unwrap();
+
+        let prompts = llm.prompts.lock().unwrap();
+        assert!(prompts.len() > 1, "expected a split, got {} prompt(s)", prompts.len());
+        assert!(prompts.iter().all(|p| estimate_tokens(p) <= 1200));
+        let with_a_and_b = prompts.iter().find(|p| p.contains("a/src/a.rs")).unwrap();
+        assert!(with_a_and_b.contains("a/src/b.rs"), "files of one directory stay together");
+        assert_eq!(stages.last().unwrap(), &format!("merging: {} partial review(s)", prompts.len()));
+
+        assert_eq!(result.risk_level, "high");
+        assert_eq!(result.summary, vec!["rewrote the parser", "Touched several files.", "tidied helpers"]);
+        assert_eq!(result.risk_reasons, vec!["parser rewrite"]);
+        assert_eq!(result.suggested_actions.len(), 1);
+        assert_eq!(result.impacted_files.len(), 4, "files come from the whole diff");
+    }
+
+    #[test]
+    fn test_failed_part_is_named_and_lowers_confidence() {
+        let llm = ChunkBackend { fail_on: Some("broken/"), ..Default::default() };
+        let files = spread_diff(&["a/x.rs", "broken/y.rs", "c/z.rs"], 200);
+        let options = PromptOptions { budget: 1000, active_file: None };
+        let result = analyze(&llm, &files, &options, &|| false, &mut |_| {}).unwrap();
+        assert_eq!(llm.prompts.lock().unwrap().len(), 3, "one part per file");
+
+        assert_eq!(result.risk_level, "med", "an unreviewed part is not low risk");
+        assert_eq!(result.risk_reasons.last().unwrap(), "Not reviewed, the model failed on: broken/y.rs");
+        assert!((result.confidence - 0.85 * 2.0 / 3.0).abs() < 1e-6, "got: {}", result.confidence);
+        assert_eq!(result.impacted_files.len(), 3);
+    }
+
+    #[test]
+    fn test_cancelled_analysis_stops_before_the_next_part() {
+        let llm = ChunkBackend::default();
+        let files = spread_diff(&["a/x.rs", "b/y.rs", "c/z.rs"], 120);
+        let options = PromptOptions { budget: 1000, active_file: None };
+        let err = analyze(&llm, &files, &options, &|| true, &mut |_| {}).unwrap_err();
+        assert!(err.to_string().contains("cancelled"));
+        assert!(llm.prompts.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_chunks_are_capped_and_never_empty() {
+        let paths: Vec<String> = (0..20).map(|i| format!("d{}/f.rs", i)).collect();
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+        let files = spread_diff(&paths, 60);
+        let options = PromptOptions { budget: 600, active_file: None };
+        let chunks = chunk_files(&files, &options, &estimate_tokens);
+        assert_eq!(chunks.len(), MAX_CHUNKS);
+        assert!(chunks.iter().all(|c| !c.is_empty()));
+        assert_eq!(chunks.iter().map(Vec::len).sum::<usize>(), 20);
+    }
+
+    /// Runs `analyze_incremental` against an in-memory piece store, returning
+    /// the result, whether it is complete and how many prompts reached the model.
+    fn incremental(
+        llm: &ChunkBackend,
+        store: &Mutex<HashMap<String, AnalysisResult>>,
+        files: &[DiffFile],
+    ) -> (AnalysisResult, bool, usize) {
+        let before = llm.prompts.lock().unwrap().len();
+        let options = PromptOptions { budget: 2048, active_file: None };
+        let pieces = Pieces {
+            model: "m",
+            lookup: &|key| store.lock().unwrap().get(key).cloned(),
+            save: &|key, piece| {
+                store.lock().unwrap().insert(key.to_string(), piece.clone());
+            },
+        };
+        let (result, complete) = analyze_incremental(llm, files, &options, &pieces, &|| false, &mut |_| {}).unwrap();
+        (result, complete, llm.prompts.lock().unwrap().len() - before)
+    }
+
+    #[test]
+    fn test_incremental_sends_only_changed_files() {
+        let llm = ChunkBackend::default();
+        let store = Mutex::new(HashMap::new());
+        let diff = |parser: &str| {
+            parse_diff(&format!(
+                "diff --git a/src/a.rs b/src/a.rs\n@@ -1 +1 @@\n+let a = 1;\ndiff --git a/danger/p.rs b/danger/p.rs\n@@ -1 +1 @@\n+{}\n",
+                parser
+            ))
+        };
+
+        let (first, complete, sent) = incremental(&llm, &store, &diff("let p = 1;"));
+        assert_eq!(sent, 2);
+        assert!(complete);
+        assert!(!first.cached);
+        assert_eq!(first.risk_level, "high");
+
+        let (second, _, sent) = incremental(&llm, &store, &diff("let p = 2;"));
+        assert_eq!(sent, 1);
+        assert!(llm.prompts.lock().unwrap().last().unwrap().contains("danger/p.rs"));
+        assert!(!llm.prompts.lock().unwrap().last().unwrap().contains("src/a.rs"));
+        assert_eq!(second.summary, vec!["rewrote the parser", "Touched several files.", "tidied helpers"]);
+        assert_eq!(second.impacted_files.len(), 2);
+        assert!(!second.cached);
+
+        let (third, _, sent) = incremental(&llm, &store, &diff("let p = 1;"));
+        assert_eq!(sent, 0);
+        assert!(third.cached);
+        assert_eq!(third.risk_level, "high");
+    }
+
+    #[test]
+    fn test_incremental_flags_files_whose_review_failed() {
+        let llm = ChunkBackend { fail_on: Some("b.rs"), ..Default::default() };
+        let store = Mutex::new(HashMap::new());
+        let (result, complete, sent) = incremental(&llm, &store, &spread_diff(&["src/a.rs", "src/b.rs"], 2));
+        assert_eq!(sent, 2);
+        assert!(!complete);
+        assert_eq!(result.risk_level, "med");
+        assert_eq!(result.risk_reasons, vec!["Not reviewed, the model failed on: src/b.rs"]);
+        assert_eq!(result.confidence, 0.85 * 0.5);
+        assert_eq!(store.lock().unwrap().len(), 1, "only the review that succeeded is kept");
+    }
+
+    #[test]
+    fn test_incremental_reviews_many_new_files_together() {
+        let llm = ChunkBackend::default();
+        let store = Mutex::new(HashMap::new());
+        let paths: Vec<String> = (0..=MAX_CHUNKS).map(|i| format!("src/f{}.rs", i)).collect();
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+        let (_, _, sent) = incremental(&llm, &store, &spread_diff(&paths, 2));
+        assert_eq!(sent, 1);
+        assert!(store.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cache_key_ignores_hunk_positions_but_not_content_or_model() {
+        let options = PromptOptions { budget: 2048, active_file: None };
+        let key = |diff: &str, model: &str| cache_key(&parse_diff(diff), model, &options);
+        let base = key("diff --git a/a.rs b/a.rs\nindex 1..2 100644\n@@ -1 +1 @@\n-x\n+y\n", "m");
+
+        assert_eq!(base, key("diff --git a/a.rs b/a.rs\nindex 3..4 100644\n@@ -9 +9 @@\n-x\n+y\n", "m"));
+        assert_ne!(base, key("diff --git a/a.rs b/a.rs\nindex 1..2 100644\n@@ -1 +1 @@\n-x\n+z\n", "m"));
+        assert_ne!(base, key("diff --git a/a.rs b/a.rs\nindex 1..2 100644\n@@ -1 +1 @@\n-x\n+y\n", "other"));
+        let bigger = PromptOptions { budget: 4096, active_file: None };
+        assert_ne!(base, cache_key(&parse_diff("diff --git a/a.rs b/a.rs\n@@ -1 +1 @@\n-x\n+y\n"), "m", &bigger));
+        let files = parse_diff("diff --git a/a.rs b/a.rs\n@@ -1 +1 @@\n-x\n+y\n");
+        assert_ne!(base, file_key(&files[0], "m", &options));
+    }
+
+    #[test]
+    fn test_same_text_ignores_case_and_full_stop() {
+        assert!(same_text("Add tests.", " add tests"));
+        assert!(!same_text("Add tests", "Add more tests"));
+        assert_eq!(risk_rank("Medium"), risk_rank("med"));
     }
 
     #[test]
@@ -286,20 +1001,20 @@ mod tests {
 
     #[test]
     fn test_build_prompt_with_no_files() {
-        // Empty file list — prompt should still contain the diff.
+        // Empty file list — prompt still has its frame and nothing omitted.
         let files: Vec<DiffFile> = vec![];
-        let prompt = build_prompt(&files, "diff content here");
-        assert!(prompt.contains("diff content here"));
+        let prompt = prompt_for(&files);
         assert!(prompt.contains("Files changed:"));
+        assert!(!prompt.contains("Left out"));
     }
 
     #[test]
     fn test_build_prompt_with_multiple_files() {
         let files = vec![
-            DiffFile { path: "a.ts".into(), added_lines: 1, removed_lines: 0, hunks: vec![] },
-            DiffFile { path: "b.rs".into(), added_lines: 5, removed_lines: 3, hunks: vec![] },
+            DiffFile { path: "a.ts".into(), added_lines: 1, removed_lines: 0, ..DiffFile::default() },
+            DiffFile { path: "b.rs".into(), added_lines: 5, removed_lines: 3, ..DiffFile::default() },
         ];
-        let prompt = build_prompt(&files, "diff");
+        let prompt = prompt_for(&files);
         assert!(prompt.contains("a.ts (+1 -0)"));
         assert!(prompt.contains("b.rs (+5 -3)"));
     }
@@ -308,7 +1023,7 @@ mod tests {
 
     #[test]
     fn test_parse_well_formed_json_extracts_all_fields() {
-        let files = vec![DiffFile { path: "auth.ts".into(), added_lines: 5, removed_lines: 2, hunks: vec![] }];
+        let files = vec![DiffFile { path: "auth.ts".into(), added_lines: 5, removed_lines: 2, ..DiffFile::default() }];
         let json = r#"{"summary":["added input validation"],"risk_level":"med","risk_reasons":["no tests"],"suggested_actions":[{"label":"Add tests","explanation":"Cover new logic"}]}"#;
         let result = parse_analysis_json(json, &files);
         assert_eq!(result.summary, vec!["added input validation"]);
@@ -374,9 +1089,9 @@ mod tests {
     #[test]
     fn test_parse_impacted_files_scored_by_line_count() {
         let files = vec![
-            DiffFile { path: "small.ts".into(), added_lines: 3,  removed_lines: 0,  hunks: vec![] },
-            DiffFile { path: "mid.rs".into(),   added_lines: 10, removed_lines: 10, hunks: vec![] },
-            DiffFile { path: "large.go".into(), added_lines: 50, removed_lines: 30, hunks: vec![] },
+            DiffFile { path: "small.ts".into(), added_lines: 3,  removed_lines: 0, ..DiffFile::default() },
+            DiffFile { path: "mid.rs".into(),   added_lines: 10, removed_lines: 10, ..DiffFile::default() },
+            DiffFile { path: "large.go".into(), added_lines: 50, removed_lines: 30, ..DiffFile::default() },
         ];
         let json = r#"{"summary":[],"risk_level":"low","risk_reasons":[],"suggested_actions":[]}"#;
         let result = parse_analysis_json(json, &files);
@@ -385,9 +1100,21 @@ mod tests {
         assert_eq!(result.impacted_files[2].score, 0.9); // 80 lines → high band
     }
 
+    #[test]
+    fn test_parse_fills_impacted_symbols_from_hunks() {
+        let files = parse_diff("diff --git a/src/lib.rs b/src/lib.rs\n@@ -1 +1 @@\n-fn old() {}\n+fn new() {}\n");
+        let json = r#"{"summary":[],"risk_level":"low","risk_reasons":[],"suggested_actions":[]}"#;
+        let from_text = parse_analysis_json(json, &files);
+        let from_tool = parse_tool_args(&serde_json::json!({}), &files);
+        for result in [from_text, from_tool] {
+            let names: Vec<_> = result.impacted_symbols.iter().map(|s| (s.name.as_str(), s.change.as_str())).collect();
+            assert_eq!(names, vec![("new", "added"), ("old", "deleted")]);
+        }
+    }
+
     #[test]
     fn test_parse_impacted_files_why_label_shows_added_and_removed() {
-        let files = vec![DiffFile { path: "x.ts".into(), added_lines: 7, removed_lines: 3, hunks: vec![] }];
+        let files = vec![DiffFile { path: "x.ts".into(), added_lines: 7, removed_lines: 3, ..DiffFile::default() }];
         let json = r#"{"summary":[],"risk_level":"low","risk_reasons":[],"suggested_actions":[]}"#;
         let result = parse_analysis_json(json, &files);
         assert_eq!(result.impacted_files[0].why, vec!["+7 -3 lines"]);
@@ -420,7 +1147,7 @@ mod tests {
 
     #[test]
     fn test_parse_tool_args_well_formed() {
-        let files = vec![DiffFile { path: "auth.ts".into(), added_lines: 5, removed_lines: 2, hunks: vec![] }];
+        let files = vec![DiffFile { path: "auth.ts".into(), added_lines: 5, removed_lines: 2, ..DiffFile::default() }];
         let args = serde_json::json!({
             "summary": ["added validation"],
             "risk_level": "med",
@@ -449,10 +1176,23 @@ mod tests {
     #[test]
     fn test_parse_tool_args_impacted_files_scored_correctly() {
         let files = vec![
-            DiffFile { path: "big.rs".into(), added_lines: 60, removed_lines: 0, hunks: vec![] },
+            DiffFile { path: "big.rs".into(), added_lines: 60, removed_lines: 0, ..DiffFile::default() },
         ];
         let args = serde_json::json!({"summary":[],"risk_level":"high","risk_reasons":[],"suggested_actions":[]});
         let result = parse_tool_args(&args, &files);
         assert_eq!(result.impacted_files[0].score, 0.9);
     }
+
+    #[test]
+    fn test_without_a_workspace_every_path_scores_by_diff_size() {
+        let files = vec![
+            DiffFile { path: "big.rs".into(), added_lines: 40, removed_lines: 20, ..DiffFile::default() },
+            DiffFile { path: "small.rs".into(), added_lines: 2, removed_lines: 1, ..DiffFile::default() },
+        ];
+        let scores = |r: &AnalysisResult| r.impacted_files.iter().map(|f| f.score).collect::<Vec<_>>();
+        let parsed = parse_analysis_json(r#"{"summary":[],"risk_level":"low"}"#, &files);
+        assert_eq!(scores(&parsed), vec![0.9, 0.3]);
+        let merged = merge(vec![parsed.clone(), parsed], &files);
+        assert_eq!(scores(&merged), vec![0.9, 0.3]);
+    }
 }
@@ -1,2 +1,7 @@
 pub mod diff;
+pub mod graph;
 pub mod impact;
+pub mod pack;
+pub mod references;
+pub mod rules;
+pub mod symbols;
@@ -0,0 +1,213 @@
+use super::diff::{DiffFile, Hunk, LineKind};
+use super::impact::normalize_score;
+use super::symbols::{hunk_weight, is_source};
+
+/// Extra rank for hunks in the file the user is looking at: they go first.
+const ACTIVE_FILE_BONUS: f32 = 1.0;
+
+/// The hunks that fit a token budget, rendered as a unified diff, and a
+/// one-line stat for each file that lost some or all of its hunks.
+#[derive(Debug, Default)]
+pub struct Packed {
+    pub diff: String,
+    pub omitted: Vec<String>,
+}
+
+struct Candidate {
+    file: usize,
+    hunk: usize,
+    rank: f32,
+    tokens: usize,
+}
+
+/// Choose hunks from `files` worth at most `budget` tokens, as counted by
+/// `count_tokens`. Hunks are ranked by the risk of their file, the weight of
+/// the definitions they touch, and whether they are in `active_file`; the
+/// chosen ones are rendered in diff order so each file still reads top down.
+pub fn pack(
+    files: &[DiffFile],
+    budget: usize,
+    active_file: Option<&str>,
+    count_tokens: &dyn Fn(&str) -> usize,
+) -> Packed {
+    let headers: Vec<usize> = files.iter().map(|f| count_tokens(&file_header(f))).collect();
+    let mut candidates: Vec<Candidate> = Vec::new();
+    for (fi, file) in files.iter().enumerate() {
+        let risk = file_risk(file);
+        let active = active_file.is_some_and(|a| is_active(a, &file.path));
+        for (hi, hunk) in file.hunks.iter().enumerate() {
+            let rank = risk + hunk_weight(&file.path, hunk) + if active { ACTIVE_FILE_BONUS } else { 0.0 };
+            let tokens = count_tokens(&render_hunk(hunk));
+            candidates.push(Candidate { file: fi, hunk: hi, rank, tokens });
+        }
+    }
+    // Highest rank first; a stable sort keeps diff order among equals.
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by(|&a, &b| candidates[b].rank.total_cmp(&candidates[a].rank));
+
+    // Smaller hunks further down may still fit after a big one is skipped.
+    let mut chosen = vec![false; candidates.len()];
+    let mut started = vec![false; files.len()];
+    let mut left = budget;
+    for i in order {
+        let c = &candidates[i];
+        let cost = c.tokens + if started[c.file] { 0 } else { headers[c.file] };
+        if cost <= left {
+            left -= cost;
+            chosen[i] = true;
+            started[c.file] = true;
+        }
+    }
+
+    let mut packed = Packed::default();
+    for (fi, file) in files.iter().enumerate() {
+        let picked: Vec<&Hunk> = candidates
+            .iter()
+            .zip(&chosen)
+            .filter(|(c, &taken)| c.file == fi && taken)
+            .map(|(c, _)| &file.hunks[c.hunk])
+            .collect();
+        if !picked.is_empty() {
+            packed.diff.push_str(&file_header(file));
+            for hunk in &picked {
+                packed.diff.push_str(&render_hunk(hunk));
+            }
+        }
+        if picked.len() < file.hunks.len() {
+            packed.omitted.push(omitted_stat(file, picked.len()));
+        }
+    }
+    packed
+}
+
+/// Tokens `file` takes when every hunk is shown.
+pub fn file_tokens(file: &DiffFile, count_tokens: &dyn Fn(&str) -> usize) -> usize {
+    count_tokens(&file_header(file)) + file.hunks.iter().map(|h| count_tokens(&render_hunk(h))).sum::<usize>()
+}
+
+/// Source files weigh by size of change; lockfiles, docs and data weigh half.
+fn file_risk(file: &DiffFile) -> f32 {
+    let score = normalize_score(file.added_lines + file.removed_lines);
+    if is_source(&file.path) { score } else { score / 2.0 }
+}
+
+/// The editor reports an absolute path; the diff has repo-relative ones.
+fn is_active(active: &str, path: &str) -> bool {
+    active == path || active.strip_suffix(path).is_some_and(|prefix| prefix.ends_with('/'))
+}
+
+fn file_header(file: &DiffFile) -> String {
+    format!("diff --git a/{} b/{}\n", file.old_path, file.path)
+}
+
+fn render_hunk(hunk: &Hunk) -> String {
+    let mut out = format!(
+        "@@ -{},{} +{},{} @@",
+        hunk.old_start, hunk.old_count, hunk.new_start, hunk.new_count
+    );
+    if !hunk.section.is_empty() {
+        out.push(' ');
+        out.push_str(&hunk.section);
+    }
+    out.push('\n');
+    for line in &hunk.lines {
+        out.push(match line.kind {
+            LineKind::Context => ' ',
+            LineKind::Added => '+',
+            LineKind::Removed => '-',
+        });
+        out.push_str(&line.text);
+        out.push('\n');
+        if line.no_newline {
+            out.push_str("\\ No newline at end of file\n");
+        }
+    }
+    out
+}
+
+/// `src/big.rs: 3 of 7 hunk(s) omitted, +120 -40 lines`. Longest when
+/// `shown` is 0, so callers can reserve room for it up front.
+pub fn omitted_stat(file: &DiffFile, shown: usize) -> String {
+    let total = file.hunks.len();
+    format!(
+        "{}: {} of {} hunk(s) omitted, +{} -{} lines",
+        file.path,
+        total - shown,
+        total,
+        file.added_lines,
+        file.removed_lines
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::diff::parse_diff;
+    use crate::llm::backend::estimate_tokens;
+
+    fn body(n: usize) -> String {
+        (0..n).map(|i| format!("+line {}\n", i)).collect()
+    }
+
+    fn hunk(start: usize, lines: usize) -> String {
+        format!("@@ -{0},0 +{0},{1} @@\n{2}", start, lines, body(lines))
+    }
+
+    #[test]
+    fn everything_fits_a_generous_budget() {
+        let files = parse_diff("diff --git a/a.rs b/a.rs\n@@ -1 +1 @@ fn a()\n-x\n+y\n\\ No newline at end of file\n");
+        let packed = pack(&files, 10_000, None, &estimate_tokens);
+        assert_eq!(packed.diff, "diff --git a/a.rs b/a.rs\n@@ -1,1 +1,1 @@ fn a()\n-x\n+y\n\\ No newline at end of file\n");
+        assert!(packed.omitted.is_empty());
+    }
+
+    #[test]
+    fn stays_within_budget_and_reports_what_was_left_out() {
+        let diff = format!(
+            "diff --git a/big.rs b/big.rs\n{}{}diff --git a/small.rs b/small.rs\n{}",
+            hunk(1, 200),
+            hunk(500, 5),
+            hunk(1, 3)
+        );
+        let files = parse_diff(&diff);
+        let packed = pack(&files, 200, None, &estimate_tokens);
+
+        assert!(estimate_tokens(&packed.diff) <= 200, "got {} tokens", estimate_tokens(&packed.diff));
+        assert!(packed.diff.contains("@@ -500,0 +500,5 @@"), "got: {}", packed.diff);
+        assert!(packed.diff.contains("diff --git a/small.rs b/small.rs"));
+        assert_eq!(packed.omitted, vec!["big.rs: 1 of 2 hunk(s) omitted, +205 -0 lines"]);
+    }
+
+    #[test]
+    fn active_file_and_definitions_outrank_size() {
+        let diff = format!(
+            "diff --git a/docs/notes.md b/docs/notes.md\n{}diff --git a/src/lib.rs b/src/lib.rs\n@@ -1,1 +1,1 @@\n-fn run() {{}}\n+fn run() {{ go() }}\ndiff --git a/src/cli.rs b/src/cli.rs\n@@ -1,1 +1,1 @@\n-// a\n+// b\n",
+            hunk(1, 60)
+        );
+        let files = parse_diff(&diff);
+        let tight = |active| pack(&files, 25, active, &estimate_tokens).diff;
+
+        let diff = tight(None);
+        assert!(diff.starts_with("diff --git a/src/lib.rs"), "got: {}", diff);
+        assert!(!diff.contains("notes.md") && !diff.contains("cli.rs"));
+
+        let diff = tight(Some("/home/me/repo/src/cli.rs"));
+        assert!(diff.starts_with("diff --git a/src/cli.rs"), "got: {}", diff);
+        assert!(!diff.contains("lib.rs"));
+    }
+
+    #[test]
+    fn zero_budget_omits_every_hunk() {
+        let files = parse_diff(&format!("diff --git a/a.rs b/a.rs\n{}{}", hunk(1, 2), hunk(10, 2)));
+        let packed = pack(&files, 0, None, &estimate_tokens);
+        assert!(packed.diff.is_empty());
+        assert_eq!(packed.omitted, vec!["a.rs: 2 of 2 hunk(s) omitted, +4 -0 lines"]);
+    }
+
+    #[test]
+    fn active_file_matches_on_path_boundary() {
+        assert!(is_active("src/a.rs", "src/a.rs"));
+        assert!(is_active("/repo/src/a.rs", "src/a.rs"));
+        assert!(!is_active("/repo/xsrc/a.rs", "src/a.rs"));
+    }
+}
@@ -0,0 +1,214 @@
+use super::symbols::same_language;
+use crate::protocol::{AnalysisResult, ImpactedFile, ImpactedSymbol};
+use crate::store::index::MIN_NAME_LEN;
+use crate::store::AuditLog;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::debug;
+
+/// Callers beyond this many add noise rather than insight.
+const MAX_CALLERS: usize = 20;
+
+/// References found in one caller file.
+#[derive(Default)]
+struct Caller {
+    refs: usize,
+    distance: usize,
+    breaks: bool,
+    why: Vec<String>,
+}
+
+/// Find files in the workspace at `root` that reference the modified or
+/// deleted symbols in `result`, and append them to `impacted_files` after the
+/// files from the diff. Looks names up in the index in `db`, which the caller
+/// refreshes first. Blocking.
+pub fn add_callers(db: &AuditLog, root: &Path, result: &mut AnalysisResult) -> Result<()> {
+    let targets: Vec<(&ImpactedSymbol, &str)> = result
+        .impacted_symbols
+        .iter()
+        .filter(|s| s.change != "added")
+        .map(|s| (s, bare_name(&s.name)))
+        .filter(|(_, name)| name.chars().count() >= MIN_NAME_LEN)
+        .collect();
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    let mut callers: BTreeMap<String, Caller> = BTreeMap::new();
+    for (symbol, name) in targets {
+        for (path, refs) in db.references(root, name)? {
+            if !same_language(&path, &symbol.file) || result.impacted_files.iter().any(|f| f.path == path) {
+                continue;
+            }
+            let distance = distance(&path, &symbol.file);
+            let caller = callers.entry(path).or_insert_with(|| Caller {
+                distance: usize::MAX,
+                ..Caller::default()
+            });
+            caller.refs += refs;
+            caller.distance = caller.distance.min(distance);
+            caller.breaks |= symbol.change == "deleted";
+            let verb = if matches!(symbol.kind.as_str(), "function" | "method") { "calls" } else { "uses" };
+            let how = if symbol.change == "deleted" { "deleted" } else { "changed" };
+            caller.why.push(format!("{} `{}` {} in {}", verb, name, how, symbol.file));
+        }
+    }
+    debug!("reference search found {} caller file(s)", callers.len());
+
+    let mut found: Vec<ImpactedFile> = callers
+        .into_iter()
+        .map(|(path, c)| ImpactedFile {
+            path,
+            score: caller_score(c.refs, c.distance, c.breaks),
+            why: c.why,
+            change: String::new(),
+        })
+        .collect();
+    // Highest score first; BTreeMap order keeps ties stable by path.
+    found.sort_by(|a, b| b.score.total_cmp(&a.score));
+    found.truncate(MAX_CALLERS);
+    result.impacted_files.extend(found);
+    Ok(())
+}
+
+/// `Store::len` → `len`, `Panel.show` → `show`.
+fn bare_name(name: &str) -> &str {
+    name.rsplit(['.', ':']).next().unwrap_or(name)
+}
+
+/// Directory hops between two repo-relative file paths: 0 for siblings.
+fn distance(a: &str, b: &str) -> usize {
+    let dirs = |p: &str| -> Vec<String> {
+        let mut parts: Vec<String> = p.split('/').map(String::from).collect();
+        parts.pop();
+        parts
+    };
+    let (a, b) = (dirs(a), dirs(b));
+    let common = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    (a.len() - common) + (b.len() - common)
+}
+
+/// More references and a closer caller mean a likelier break; a caller of a
+/// deleted symbol will not compile at all.
+fn caller_score(refs: usize, distance: usize, breaks: bool) -> f32 {
+    let base = match refs {
+        1 => 0.3,
+        2..=5 => 0.45,
+        _ => 0.6,
+    };
+    let nearby = match distance {
+        0 => 0.2,
+        1..=2 => 0.1,
+        _ => 0.0,
+    };
+    let broken = if breaks { 0.2 } else { 0.0 };
+    f32::min(base + nearby + broken, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::symbols::is_source;
+    use crate::test_support::{modified, result_with, TempDir};
+    use std::fs;
+
+    fn indexed(root: &Path) -> AuditLog {
+        let db = AuditLog::open(":memory:").unwrap();
+        db.refresh_index(root, is_source, |_, _| Vec::new()).unwrap();
+        db
+    }
+
+    fn result_for(file: &str, symbols: &[(&str, &str, &str)]) -> AnalysisResult {
+        AnalysisResult {
+            impacted_symbols: symbols
+                .iter()
+                .map(|(name, kind, change)| ImpactedSymbol {
+                    name: name.to_string(),
+                    kind: kind.to_string(),
+                    file: file.into(),
+                    score: 0.6,
+                    change: change.to_string(),
+                })
+                .collect(),
+            ..result_with(vec![modified(file, 0.3)])
+        }
+    }
+
+    #[test]
+    fn finds_callers_and_explains_why() {
+        let tree = TempDir::new("refs-callers");
+        tree.write("src/analyzer/diff.rs", "pub fn parse_diff() {}\n");
+        tree.write("src/main.rs", "fn main() { analyzer::diff::parse_diff(); parse_diff(); }\n");
+        tree.write("src/other.rs", "fn unrelated() {}\n");
+        let mut result = result_for("src/analyzer/diff.rs", &[("parse_diff", "function", "modified")]);
+
+        add_callers(&indexed(&tree.root), &tree.root, &mut result).unwrap();
+
+        assert_eq!(result.impacted_files.len(), 2, "got: {:?}", result.impacted_files);
+        let caller = &result.impacted_files[1];
+        assert_eq!(caller.path, "src/main.rs");
+        assert_eq!(caller.why, vec!["calls `parse_diff` changed in src/analyzer/diff.rs"]);
+        assert_eq!(caller.score, caller_score(2, 1, false));
+    }
+
+    #[test]
+    fn respects_gitignore_and_language() {
+        let tree = TempDir::new("refs-ignored");
+        fs::create_dir_all(tree.root.join(".git")).unwrap();
+        tree.write(".gitignore", "target/\n");
+        tree.write("lib.rs", "pub struct Config;\n");
+        tree.write("target/gen.rs", "use crate::Config;\n");
+        tree.write("web/app.ts", "const Config = 1;\n");
+        let mut result = result_for("lib.rs", &[("Config", "type", "modified")]);
+
+        add_callers(&indexed(&tree.root), &tree.root, &mut result).unwrap();
+
+        assert_eq!(result.impacted_files.len(), 1, "got: {:?}", result.impacted_files);
+    }
+
+    #[test]
+    fn added_and_short_symbols_are_not_searched() {
+        let tree = TempDir::new("refs-skipped");
+        tree.write("a.rs", "fn new() {} fn fresh() {}\n");
+        tree.write("b.rs", "fn x() { new(); fresh(); }\n");
+        let mut result = result_for("a.rs", &[("new", "function", "modified"), ("fresh", "function", "added")]);
+
+        add_callers(&indexed(&tree.root), &tree.root, &mut result).unwrap();
+
+        assert_eq!(result.impacted_files.len(), 1);
+    }
+
+    #[test]
+    fn callers_of_deleted_symbols_rank_first() {
+        let tree = TempDir::new("refs-deleted");
+        tree.write("core/api.py", "def fetch(): pass\ndef store(): pass\n");
+        tree.write("far/away/uses_store.py", "store()\n");
+        tree.write("far/away/uses_fetch.py", "fetch()\n");
+        let mut result = result_for(
+            "core/api.py",
+            &[("fetch", "function", "modified"), ("Repo.store", "method", "deleted")],
+        );
+
+        add_callers(&indexed(&tree.root), &tree.root, &mut result).unwrap();
+
+        let paths: Vec<_> = result.impacted_files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["core/api.py", "far/away/uses_store.py", "far/away/uses_fetch.py"]);
+        assert_eq!(result.impacted_files[1].why, vec!["calls `store` deleted in core/api.py"]);
+    }
+
+    #[test]
+    fn distance_counts_directory_hops() {
+        assert_eq!(distance("src/a.rs", "src/b.rs"), 0);
+        assert_eq!(distance("src/x/a.rs", "src/b.rs"), 1);
+        assert_eq!(distance("web/a.ts", "src/b.ts"), 2);
+        assert_eq!(distance("a.rs", "b.rs"), 0);
+    }
+
+    #[test]
+    fn caller_score_grows_with_refs_and_proximity() {
+        assert!(caller_score(1, 5, false) < caller_score(3, 5, false));
+        assert!(caller_score(3, 5, false) < caller_score(3, 0, false));
+        assert!(caller_score(10, 0, true) <= 1.0);
+    }
+}
@@ -0,0 +1,53 @@
+use crate::config::RiskRule;
+use crate::protocol::{risk_rank, AnalysisResult};
+
+/// Raise `result` to the level of every rule that one of the changed `paths`
+/// matches, and add the rule's reason. Rules never lower what the model said.
+pub fn apply(result: &mut AnalysisResult, paths: &[String], rules: &[RiskRule]) {
+    for rule in rules {
+        let matched: Vec<&str> = paths.iter().map(String::as_str).filter(|p| rule.paths.is_match(p)).collect();
+        let Some(first) = matched.first() else { continue };
+        if risk_rank(&rule.level) > risk_rank(&result.risk_level) {
+            result.risk_level = rule.level.clone();
+        }
+        let reason = match rule.reason.as_str() {
+            "" => format!("Matches risk rule `{}`", rule.paths.matching(first).unwrap_or_default()),
+            reason => reason.to_string(),
+        };
+        let reason = match matched.len() {
+            1 => format!("{} ({})", reason, first),
+            n => format!("{} ({} and {} more)", reason, first, n - 1),
+        };
+        if !result.risk_reasons.contains(&reason) {
+            result.risk_reasons.push(reason);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::config;
+
+    fn rules(toml: &str) -> Vec<RiskRule> {
+        config("rules", toml).analysis.risk_rules
+    }
+
+    #[test]
+    fn matching_rule_raises_level_and_explains() {
+        let rules = rules(
+            "[[risk]]\npaths = [\"migrations/**\"]\nlevel = \"high\"\nreason = \"Schema change\"\n\n\
+             [[risk]]\npaths = [\"*.md\"]\nlevel = \"med\"\n",
+        );
+        let mut result = AnalysisResult { risk_level: "low".to_string(), ..Default::default() };
+        let paths = ["migrations/001.sql", "migrations/002.sql", "src/lib.rs"].map(String::from);
+        apply(&mut result, &paths, &rules);
+        assert_eq!(result.risk_level, "high");
+        assert_eq!(result.risk_reasons, vec!["Schema change (migrations/001.sql and 1 more)"]);
+
+        let mut result = AnalysisResult { risk_level: "high".to_string(), ..Default::default() };
+        apply(&mut result, &["README.md".to_string()], &rules);
+        assert_eq!(result.risk_level, "high", "rules never lower the level");
+        assert_eq!(result.risk_reasons, vec!["Matches risk rule `*.md` (README.md)"]);
+    }
+}
@@ -0,0 +1,716 @@
+use super::diff::{DiffFile, Hunk, LineKind};
+use crate::protocol::ImpactedSymbol;
+use std::collections::HashSet;
+use tree_sitter::{Language, Node, Parser};
+
+/// Languages we can extract symbols from, picked by file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lang {
+    Rust,
+    TypeScript,
+    Tsx,
+    Python,
+    Go,
+}
+
+impl Lang {
+    fn from_path(path: &str) -> Option<Self> {
+        match path.rsplit_once('.')?.1 {
+            "rs" => Some(Lang::Rust),
+            "ts" | "mts" | "cts" | "js" | "mjs" | "cjs" => Some(Lang::TypeScript),
+            "tsx" | "jsx" => Some(Lang::Tsx),
+            "py" => Some(Lang::Python),
+            "go" => Some(Lang::Go),
+            _ => None,
+        }
+    }
+
+    fn grammar(self) -> Language {
+        match self {
+            Lang::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Lang::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Lang::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Lang::Python => tree_sitter_python::LANGUAGE.into(),
+            Lang::Go => tree_sitter_go::LANGUAGE.into(),
+        }
+    }
+}
+
+/// Whether `path` is in a language we extract symbols from.
+pub fn is_source(path: &str) -> bool {
+    Lang::from_path(path).is_some()
+}
+
+/// Whether a reference in the file at `a` could resolve to a symbol defined in
+/// the file at `b`. TypeScript, TSX and JavaScript count as one language.
+pub fn same_language(a: &str, b: &str) -> bool {
+    let family = |path: &str| Lang::from_path(path).map(|l| if l == Lang::Tsx { Lang::TypeScript } else { l });
+    matches!((family(a), family(b)), (Some(x), Some(y)) if x == y)
+}
+
+/// How a symbol was touched by the diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl Change {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Modified => "modified",
+            Change::Deleted => "deleted",
+        }
+    }
+
+    /// Deleting a symbol can break callers outright; adding one cannot.
+    fn score(self) -> f32 {
+        match self {
+            Change::Added => 0.3,
+            Change::Modified => 0.6,
+            Change::Deleted => 0.9,
+        }
+    }
+
+    fn merge(self, other: Change) -> Change {
+        if self == other { self } else { Change::Modified }
+    }
+}
+
+/// A definition found in one side of a hunk. Rows are relative to that side.
+#[derive(Debug)]
+struct Def {
+    name: String,
+    kind: &'static str,
+    start_row: usize,
+    end_row: usize,
+}
+
+/// One side (old or new) of a hunk, reassembled as source text.
+#[derive(Default)]
+struct Side {
+    text: String,
+    /// Rows of `text` that the diff changed (removed on the old side, added on the new).
+    changed: Vec<usize>,
+    rows: usize,
+}
+
+impl Side {
+    fn push(&mut self, line: &str, changed: bool) {
+        if changed && !line.trim().is_empty() {
+            self.changed.push(self.rows);
+        }
+        self.text.push_str(line);
+        self.text.push('\n');
+        self.rows += 1;
+    }
+}
+
+/// Work out which functions, methods, types and constants each file's hunks
+/// added, modified or deleted. Purely syntactic — runs before the LLM and
+/// gives the same answer for the same diff.
+pub fn extract(files: &[DiffFile]) -> Vec<ImpactedSymbol> {
+    let mut parser = Parser::new();
+    let mut out = Vec::new();
+
+    for file in files {
+        let Some(lang) = Lang::from_path(&file.path) else { continue };
+        if parser.set_language(&lang.grammar()).is_err() {
+            continue;
+        }
+
+        // (name, kind, change) in first-seen order; merged across hunks.
+        let mut found: Vec<(String, &'static str, Change)> = Vec::new();
+        for hunk in &file.hunks {
+            for (name, kind, change) in hunk_symbols(&mut parser, lang, hunk) {
+                match found.iter_mut().find(|(n, k, _)| *n == name && *k == kind) {
+                    Some(entry) => entry.2 = entry.2.merge(change),
+                    None => found.push((name, kind, change)),
+                }
+            }
+        }
+
+        out.extend(found.into_iter().map(|(name, kind, change)| ImpactedSymbol {
+            name,
+            kind: kind.to_string(),
+            file: file.path.clone(),
+            score: change.score(),
+            change: change.as_str().to_string(),
+        }));
+    }
+
+    out
+}
+
+/// How much one hunk matters by the definitions it touches: the score of
+/// its weightiest change (deleting beats modifying beats adding), or 0.0 when
+/// it touches none or the language is not supported.
+pub fn hunk_weight(path: &str, hunk: &Hunk) -> f32 {
+    let Some(lang) = Lang::from_path(path) else { return 0.0 };
+    let mut parser = Parser::new();
+    if parser.set_language(&lang.grammar()).is_err() {
+        return 0.0;
+    }
+    hunk_symbols(&mut parser, lang, hunk)
+        .into_iter()
+        .map(|(_, _, change)| change.score())
+        .fold(0.0, f32::max)
+}
+
+fn hunk_symbols(parser: &mut Parser, lang: Lang, hunk: &Hunk) -> Vec<(String, &'static str, Change)> {
+    let mut old = Side::default();
+    let mut new = Side::default();
+    for line in &hunk.lines {
+        match line.kind {
+            LineKind::Added => new.push(&line.text, true),
+            LineKind::Removed => old.push(&line.text, true),
+            LineKind::Context => {
+                old.push(&line.text, false);
+                new.push(&line.text, false);
+            }
+        }
+    }
+
+    let old_defs = definitions(parser, lang, &old.text);
+    let new_defs = definitions(parser, lang, &new.text);
+    let (old_hit, old_orphans) = touched(&old_defs, &old.changed);
+    let (new_hit, new_orphans) = touched(&new_defs, &new.changed);
+
+    let has = |defs: &[Def], d: &Def| defs.iter().any(|o| o.name == d.name && o.kind == d.kind);
+    let mut symbols: Vec<(String, &'static str, Change)> = Vec::new();
+    let mut record = |name: &str, kind: &'static str, change: Change| {
+        match symbols.iter_mut().find(|(n, k, _)| n == name && *k == kind) {
+            Some(entry) => entry.2 = entry.2.merge(change),
+            None => symbols.push((name.to_string(), kind, change)),
+        }
+    };
+
+    for d in new_hit.iter().map(|&i| &new_defs[i]) {
+        let change = if has(&old_defs, d) { Change::Modified } else { Change::Added };
+        record(&d.name, d.kind, change);
+    }
+    for d in old_hit.iter().map(|&i| &old_defs[i]) {
+        let change = if has(&new_defs, d) { Change::Modified } else { Change::Deleted };
+        record(&d.name, d.kind, change);
+    }
+
+    // Changed lines inside a definition whose start lies outside the hunk:
+    // git names the enclosing definition in the hunk header.
+    if old_orphans || new_orphans {
+        if let Some((name, kind)) = header_symbol(lang, &hunk.section) {
+            record(&name, kind, Change::Modified);
+        }
+    }
+
+    symbols
+}
+
+/// Indices of `defs` that contain a changed row (innermost definition wins),
+/// and whether any changed row fell outside every definition.
+fn touched(defs: &[Def], changed: &[usize]) -> (Vec<usize>, bool) {
+    let mut hit = Vec::new();
+    let mut orphans = false;
+    for &row in changed {
+        let innermost = defs
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| d.start_row <= row && row <= d.end_row)
+            .min_by_key(|(_, d)| d.end_row - d.start_row)
+            .map(|(i, _)| i);
+        match innermost {
+            Some(i) if !hit.contains(&i) => hit.push(i),
+            Some(_) => {}
+            None => orphans = true,
+        }
+    }
+    hit.sort_unstable();
+    (hit, orphans)
+}
+
+fn definitions(parser: &mut Parser, lang: Lang, text: &str) -> Vec<Def> {
+    let Some(tree) = parser.parse(text, None) else { return Vec::new() };
+    let mut defs = Vec::new();
+    collect(tree.root_node(), lang, text.as_bytes(), &mut defs);
+
+    // A fragment can repeat a name (overloads, cfg'd items); keep the first.
+    let mut seen = HashSet::new();
+    defs.retain(|d| seen.insert((d.name.clone(), d.kind, d.start_row)));
+    defs
+}
+
+fn collect(node: Node, lang: Lang, src: &[u8], defs: &mut Vec<Def>) {
+    for (name, kind) in classify(node, lang, src) {
+        defs.push(Def {
+            name,
+            kind,
+            start_row: node.start_position().row,
+            end_row: node.end_position().row,
+        });
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect(child, lang, src, defs);
+    }
+}
+
+fn text<'a>(node: Node, src: &'a [u8]) -> &'a str {
+    node.utf8_text(src).unwrap_or("")
+}
+
+fn field_text(node: Node, field: &str, src: &[u8]) -> Option<String> {
+    node.child_by_field_name(field).map(|n| text(n, src).to_string())
+}
+
+/// Strip generics, references and pointer markers from a type name.
+fn bare_type(raw: &str) -> &str {
+    let raw = raw.trim_start_matches(['&', '*']).trim();
+    let end = raw.find(['<', '[']).unwrap_or(raw.len());
+    raw[..end].trim()
+}
+
+/// Name of the nearest ancestor of `kind` (e.g. the impl or class around a method).
+fn enclosing(node: Node, kinds: &[&str], field: &str, src: &[u8]) -> Option<String> {
+    let mut cur = node.parent();
+    while let Some(n) = cur {
+        if kinds.contains(&n.kind()) {
+            return field_text(n, field, src).map(|t| bare_type(&t).to_string());
+        }
+        cur = n.parent();
+    }
+    None
+}
+
+/// The symbol(s) `node` defines, if it is a definition we report.
+fn classify(node: Node, lang: Lang, src: &[u8]) -> Vec<(String, &'static str)> {
+    let named = |kind: &'static str| field_text(node, "name", src).map(|n| vec![(n, kind)]).unwrap_or_default();
+
+    match (lang, node.kind()) {
+        (Lang::Rust, "function_item" | "function_signature_item") => {
+            let Some(name) = field_text(node, "name", src) else { return vec![] };
+            match enclosing(node, &["impl_item", "trait_item"], "type", src)
+                .or_else(|| enclosing(node, &["trait_item"], "name", src))
+            {
+                Some(owner) => vec![(format!("{}::{}", owner, name), "method")],
+                None if node.kind() == "function_item" => vec![(name, "function")],
+                None => vec![(name, "method")],
+            }
+        }
+        (Lang::Rust, "struct_item" | "enum_item" | "union_item" | "trait_item" | "type_item") => named("type"),
+        (Lang::Rust, "const_item" | "static_item") => named("constant"),
+
+        (Lang::TypeScript | Lang::Tsx, "function_declaration" | "generator_function_declaration") => named("function"),
+        (
+            Lang::TypeScript | Lang::Tsx,
+            "class_declaration" | "abstract_class_declaration" | "interface_declaration"
+            | "type_alias_declaration" | "enum_declaration",
+        ) => named("type"),
+        (Lang::TypeScript | Lang::Tsx, "method_definition") => {
+            let Some(name) = field_text(node, "name", src) else { return vec![] };
+            match enclosing(node, &["class_declaration", "abstract_class_declaration", "class"], "name", src) {
+                Some(owner) => vec![(format!("{}.{}", owner, name), "method")],
+                None => vec![(name, "method")],
+            }
+        }
+        (Lang::TypeScript | Lang::Tsx, "lexical_declaration") => {
+            // Only module-level `const`: `const x = 1` or `const f = () => {}`.
+            let top_level = node
+                .parent()
+                .is_some_and(|p| matches!(p.kind(), "program" | "export_statement"));
+            if !top_level || node.child(0).map(|c| text(c, src)) != Some("const") {
+                return vec![];
+            }
+            let mut cursor = node.walk();
+            node.named_children(&mut cursor)
+                .filter(|c| c.kind() == "variable_declarator")
+                .filter_map(|decl| {
+                    let name = decl.child_by_field_name("name").filter(|n| n.kind() == "identifier")?;
+                    let is_fn = decl
+                        .child_by_field_name("value")
+                        .is_some_and(|v| matches!(v.kind(), "arrow_function" | "function_expression" | "function"));
+                    Some((text(name, src).to_string(), if is_fn { "function" } else { "constant" }))
+                })
+                .collect()
+        }
+
+        (Lang::Python, "function_definition") => {
+            let Some(name) = field_text(node, "name", src) else { return vec![] };
+            // Methods sit directly in a class body (possibly under a decorator).
+            let mut parent = node.parent();
+            if parent.is_some_and(|p| p.kind() == "decorated_definition") {
+                parent = parent.and_then(|p| p.parent());
+            }
+            let class = parent
+                .filter(|p| p.kind() == "block")
+                .and_then(|b| b.parent())
+                .filter(|c| c.kind() == "class_definition");
+            match class.and_then(|c| field_text(c, "name", src)) {
+                Some(owner) => vec![(format!("{}.{}", owner, name), "method")],
+                None => vec![(name, "function")],
+            }
+        }
+        (Lang::Python, "class_definition") => named("type"),
+        (Lang::Python, "assignment") => {
+            // Module-level UPPER_CASE names are constants by convention.
+            let module_level = node
+                .parent()
+                .filter(|p| p.kind() == "expression_statement")
+                .and_then(|p| p.parent())
+                .is_some_and(|g| g.kind() == "module");
+            let Some(left) = node.child_by_field_name("left").filter(|l| l.kind() == "identifier") else {
+                return vec![];
+            };
+            let name = text(left, src);
+            let upper = name.chars().any(|c| c.is_ascii_uppercase())
+                && !name.chars().any(|c| c.is_ascii_lowercase());
+            if module_level && upper { vec![(name.to_string(), "constant")] } else { vec![] }
+        }
+
+        (Lang::Go, "function_declaration") => named("function"),
+        (Lang::Go, "method_declaration") => {
+            let Some(name) = field_text(node, "name", src) else { return vec![] };
+            let receiver = node
+                .child_by_field_name("receiver")
+                .and_then(|r| {
+                    let mut cursor = r.walk();
+                    let param = r.named_children(&mut cursor).find(|c| c.kind() == "parameter_declaration");
+                    param
+                })
+                .and_then(|p| field_text(p, "type", src));
+            match receiver {
+                Some(owner) => vec![(format!("{}.{}", bare_type(&owner), name), "method")],
+                None => vec![(name, "method")],
+            }
+        }
+        (Lang::Go, "type_spec" | "type_alias") => named("type"),
+        (Lang::Go, "const_spec") => {
+            let mut cursor = node.walk();
+            node.children_by_field_name("name", &mut cursor)
+                .map(|n| (text(n, src).to_string(), "constant"))
+                .collect()
+        }
+
+        _ => vec![],
+    }
+}
+
+/// Best-effort read of the definition git shows after the second `@@` of a
+/// hunk header, e.g. `pub fn analyze(llm: &dyn LlmBackend,`.
+fn header_symbol(lang: Lang, context: &str) -> Option<(String, &'static str)> {
+    let words: Vec<&str> = context
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .filter(|w| !w.is_empty())
+        .collect();
+    let after = |i: usize| words.get(i + 1).map(|w| w.to_string());
+
+    for (i, word) in words.iter().enumerate() {
+        let found = match (lang, *word) {
+            (Lang::Rust, "fn") => after(i).map(|n| (n, "function")),
+            (Lang::Rust, "struct" | "enum" | "union" | "trait" | "type") => after(i).map(|n| (n, "type")),
+            (Lang::Rust, "const" | "static") => {
+                let skip = usize::from(words.get(i + 1) == Some(&"mut"));
+                after(i + skip).map(|n| (n, "constant"))
+            }
+            (Lang::Python, "def") => after(i).map(|n| (n, "function")),
+            (Lang::Python, "class") => after(i).map(|n| (n, "type")),
+            (Lang::Go, "func") => {
+                // `func (r *Recv) Name(` — the name follows the receiver list.
+                let rest = context.split_once("func")?.1.trim_start();
+                if let Some(receiver) = rest.strip_prefix('(') {
+                    let (recv, tail) = receiver.split_once(')')?;
+                    let owner = bare_type(recv.split_whitespace().last()?);
+                    let name: String = tail.trim_start().chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+                    (!name.is_empty()).then(|| (format!("{}.{}", owner, name), "method"))
+                } else {
+                    after(i).map(|n| (n, "function"))
+                }
+            }
+            (Lang::Go, "type") => after(i).map(|n| (n, "type")),
+            (Lang::TypeScript | Lang::Tsx, "function") => after(i).map(|n| (n, "function")),
+            (Lang::TypeScript | Lang::Tsx, "class" | "interface" | "enum" | "type") => after(i).map(|n| (n, "type")),
+            (Lang::TypeScript | Lang::Tsx, "const") => {
+                let is_fn = context.contains("=>") || context.contains("function");
+                after(i).map(|n| (n, if is_fn { "function" } else { "constant" }))
+            }
+            _ => None,
+        };
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::diff::parse_diff;
+
+    fn symbols(diff: &str) -> Vec<(String, String, String)> {
+        extract(&parse_diff(diff))
+            .into_iter()
+            .map(|s| (s.name, s.kind, s.change))
+            .collect()
+    }
+
+    fn sym(name: &str, kind: &str, change: &str) -> (String, String, String) {
+        (name.to_string(), kind.to_string(), change.to_string())
+    }
+
+    #[test]
+    fn rust_added_function() {
+        let diff = concat!(
+            "diff --git a/src/lib.rs b/src/lib.rs\n",
+            "@@ -1,3 +1,7 @@\n",
+            " fn one() {}\n",
+            "+\n",
+            "+pub fn two() -> u32 {\n",
+            "+    2\n",
+            "+}\n",
+        );
+        assert_eq!(symbols(diff), vec![sym("two", "function", "added")]);
+    }
+
+    #[test]
+    fn rust_modified_body_and_deleted_function() {
+        let diff = concat!(
+            "diff --git a/src/lib.rs b/src/lib.rs\n",
+            "@@ -1,8 +1,5 @@\n",
+            " fn keep() -> u32 {\n",
+            "-    1\n",
+            "+    2\n",
+            " }\n",
+            "-\n",
+            "-fn gone() {\n",
+            "-}\n",
+            " const LIMIT: usize = 3;\n",
+        );
+        assert_eq!(
+            symbols(diff),
+            vec![sym("keep", "function", "modified"), sym("gone", "function", "deleted")]
+        );
+    }
+
+    #[test]
+    fn rust_method_is_qualified_by_impl_type() {
+        let diff = concat!(
+            "diff --git a/src/a.rs b/src/a.rs\n",
+            "@@ -1,5 +1,5 @@\n",
+            " impl<T> Store<T> {\n",
+            "     pub fn len(&self) -> usize {\n",
+            "-        self.items.len()\n",
+            "+        self.items.len() + self.pending\n",
+            "     }\n",
+        );
+        assert_eq!(symbols(diff), vec![sym("Store::len", "method", "modified")]);
+    }
+
+    #[test]
+    fn rust_struct_field_and_constant() {
+        let diff = concat!(
+            "diff --git a/src/a.rs b/src/a.rs\n",
+            "@@ -1,4 +1,5 @@\n",
+            "-const LIMIT: usize = 3;\n",
+            "+const LIMIT: usize = 4;\n",
+            " pub struct Config {\n",
+            "     pub name: String,\n",
+            "+    pub verbose: bool,\n",
+            " }\n",
+        );
+        assert_eq!(
+            symbols(diff),
+            vec![sym("LIMIT", "constant", "modified"), sym("Config", "type", "modified")]
+        );
+    }
+
+    #[test]
+    fn rust_change_deep_in_body_uses_hunk_header() {
+        let diff = concat!(
+            "diff --git a/src/a.rs b/src/a.rs\n",
+            "@@ -40,3 +40,3 @@ pub fn analyze(llm: &dyn LlmBackend,\n",
+            "     let x = 1;\n",
+            "-    let y = 2;\n",
+            "+    let y = 3;\n",
+        );
+        assert_eq!(symbols(diff), vec![sym("analyze", "function", "modified")]);
+    }
+
+    #[test]
+    fn rename_is_delete_plus_add() {
+        let diff = concat!(
+            "diff --git a/src/a.rs b/src/a.rs\n",
+            "@@ -1,2 +1,2 @@\n",
+            "-fn old_name() {}\n",
+            "+fn new_name() {}\n",
+        );
+        assert_eq!(
+            symbols(diff),
+            vec![sym("new_name", "function", "added"), sym("old_name", "function", "deleted")]
+        );
+    }
+
+    #[test]
+    fn same_symbol_across_hunks_is_reported_once() {
+        let diff = concat!(
+            "diff --git a/src/a.rs b/src/a.rs\n",
+            "@@ -1,2 +1,2 @@ fn big() {\n",
+            "-    a();\n",
+            "+    b();\n",
+            "@@ -90,2 +90,2 @@ fn big() {\n",
+            "-    c();\n",
+            "+    d();\n",
+        );
+        assert_eq!(symbols(diff), vec![sym("big", "function", "modified")]);
+    }
+
+    #[test]
+    fn typescript_functions_classes_and_consts() {
+        let diff = concat!(
+            "diff --git a/src/app.ts b/src/app.ts\n",
+            "@@ -1,6 +1,10 @@\n",
+            "+export const TIMEOUT_MS = 500;\n",
+            "+export const handler = (req: Request) => req.url;\n",
+            " export class Panel {\n",
+            "     show(): void {\n",
+            "-        this.open = true;\n",
+            "+        this.open = !this.open;\n",
+            "     }\n",
+            " }\n",
+            "+interface Opts { quiet: boolean }\n",
+            "+function helper() {}\n",
+        );
+        assert_eq!(
+            symbols(diff),
+            vec![
+                sym("TIMEOUT_MS", "constant", "added"),
+                sym("handler", "function", "added"),
+                sym("Panel.show", "method", "modified"),
+                sym("Opts", "type", "added"),
+                sym("helper", "function", "added"),
+            ]
+        );
+    }
+
+    #[test]
+    fn python_methods_functions_and_constants() {
+        let diff = concat!(
+            "diff --git a/pkg/mod.py b/pkg/mod.py\n",
+            "@@ -1,6 +1,7 @@\n",
+            "-MAX_RETRIES = 3\n",
+            "+MAX_RETRIES = 5\n",
+            " class Client:\n",
+            "     @property\n",
+            "     def url(self):\n",
+            "-        return self._url\n",
+            "+        return self._base + self._url\n",
+            "+def standalone():\n",
+            "+    pass\n",
+        );
+        assert_eq!(
+            symbols(diff),
+            vec![
+                sym("MAX_RETRIES", "constant", "modified"),
+                sym("Client.url", "method", "modified"),
+                sym("standalone", "function", "added"),
+            ]
+        );
+    }
+
+    #[test]
+    fn go_methods_types_and_constants() {
+        let diff = concat!(
+            "diff --git a/srv/server.go b/srv/server.go\n",
+            "@@ -1,8 +1,9 @@\n",
+            "-const Port = 80\n",
+            "+const Port = 8080\n",
+            " type Server struct {\n",
+            "+\tname string\n",
+            " }\n",
+            " func (s *Server) Start() error {\n",
+            "-\treturn nil\n",
+            "+\treturn s.listen()\n",
+            " }\n",
+        );
+        assert_eq!(
+            symbols(diff),
+            vec![
+                sym("Port", "constant", "modified"),
+                sym("Server", "type", "modified"),
+                sym("Server.Start", "method", "modified"),
+            ]
+        );
+    }
+
+    #[test]
+    fn go_method_from_hunk_header() {
+        let diff = concat!(
+            "diff --git a/srv/server.go b/srv/server.go\n",
+            "@@ -20,2 +20,2 @@ func (s *Server) Stop(ctx context.Context) error {\n",
+            "-\ts.closed = false\n",
+            "+\ts.closed = true\n",
+        );
+        assert_eq!(symbols(diff), vec![sym("Server.Stop", "method", "modified")]);
+    }
+
+    #[test]
+    fn unsupported_languages_and_blank_line_edits_yield_nothing() {
+        let diff = concat!(
+            "diff --git a/README.md b/README.md\n",
+            "@@ -1 +1 @@\n",
+            "-# Old\n",
+            "+# New\n",
+            "diff --git a/src/a.rs b/src/a.rs\n",
+            "@@ -1,2 +1,3 @@\n",
+            " fn a() {}\n",
+            "+\n",
+            " fn b() {}\n",
+        );
+        assert!(symbols(diff).is_empty());
+    }
+
+    #[test]
+    fn scores_follow_change_kind() {
+        let diff = concat!(
+            "diff --git a/src/a.rs b/src/a.rs\n",
+            "@@ -1,2 +1,2 @@\n",
+            "-fn old() {}\n",
+            "+fn new() {}\n",
+        );
+        let syms = extract(&parse_diff(diff));
+        assert_eq!(syms[0].score, 0.3);
+        assert_eq!(syms[1].score, 0.9);
+        assert!(syms.iter().all(|s| s.file == "src/a.rs"));
+    }
+
+    #[test]
+    fn same_language_groups_ts_and_js() {
+        assert!(same_language("a/b.tsx", "c.js"));
+        assert!(same_language("x.rs", "y.rs"));
+        assert!(!same_language("x.rs", "y.go"));
+        assert!(!same_language("README.md", "README.md"));
+    }
+
+    #[test]
+    fn header_symbol_reads_common_definition_lines() {
+        assert_eq!(header_symbol(Lang::Rust, " pub(crate) fn run(x: u32) {"), Some(("run".into(), "function")));
+        assert_eq!(header_symbol(Lang::Rust, " static mut COUNT: u32 = 0;"), Some(("COUNT".into(), "constant")));
+        assert_eq!(header_symbol(Lang::Rust, " impl Foo for Bar {"), None);
+        assert_eq!(header_symbol(Lang::Python, " async def fetch(self):"), Some(("fetch".into(), "function")));
+        assert_eq!(header_symbol(Lang::TypeScript, " export const go = async () => {"), Some(("go".into(), "function")));
+        assert_eq!(header_symbol(Lang::Go, " func Serve() {"), Some(("Serve".into(), "function")));
+    }
+
+    #[test]
+    fn hunk_weight_ranks_deletions_over_additions() {
+        let files = parse_diff(concat!(
+            "diff --git a/a.rs b/a.rs\n",
+            "@@ -1,2 +1,1 @@\n-fn gone() {}\n keep();\n",
+            "@@ -10,1 +9,2 @@\n keep();\n+fn fresh() {}\n",
+            "@@ -20,1 +20,1 @@\n-// note\n+// noted\n",
+        ));
+        let weights: Vec<f32> = files[0].hunks.iter().map(|h| hunk_weight("a.rs", h)).collect();
+        assert_eq!(weights, vec![Change::Deleted.score(), Change::Added.score(), 0.0]);
+        assert_eq!(hunk_weight("notes.txt", &files[0].hunks[0]), 0.0);
+    }
+}
@@ -0,0 +1,104 @@
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: senior-daemon [OPTIONS]
+       senior-daemon --once [--diff-file <PATH>] [--config <PATH>]
+
+Options:
+  --config <PATH>    Settings file [env: SENIOR_CONFIG]
+                     [default: $XDG_CONFIG_HOME/senior/senior.toml]
+  --socket <PATH>    Unix socket to listen on [env: SENIOR_SOCKET_PATH]
+  --data-dir <DIR>   Directory for the SQLite database [env: SENIOR_DATA_DIR]
+  --once             Review one patch, print the result as JSON and exit,
+                     without a socket or database
+  --diff-file <PATH> Patch for --once [default: - (stdin)]
+  -h, --help         Print this help
+";
+
+/// Command-line flags of `senior-daemon`; each overrides its environment variable.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Args {
+    pub config: Option<PathBuf>,
+    pub socket: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+    pub once: bool,
+    pub diff_file: Option<PathBuf>,
+    pub help: bool,
+}
+
+/// Parse the arguments after the program name. Values may follow their flag
+/// as the next argument or after `=`.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg, None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .map(PathBuf::from)
+                .ok_or_else(|| anyhow!("{} needs a value\n\n{}", flag, USAGE))
+        };
+        match flag.as_str() {
+            "--config" => parsed.config = Some(value()?),
+            "--socket" => parsed.socket = Some(value()?),
+            "--data-dir" => parsed.data_dir = Some(value()?),
+            "--once" => parsed.once = true,
+            "--diff-file" => parsed.diff_file = Some(value()?),
+            "-h" | "--help" => parsed.help = true,
+            _ => return Err(anyhow!("unknown argument '{}'\n\n{}", flag, USAGE)),
+        }
+    }
+    if parsed.once && (parsed.socket.is_some() || parsed.data_dir.is_some()) {
+        return Err(anyhow!("--once uses no socket or database; drop --socket and --data-dir"));
+    }
+    if parsed.diff_file.is_some() && !parsed.once {
+        return Err(anyhow!("--diff-file only applies to --once"));
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &[&str]) -> Result<Args> {
+        parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_overrides_nothing() {
+        assert_eq!(parse_str(&[]).unwrap(), Args::default());
+    }
+
+    #[test]
+    fn flags_take_separate_or_inline_values() {
+        let args = parse_str(&["--socket", "/run/s.sock", "--data-dir=/var/senior", "--config", "s.toml"]).unwrap();
+        assert_eq!(args.config, Some(PathBuf::from("s.toml")));
+        assert_eq!(args.socket, Some(PathBuf::from("/run/s.sock")));
+        assert_eq!(args.data_dir, Some(PathBuf::from("/var/senior")));
+    }
+
+    #[test]
+    fn missing_values_and_unknown_flags_are_errors() {
+        assert!(parse_str(&["--socket"]).is_err());
+        let err = parse_str(&["--model", "x"]).unwrap_err().to_string();
+        assert!(err.starts_with("unknown argument '--model'"), "got: {}", err);
+        assert!(parse_str(&["-h"]).unwrap().help);
+    }
+
+    #[test]
+    fn once_takes_a_diff_file_but_no_storage() {
+        let args = parse_str(&["--once", "--diff-file=x.patch"]).unwrap();
+        assert!(args.once);
+        assert_eq!(args.diff_file, Some(PathBuf::from("x.patch")));
+        assert!(parse_str(&["--once", "--socket", "/s"]).is_err());
+        let err = parse_str(&["--diff-file", "x.patch"]).unwrap_err().to_string();
+        assert_eq!(err, "--diff-file only applies to --once");
+    }
+}
@@ -0,0 +1,333 @@
+use crate::hook::Hook;
+use anyhow::{anyhow, Result};
+use senior_daemon::protocol::{DiffSelector, RenderFormat};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: senior [OPTIONS] <COMMAND>
+
+Commands:
+  analyze             Review the repository's changes: the working tree if
+                      dirty, otherwise the last commit
+  ask <QUESTION>      Ask about the repository's latest analysis
+  history             List past analyses of the repository, newest first
+  hook install        Install pre-commit and pre-push hooks that review what
+                      is committed or pushed and block risky changes
+  ping                Check that the daemon is running
+  render [FILE]       Turn a saved analysis (JSON, as from --json or
+                      senior-daemon --once) into a report; - is stdin
+  status              Show the daemon's model, uptime and config file
+
+Analyze options (pick one):
+  --staged            Only what is in the index
+  --working-tree      Staged and unstaged changes against HEAD
+  --range <FROM..TO>  A commit range; TO defaults to HEAD
+  --branch <BASE>     The current branch against its merge-base with BASE
+  --diff <FILE>       A patch file instead, or - for stdin
+
+Report options (analyze, render):
+  --format <FORMAT>   text, markdown, sarif or junit [default: text]
+
+History options:
+  --file <PATH>       Only analyses whose diff touched PATH
+  --limit <N>         At most N entries [default: 20]
+
+Hook options:
+  --threshold <LEVEL> Block at this risk or above: low, med or high
+                      [default: high; env: SENIOR_HOOK_THRESHOLD]
+  --force             Replace hooks senior did not write
+
+Options:
+  --repo <DIR>        Repository to work on [default: .]
+  --socket <PATH>     Daemon socket [env: SENIOR_SOCKET_PATH]
+  --config <PATH>     Settings file whose [storage] socket to use
+                      [env: SENIOR_CONFIG]
+  --json              Print the daemon's response as it sent it
+  --no-color          Plain text even on a terminal [env: NO_COLOR]
+  -h, --help          Print this help
+
+Exit status: 0 on success, 1 when an analysis rates the change high risk,
+2 on errors. Set SENIOR_HOOK_OVERRIDE=1 to get past a blocking hook.
+";
+
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+const DEFAULT_THRESHOLD: &str = "high";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Analyze(Changes),
+    Ask { question: String },
+    History { file: Option<String>, limit: usize },
+    /// `hook install`.
+    HookInstall { threshold: String, force: bool },
+    /// `hook run <HOOK> [ARGS]`, which the installed hooks call with git's
+    /// arguments; pre-push's first one names the remote.
+    HookRun { hook: Hook, remote: Option<String>, threshold: String },
+    Ping,
+    /// `render [FILE]`; `-` is stdin.
+    Render { input: PathBuf },
+    Status,
+}
+
+/// What `analyze` reviews.
+#[derive(Debug, PartialEq)]
+pub enum Changes {
+    /// Changes the daemon reads from git itself.
+    Select(DiffSelector),
+    /// A patch file; `-` is stdin.
+    Patch(PathBuf),
+}
+
+/// Parsed command line of `senior`. `command` is `None` only with `help`.
+#[derive(Debug, PartialEq)]
+pub struct Cli {
+    pub command: Option<Command>,
+    pub repo: PathBuf,
+    pub socket: Option<PathBuf>,
+    /// Read for its `[storage]` socket when `--socket` is not given.
+    pub config: Option<PathBuf>,
+    pub json: bool,
+    /// How `analyze` and `render` print a result.
+    pub format: RenderFormat,
+    pub no_color: bool,
+    pub help: bool,
+}
+
+/// Parse the arguments after the program name. Options may come before or
+/// after the command, and values may follow their flag as the next argument
+/// or after `=`.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli> {
+    let mut cli = Cli {
+        command: None,
+        repo: PathBuf::from("."),
+        socket: None,
+        config: None,
+        json: false,
+        format: RenderFormat::default(),
+        no_color: false,
+        help: false,
+    };
+    let mut words: Vec<String> = Vec::new();
+    let mut changes: Vec<Changes> = Vec::new();
+    let (mut file, mut limit) = (None, None);
+    let (mut threshold, mut force) = (None, false);
+    let mut format = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg, None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| anyhow!("{} needs a value\n\n{}", flag, USAGE))
+        };
+        match flag.as_str() {
+            "--repo" => cli.repo = PathBuf::from(value()?),
+            "--socket" => cli.socket = Some(PathBuf::from(value()?)),
+            "--config" => cli.config = Some(PathBuf::from(value()?)),
+            "--json" => cli.json = true,
+            "--no-color" => cli.no_color = true,
+            "-h" | "--help" => cli.help = true,
+            "--staged" => changes.push(Changes::Select(DiffSelector::Staged)),
+            "--working-tree" => changes.push(Changes::Select(DiffSelector::WorkingTree)),
+            "--range" => changes.push(Changes::Select(range(&value()?)?)),
+            "--branch" => changes.push(Changes::Select(DiffSelector::Branch { base: value()? })),
+            "--diff" => changes.push(Changes::Patch(PathBuf::from(value()?))),
+            "--file" => file = Some(value()?),
+            "--limit" => {
+                let raw = value()?;
+                limit = Some(raw.parse().ok().filter(|&n: &usize| n > 0).ok_or_else(|| {
+                    anyhow!("--limit must be a positive integer, got '{}'", raw)
+                })?);
+            }
+            "--threshold" => {
+                let raw = value()?;
+                if !["low", "med", "high"].contains(&raw.as_str()) {
+                    return Err(anyhow!("--threshold must be low, med or high, got '{}'", raw));
+                }
+                threshold = Some(raw);
+            }
+            "--force" => force = true,
+            "--format" => {
+                let raw = value()?;
+                format = Some(RenderFormat::parse(&raw).ok_or_else(|| {
+                    anyhow!("--format must be text, markdown, sarif or junit, got '{}'", raw)
+                })?);
+            }
+            f if f.starts_with('-') => return Err(anyhow!("unknown option '{}'\n\n{}", f, USAGE)),
+            _ => words.push(flag),
+        }
+    }
+    if cli.help {
+        return Ok(cli);
+    }
+
+    let mut words = words.into_iter();
+    let name = words.next().ok_or_else(|| anyhow!("missing command\n\n{}", USAGE))?;
+    let rest: Vec<String> = words.collect();
+    if name != "analyze" && !changes.is_empty() {
+        return Err(anyhow!("--staged, --working-tree, --range, --branch and --diff only apply to analyze"));
+    }
+    if name != "history" && (file.is_some() || limit.is_some()) {
+        return Err(anyhow!("--file and --limit only apply to history"));
+    }
+    if name != "hook" && (threshold.is_some() || force) {
+        return Err(anyhow!("--threshold and --force only apply to hook"));
+    }
+    if let Some(format) = format {
+        if !["analyze", "render"].contains(&name.as_str()) {
+            return Err(anyhow!("--format only applies to analyze and render"));
+        }
+        if cli.json {
+            return Err(anyhow!("pick one of --json and --format"));
+        }
+        cli.format = format;
+    }
+    if name == "render" && rest.len() > 1 {
+        return Err(anyhow!("unexpected argument '{}'\n\n{}", rest[1], USAGE));
+    }
+    if !["ask", "hook", "render"].contains(&name.as_str()) && !rest.is_empty() {
+        return Err(anyhow!("unexpected argument '{}'\n\n{}", rest[0], USAGE));
+    }
+    cli.command = Some(match name.as_str() {
+        "analyze" => {
+            if changes.len() > 1 {
+                return Err(anyhow!("pick one of --staged, --working-tree, --range, --branch and --diff"));
+            }
+            Command::Analyze(changes.pop().unwrap_or(Changes::Select(DiffSelector::LastChange)))
+        }
+        "ask" if rest.is_empty() => return Err(anyhow!("ask needs a question\n\n{}", USAGE)),
+        "ask" => Command::Ask { question: rest.join(" ") },
+        "history" => Command::History { file, limit: limit.unwrap_or(DEFAULT_HISTORY_LIMIT) },
+        "hook" => hook(&rest, threshold.unwrap_or_else(|| DEFAULT_THRESHOLD.to_string()), force)?,
+        "ping" => Command::Ping,
+        "render" => Command::Render { input: rest.first().map_or_else(|| PathBuf::from("-"), PathBuf::from) },
+        "status" => Command::Status,
+        other => return Err(anyhow!("unknown command '{}'\n\n{}", other, USAGE)),
+    });
+    Ok(cli)
+}
+
+/// The words after `hook`.
+fn hook(words: &[String], threshold: String, force: bool) -> Result<Command> {
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    match words[..] {
+        ["install"] => Ok(Command::HookInstall { threshold, force }),
+        ["run", name, ref hook_args @ ..] if !force => {
+            let hook = Hook::parse(name).ok_or_else(|| anyhow!("no such hook '{}': use pre-commit or pre-push", name))?;
+            Ok(Command::HookRun { hook, remote: hook_args.first().map(|r| r.to_string()), threshold })
+        }
+        ["run", ..] => Err(anyhow!("--force only applies to hook install")),
+        _ => Err(anyhow!("hook needs install, or run <HOOK>\n\n{}", USAGE)),
+    }
+}
+
+/// `FROM..TO` or `FROM..`; a bare `FROM` means `FROM..HEAD` too.
+fn range(raw: &str) -> Result<DiffSelector> {
+    let (from, to) = raw.split_once("..").unwrap_or((raw, ""));
+    if from.is_empty() {
+        return Err(anyhow!("--range needs a start, as in main..HEAD"));
+    }
+    Ok(DiffSelector::CommitRange { from: from.to_string(), to: Some(to.to_string()).filter(|t| !t.is_empty()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &[&str]) -> Result<Cli> {
+        parse(args.iter().map(|a| a.to_string()))
+    }
+
+    fn command(args: &[&str]) -> Command {
+        parse_str(args).unwrap().command.unwrap()
+    }
+
+    #[test]
+    fn analyze_defaults_to_the_last_change() {
+        assert_eq!(command(&["analyze"]), Command::Analyze(Changes::Select(DiffSelector::LastChange)));
+        assert_eq!(command(&["analyze", "--staged"]), Command::Analyze(Changes::Select(DiffSelector::Staged)));
+        assert_eq!(
+            command(&["analyze", "--range=main..feature"]),
+            Command::Analyze(Changes::Select(DiffSelector::CommitRange {
+                from: "main".into(),
+                to: Some("feature".into())
+            }))
+        );
+        assert_eq!(
+            command(&["analyze", "--range", "v1.0"]),
+            Command::Analyze(Changes::Select(DiffSelector::CommitRange { from: "v1.0".into(), to: None }))
+        );
+        assert_eq!(command(&["--diff", "-", "analyze"]), Command::Analyze(Changes::Patch("-".into())));
+    }
+
+    #[test]
+    fn options_go_before_or_after_the_command() {
+        let cli = parse_str(&["--json", "history", "--repo", "/r", "--limit=5", "--no-color", "--config=/c.toml"]).unwrap();
+        assert_eq!(cli.command, Some(Command::History { file: None, limit: 5 }));
+        assert_eq!(cli.repo, PathBuf::from("/r"));
+        assert_eq!(cli.config, Some(PathBuf::from("/c.toml")));
+        assert!(cli.json && cli.no_color);
+        assert_eq!(command(&["history"]), Command::History { file: None, limit: DEFAULT_HISTORY_LIMIT });
+    }
+
+    #[test]
+    fn ask_joins_its_words() {
+        assert_eq!(command(&["ask", "why", "is this risky?"]), Command::Ask { question: "why is this risky?".into() });
+        assert!(parse_str(&["ask"]).is_err());
+    }
+
+    #[test]
+    fn misplaced_and_conflicting_options_are_errors() {
+        let err = |args: &[&str]| parse_str(args).unwrap_err().to_string();
+        assert!(err(&["history", "--staged"]).contains("only apply to analyze"));
+        assert!(err(&["analyze", "--limit", "3"]).contains("only apply to history"));
+        assert!(err(&["analyze", "--staged", "--diff", "x.patch"]).starts_with("pick one of"));
+        assert!(err(&["ping", "extra"]).starts_with("unexpected argument 'extra'"));
+        assert!(err(&["history", "--limit", "0"]).contains("positive integer"));
+        assert!(err(&["deploy"]).starts_with("unknown command 'deploy'"));
+        assert!(err(&["ping", "--verbose"]).starts_with("unknown option '--verbose'"));
+        assert!(err(&[]).starts_with("missing command"));
+    }
+
+    #[test]
+    fn hook_takes_a_subcommand() {
+        assert_eq!(command(&["hook", "install"]), Command::HookInstall { threshold: "high".into(), force: false });
+        assert_eq!(
+            command(&["hook", "install", "--threshold=med", "--force"]),
+            Command::HookInstall { threshold: "med".into(), force: true }
+        );
+        assert_eq!(
+            command(&["--socket", "/s", "hook", "run", "pre-push", "--threshold", "low", "origin", "git@host:r.git"]),
+            Command::HookRun { hook: Hook::PrePush, remote: Some("origin".into()), threshold: "low".into() }
+        );
+        let err = |args: &[&str]| parse_str(args).unwrap_err().to_string();
+        assert!(err(&["hook"]).starts_with("hook needs install"));
+        assert!(err(&["hook", "run", "post-merge"]).starts_with("no such hook"));
+        assert!(err(&["hook", "install", "--threshold", "critical"]).contains("low, med or high"));
+        assert!(err(&["analyze", "--force"]).contains("only apply to hook"));
+    }
+
+    #[test]
+    fn format_applies_to_analyze_and_render() {
+        let cli = parse_str(&["analyze", "--staged", "--format", "sarif"]).unwrap();
+        assert_eq!(cli.format, RenderFormat::Sarif);
+        assert_eq!(parse_str(&["analyze"]).unwrap().format, RenderFormat::Text);
+        assert_eq!(command(&["render", "--format=markdown"]), Command::Render { input: "-".into() });
+        assert_eq!(command(&["render", "result.json"]), Command::Render { input: "result.json".into() });
+        let err = |args: &[&str]| parse_str(args).unwrap_err().to_string();
+        assert!(err(&["history", "--format", "junit"]).contains("only applies to analyze and render"));
+        assert!(err(&["analyze", "--json", "--format", "junit"]).starts_with("pick one of --json and --format"));
+        assert!(err(&["render", "--format", "html"]).contains("text, markdown, sarif or junit"));
+        assert!(err(&["render", "a.json", "b.json"]).starts_with("unexpected argument 'b.json'"));
+    }
+
+    #[test]
+    fn help_needs_no_command() {
+        let cli = parse_str(&["--help"]).unwrap();
+        assert!(cli.help && cli.command.is_none());
+    }
+}
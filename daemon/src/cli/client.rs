@@ -0,0 +1,31 @@
+use anyhow::{anyhow, Result};
+use senior_daemon::protocol::{Request, RequestEnvelope, Response, ResponseEnvelope};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+/// Send `request` to the daemon at `socket` on a connection of its own and
+/// return the final response. Streaming frames (`analysis_progress`, `token`)
+/// that come first are passed to `on_frame`.
+pub fn request(socket: &Path, request: Request, on_frame: &mut dyn FnMut(&Response)) -> Result<Response> {
+    let stream = UnixStream::connect(socket).map_err(|e| {
+        anyhow!("cannot reach the daemon at {}: {} (is senior-daemon running?)", socket.display(), e)
+    })?;
+    let mut line = serde_json::to_string(&RequestEnvelope { id: None, request })?;
+    line.push('\n');
+    (&stream).write_all(line.as_bytes())?;
+
+    for line in BufReader::new(&stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let frame: ResponseEnvelope =
+            serde_json::from_str(&line).map_err(|e| anyhow!("unexpected reply from the daemon: {}: {}", e, line))?;
+        match frame.response {
+            frame @ (Response::AnalysisProgress { .. } | Response::Token { .. }) => on_frame(&frame),
+            response => return Ok(response),
+        }
+    }
+    Err(anyhow!("the daemon closed the connection without answering"))
+}
@@ -0,0 +1,267 @@
+use crate::{client, files_touched};
+use anyhow::{anyhow, Result};
+use senior_daemon::git;
+use senior_daemon::protocol::{risk_rank, AnalyzeDiffPayload, Request, Response};
+use senior_daemon::render::{self, Style};
+use std::fs;
+use std::io::BufRead;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// First line after the shebang of every hook `install` writes, so running it
+/// again may replace them.
+const MARKER: &str = "# Installed by `senior hook install`";
+
+/// Set to anything but `0` to let a blocked commit or push through.
+pub const OVERRIDE_VAR: &str = "SENIOR_HOOK_OVERRIDE";
+
+/// Read by the installed hooks in place of the threshold given at install.
+pub const THRESHOLD_VAR: &str = "SENIOR_HOOK_THRESHOLD";
+
+/// Exit status that makes git abandon the commit or push.
+const EXIT_BLOCKED: u8 = 1;
+
+/// git's empty tree: the base of a push that shares no history with the remote.
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// Reviews the staged changes.
+    PreCommit,
+    /// Reviews the commits each pushed ref adds.
+    PrePush,
+}
+
+impl Hook {
+    pub const ALL: [Hook; 2] = [Hook::PreCommit, Hook::PrePush];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|h| h.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PreCommit => "pre-commit",
+            Hook::PrePush => "pre-push",
+        }
+    }
+
+    fn action(self) -> &'static str {
+        match self {
+            Hook::PreCommit => "commit",
+            Hook::PrePush => "push",
+        }
+    }
+}
+
+/// Write the pre-commit and pre-push hooks of the repository containing
+/// `repo` (honouring `core.hooksPath`). They run `exe hook run`, talking to
+/// the daemon at `socket` if one was named, and block at `threshold`. A hook
+/// senior did not write is only replaced with `force`. Returns the hooks
+/// directory.
+pub fn install(repo: &Path, exe: &Path, threshold: &str, socket: Option<&Path>, force: bool) -> Result<PathBuf> {
+    let dir = PathBuf::from(git::run(repo, &["rev-parse", "--git-path", "hooks"])?.trim());
+    let dir = if dir.is_absolute() { dir } else { repo.join(dir) };
+    fs::create_dir_all(&dir).map_err(|e| anyhow!("cannot create {}: {}", dir.display(), e))?;
+    let paths: Vec<PathBuf> = Hook::ALL.iter().map(|h| dir.join(h.name())).collect();
+    for path in &paths {
+        if path.exists() && !written_by_us(path) && !force {
+            return Err(anyhow!(
+                "{} already exists and was not written by senior; pass --force to replace it",
+                path.display()
+            ));
+        }
+    }
+    for (hook, path) in Hook::ALL.iter().zip(&paths) {
+        fs::write(path, script(*hook, exe, threshold, socket))
+            .and_then(|_| fs::set_permissions(path, fs::Permissions::from_mode(0o755)))
+            .map_err(|e| anyhow!("cannot write {}: {}", path.display(), e))?;
+    }
+    Ok(dir)
+}
+
+/// Whether the hook at `path` is one `install` wrote. One that cannot be read
+/// as text, such as a compiled binary, is not.
+fn written_by_us(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|text| text.contains(MARKER))
+}
+
+/// The shell script git runs for `hook`. A missing `senior` binary lets
+/// everything through rather than blocking every commit.
+fn script(hook: Hook, exe: &Path, threshold: &str, socket: Option<&Path>) -> String {
+    let socket = socket.map(|s| format!(" --socket {}", quote(&s.display().to_string()))).unwrap_or_default();
+    format!(
+        "#!/bin/sh\n\
+         {marker}; run it again to update this hook.\n\
+         # Set {override_var}=1 to {action} anyway, or {threshold_var} to change the risk that blocks.\n\
+         senior={exe}\n\
+         if [ ! -x \"$senior\" ]; then\n\
+         \x20   echo \"senior: $senior not found; skipping the {name} review\" >&2\n\
+         \x20   exit 0\n\
+         fi\n\
+         exec \"$senior\"{socket} hook run {name} --threshold \"${{{threshold_var}:-{threshold}}}\" \"$@\"\n",
+        marker = MARKER,
+        override_var = OVERRIDE_VAR,
+        threshold_var = THRESHOLD_VAR,
+        action = hook.action(),
+        exe = quote(&exe.display().to_string()),
+        name = hook.name(),
+        socket = socket,
+        threshold = threshold,
+    )
+}
+
+/// `s` single-quoted for sh.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Review what `hook` is about to let into the repository containing `repo`
+/// and block when the riskiest review reaches `threshold`, unless
+/// `SENIOR_HOOK_OVERRIDE` is set. For pre-push, `remote` is the remote's name
+/// and the pushed refs are read from stdin. The diffs are computed here
+/// rather than by the daemon because git points a hook at the index it is
+/// about to commit, which can differ from the one on disk (`git commit -a`).
+/// A diff the daemon cannot be reached for or fails to answer about blocks
+/// nothing, but the reviews it already gave still do.
+pub fn run(hook: Hook, remote: Option<&str>, threshold: &str, repo: &Path, socket: &Path, style: Style) -> Result<ExitCode> {
+    let root = git::workspace(repo);
+    let dir = Path::new(&root);
+    let mut diffs = Vec::new();
+    match hook {
+        Hook::PreCommit => diffs.push(("staged changes".to_string(), git::run_diff(dir, &["--cached"])?)),
+        Hook::PrePush => {
+            for line in std::io::stdin().lock().lines() {
+                let line = line?;
+                if let Some((from, to)) = push_range(&line, remote.unwrap_or_default(), dir)? {
+                    let label = format!("{} ({}..{})", line.split_whitespace().nth(2).unwrap_or_default(), short(&from), short(&to));
+                    diffs.push((label, git::run_diff(dir, &[&from, &to])?));
+                }
+            }
+        }
+    }
+
+    let mut worst: Option<String> = None;
+    for (label, diff) in diffs.into_iter().filter(|(_, diff)| !diff.trim().is_empty()) {
+        let request = Request::AnalyzeDiff(AnalyzeDiffPayload {
+            files_touched: files_touched(&diff),
+            diff,
+            active_file: String::new(),
+            trigger: hook.name().to_string(),
+            stream: false,
+            workspace: Some(root.clone()),
+            supersede: false,
+        });
+        match client::request(socket, request, &mut |_| {}) {
+            Ok(Response::AnalysisResult(result)) => {
+                println!("senior: {} review of {}\n{}", hook.name(), label, render::text(&result, style));
+                if worst.as_deref().is_none_or(|w| risk_rank(&result.risk_level) > risk_rank(w)) {
+                    worst = Some(result.risk_level);
+                }
+            }
+            Ok(Response::Error { message }) => eprintln!("senior: {}; not blocking the {}", message, hook.action()),
+            Ok(other) => eprintln!("senior: unexpected reply from the daemon: {:?}; not blocking the {}", other, hook.action()),
+            Err(e) => {
+                // Reviews already received still count.
+                eprintln!("senior: {}; skipping the rest of the {} review", e, hook.name());
+                break;
+            }
+        }
+    }
+
+    let Some(level) = worst.filter(|w| risk_rank(w) >= risk_rank(threshold)) else {
+        return Ok(ExitCode::SUCCESS);
+    };
+    if std::env::var(OVERRIDE_VAR).is_ok_and(|v| !v.is_empty() && v != "0") {
+        eprintln!("senior: {} risk, but {} is set; letting the {} through", level, OVERRIDE_VAR, hook.action());
+        return Ok(ExitCode::SUCCESS);
+    }
+    eprintln!(
+        "senior: {} blocked: {} risk reaches the {} threshold. Review the report above, or set {}=1 to {} anyway.",
+        hook.action(),
+        level,
+        threshold,
+        OVERRIDE_VAR,
+        hook.action()
+    );
+    Ok(ExitCode::from(EXIT_BLOCKED))
+}
+
+/// The commits one line of pre-push input adds, as `(from, to)`, or `None`
+/// for a deleted ref or one the remote already has everything of. A new ref
+/// is compared with the last commit `remote` has, found by walking back from
+/// it; a ref with no history on the remote at all, with the empty tree.
+fn push_range(line: &str, remote: &str, dir: &Path) -> Result<Option<(String, String)>> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let [_, local_sha, _, remote_sha] = fields[..] else {
+        return Err(anyhow!("unexpected pre-push input: '{}'", line));
+    };
+    let is_zero = |sha: &str| sha.bytes().all(|b| b == b'0');
+    if is_zero(local_sha) {
+        return Ok(None);
+    }
+    let known = |sha: &str| git::run(dir, &["cat-file", "-e", &format!("{}^{{commit}}", sha)]).is_ok();
+    if !is_zero(remote_sha) && known(remote_sha) {
+        return Ok(Some((remote_sha.to_string(), local_sha.to_string())));
+    }
+    let not_on_remote = format!("--remotes={}", remote);
+    let added = git::run(dir, &["rev-list", "--reverse", local_sha, "--not", &not_on_remote])?;
+    let Some(first) = added.lines().next() else { return Ok(None) };
+    let base = git::run(dir, &["rev-parse", "--verify", "--quiet", &format!("{}^", first)])
+        .map(|out| out.trim().to_string())
+        .unwrap_or_else(|_| EMPTY_TREE.to_string());
+    Ok(Some((base, local_sha.to_string())))
+}
+
+fn short(sha: &str) -> &str {
+    sha.get(..7).unwrap_or(sha)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn hooks_round_trip_their_names() {
+        assert_eq!(Hook::parse("pre-push"), Some(Hook::PrePush));
+        assert_eq!(Hook::parse(Hook::PreCommit.name()), Some(Hook::PreCommit));
+        assert_eq!(Hook::parse("post-merge"), None);
+    }
+
+    #[test]
+    fn script_runs_the_cli_with_an_overridable_threshold() {
+        let text = script(Hook::PrePush, Path::new("/opt/it's/senior"), "med", Some(Path::new("/run/s.sock")));
+        assert!(text.starts_with("#!/bin/sh\n# Installed by `senior hook install`"), "got:\n{}", text);
+        assert!(text.contains("senior='/opt/it'\\''s/senior'\n"), "got:\n{}", text);
+        assert!(
+            text.ends_with(
+                "exec \"$senior\" --socket '/run/s.sock' hook run pre-push --threshold \"${SENIOR_HOOK_THRESHOLD:-med}\" \"$@\"\n"
+            ),
+            "got:\n{}",
+            text
+        );
+        assert!(!script(Hook::PreCommit, Path::new("/s"), "high", None).contains("--socket"));
+    }
+
+    #[test]
+    fn only_hooks_carrying_the_marker_are_ours() {
+        let dir = TempDir::new("hook-ours");
+        let ours = dir.write("ours", script(Hook::PreCommit, Path::new("/s"), "high", None));
+        let theirs = dir.write("theirs", "#!/bin/sh\nexit 0\n");
+        let binary = dir.write("binary", [0x7f, b'E', b'L', b'F', 0xff, 0xfe]);
+
+        assert!(written_by_us(&ours));
+        assert!(!written_by_us(&theirs));
+        assert!(!written_by_us(&binary), "an unreadable hook is not ours to replace");
+    }
+
+    #[test]
+    fn deleted_refs_need_no_review() {
+        let zero = "0".repeat(40);
+        let line = format!("(delete) {} refs/heads/old {}", zero, "a".repeat(40));
+        assert_eq!(push_range(&line, "origin", Path::new("/nonexistent")).unwrap(), None);
+        assert!(push_range("garbage", "origin", Path::new("/")).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! `senior`: talks to a running senior-daemon over its socket, for use from a
+//! terminal or a script.
+
+mod args;
+mod client;
+mod hook;
+mod output;
+#[cfg(test)]
+#[path = "../temp_dir.rs"]
+mod temp_dir;
+
+use anyhow::{anyhow, Result};
+use args::{Changes, Command};
+use senior_daemon::git;
+use senior_daemon::paths::{self, Locations, StoragePaths};
+use senior_daemon::protocol::{
+    AnalysisResult, AnalyzeDiffPayload, AnalyzeRepoPayload, HistoryPayload, RenderFormat, Request, Response,
+    VoiceQueryPayload,
+};
+use senior_daemon::render::{self, Style};
+use std::io::{IsTerminal, Read};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Exit status when an analysis rates the change high risk.
+const EXIT_HIGH_RISK: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+/// Recorded as the trigger of analyses the CLI asks for.
+const TRIGGER: &str = "cli";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("senior: {}", e);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+fn run() -> Result<ExitCode> {
+    let cli = args::parse(std::env::args().skip(1))?;
+    let Some(command) = cli.command.filter(|_| !cli.help) else {
+        print!("{}", args::USAGE);
+        return Ok(ExitCode::SUCCESS);
+    };
+    let env = |name: &str| std::env::var(name).ok();
+    let flags = Locations { socket: cli.socket.clone(), data_dir: None };
+    // The settings file only matters, and is only read, when nothing closer names the socket.
+    let configured = match flags.socket.is_some() || env("SENIOR_SOCKET_PATH").is_some() {
+        true => Locations::default(),
+        false => paths::configured(cli.config.clone(), &env)?,
+    };
+    let socket = StoragePaths::resolve(flags, configured, &env)?.socket;
+    let style = Style { color: !cli.no_color && env("NO_COLOR").is_none() && std::io::stdout().is_terminal() };
+    let progress = !cli.json && std::io::stderr().is_terminal();
+    let repo = git::workspace(&cli.repo);
+
+    let request = match command {
+        Command::HookInstall { threshold, force } => {
+            let exe = std::env::current_exe().and_then(|p| p.canonicalize()).unwrap_or_else(|_| "senior".into());
+            let dir = hook::install(&cli.repo, &exe, &threshold, cli.socket.as_deref(), force)?;
+            println!("Installed pre-commit and pre-push hooks in {}; they block at {} risk.", dir.display(), threshold);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Command::HookRun { hook, remote, threshold } => {
+            return hook::run(hook, remote.as_deref(), &threshold, &cli.repo, &socket, style);
+        }
+        Command::Render { input } => {
+            let result = read_result(&input)?;
+            print!("{}", report(&result, cli.format, style));
+            return Ok(exit_code(&result));
+        }
+        Command::Analyze(Changes::Select(selector)) => Request::AnalyzeRepo(AnalyzeRepoPayload {
+            repo_root: repo.clone(),
+            selector,
+            active_file: String::new(),
+            trigger: TRIGGER.to_string(),
+            stream: progress,
+            supersede: false,
+        }),
+        Command::Analyze(Changes::Patch(path)) => {
+            let diff = read_input(&path)?;
+            Request::AnalyzeDiff(AnalyzeDiffPayload {
+                files_touched: files_touched(&diff),
+                diff,
+                active_file: String::new(),
+                trigger: TRIGGER.to_string(),
+                stream: progress,
+                workspace: Some(repo.clone()),
+                supersede: false,
+            })
+        }
+        Command::Ask { question } => {
+            // Answer in the light of the latest analysis of this repository.
+            let latest = HistoryPayload { repo: Some(repo.clone()), file: None, since: None, until: None, limit: 1 };
+            let context = match client::request(&socket, Request::History(latest), &mut |_| {})? {
+                Response::History { mut entries } => entries.pop().map(|e| e.result),
+                _ => None,
+            };
+            Request::VoiceQuery(VoiceQueryPayload { question, context })
+        }
+        Command::History { file, limit } => Request::History(HistoryPayload {
+            repo: Some(repo.clone()),
+            file,
+            since: None,
+            until: None,
+            limit,
+        }),
+        Command::Ping => Request::Ping,
+        Command::Status => Request::Status,
+    };
+
+    let response = client::request(&socket, request, &mut |frame| {
+        if let (true, Response::AnalysisProgress { stage, detail }) = (progress, frame) {
+            eprintln!("{}: {}", stage, detail);
+        }
+    })?;
+    if cli.json {
+        println!("{}", serde_json::to_string(&response)?);
+    }
+    let mut code = ExitCode::SUCCESS;
+    let text = match response {
+        Response::Error { message } => return Err(anyhow!(message)),
+        Response::Cancelled { reason } => return Err(anyhow!("analysis cancelled: {}", reason)),
+        Response::AnalysisResult(result) => {
+            code = exit_code(&result);
+            report(&result, cli.format, style)
+        }
+        Response::History { entries } => output::history(&entries, now(), style),
+        Response::VoiceAnswer { text } => format!("{}\n", render::clean(&text)),
+        Response::Pong => format!("senior-daemon is running at {}\n", socket.display()),
+        Response::Status(status) => output::status(&status, &socket, style),
+        other => return Err(anyhow!("unexpected reply from the daemon: {:?}", other)),
+    };
+    if !cli.json {
+        print!("{}", text);
+    }
+    Ok(code)
+}
+
+/// `result` in `format`, coloured as `style` says when it is text.
+fn report(result: &AnalysisResult, format: RenderFormat, style: Style) -> String {
+    match format {
+        RenderFormat::Text => render::text(result, style),
+        format => render::render(result, format),
+    }
+}
+
+fn exit_code(result: &AnalysisResult) -> ExitCode {
+    if result.risk_level.trim().eq_ignore_ascii_case("high") {
+        ExitCode::from(EXIT_HIGH_RISK)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// The analysis saved at `path` (stdin for `-`): a bare result, as
+/// `senior-daemon --once` prints it, or the `analysis_result` frame `--json`
+/// prints.
+fn read_result(path: &Path) -> Result<AnalysisResult> {
+    let mut value: serde_json::Value = serde_json::from_str(&read_input(path)?)
+        .map_err(|e| anyhow!("{} is not JSON: {}", path.display(), e))?;
+    if value["type"] == "analysis_result" {
+        value = value["payload"].take();
+    }
+    serde_json::from_value(value).map_err(|e| anyhow!("{} is not an analysis result: {}", path.display(), e))
+}
+
+/// The file at `path`, or stdin for `-`.
+fn read_input(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        let mut diff = String::new();
+        std::io::stdin().read_to_string(&mut diff)?;
+        return Ok(diff);
+    }
+    std::fs::read_to_string(path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))
+}
+
+/// Paths a unified diff changes, from its `diff --git` or `+++` lines.
+fn files_touched(diff: &str) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    for line in diff.lines() {
+        let path = match line.strip_prefix("diff --git ") {
+            Some(rest) => rest.split_once(" b/").map(|(_, b)| b),
+            None => line.strip_prefix("+++ b/"),
+        };
+        if let Some(path) = path.filter(|p| !files.iter().any(|f| f == p)) {
+            files.push(path.to_string());
+        }
+    }
+    files
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_touched_reads_git_and_plain_diffs() {
+        let git = "diff --git a/src/a.rs b/src/a.rs\n--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1 +1 @@\n-x\n+y\n\
+                   diff --git a/old.rs b/new.rs\nrename from old.rs\nrename to new.rs\n";
+        assert_eq!(files_touched(git), vec!["src/a.rs", "new.rs"]);
+        assert_eq!(files_touched("--- a/lib.py\n+++ b/lib.py\n@@ -1 +1 @@\n"), vec!["lib.py"]);
+    }
+}
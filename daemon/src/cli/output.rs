@@ -0,0 +1,121 @@
+use senior_daemon::protocol::{DaemonStatus, HistoryEntry};
+use senior_daemon::render::{clean, Style};
+use std::fmt::Write;
+use std::path::Path;
+
+/// One line per analysis: id, age, risk, branch and commit, file count and
+/// the first summary line. `now` is in Unix seconds.
+pub fn history(entries: &[HistoryEntry], now: i64, style: Style) -> String {
+    if entries.is_empty() {
+        return "No analyses recorded yet.\n".to_string();
+    }
+    let mut out = String::new();
+    for entry in entries {
+        let at = match (entry.branch.as_str(), entry.head.get(..7).unwrap_or(&entry.head)) {
+            ("", "") => "-".to_string(),
+            (branch, "") => branch.to_string(),
+            ("", head) => head.to_string(),
+            (branch, head) => format!("{}@{}", branch, head),
+        };
+        let _ = writeln!(
+            out,
+            "#{:<5} {:>8}  {:<4}  {}  {} file(s)  {}",
+            entry.id,
+            age(now - entry.ts),
+            style.risk(&entry.result.risk_level),
+            style.dim(&at),
+            entry.files.len(),
+            clean(entry.result.summary.first().map(String::as_str).unwrap_or_default())
+        );
+    }
+    out
+}
+
+/// The daemon's `status`, and the socket it answered on.
+pub fn status(status: &DaemonStatus, socket: &Path, style: Style) -> String {
+    let model = match (status.llm_loaded, status.model_id.split_once(':')) {
+        (true, _) => status.model_id.clone(),
+        (false, Some((kind, _))) => format!("none ({} backend in stub mode)", kind.to_lowercase()),
+        (false, None) => "none (stub mode)".to_string(),
+    };
+    let mut out = String::new();
+    let _ = writeln!(out, "{}  {}", style.bold("version"), status.version);
+    let _ = writeln!(out, "{}    {}", style.bold("model"), model);
+    let _ = writeln!(out, "{}     {}", style.bold("busy"), if status.busy { "yes" } else { "no" });
+    let _ = writeln!(out, "{}   {}", style.bold("uptime"), age(status.uptime_secs as i64).trim_end_matches(" ago"));
+    let _ = writeln!(out, "{}   {}", style.bold("config"), status.config.as_deref().unwrap_or("none"));
+    let _ = writeln!(out, "{}   {}", style.bold("socket"), socket.display());
+    out
+}
+
+/// `seconds` as a rough age: `just now`, `5m ago`, `3h ago`, `2d ago`.
+fn age(seconds: i64) -> String {
+    match seconds.max(0) {
+        s if s < 60 => "just now".to_string(),
+        s if s < 3600 => format!("{}m ago", s / 60),
+        s if s < 86400 => format!("{}h ago", s / 3600),
+        s => format!("{}d ago", s / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use senior_daemon::protocol::AnalysisResult;
+
+    fn result() -> AnalysisResult {
+        AnalysisResult {
+            summary: vec!["renamed the cache".to_string()],
+            risk_level: "high".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn history_lines_are_compact() {
+        let entry = HistoryEntry {
+            id: 7,
+            ts: 1_000,
+            repo: "/r".into(),
+            branch: "main".into(),
+            head: "0123456789abcdef".into(),
+            trigger: "cli".into(),
+            diff_hash: String::new(),
+            files: vec!["a".into(), "b".into()],
+            model: String::new(),
+            latency_ms: 5,
+            result: result(),
+        };
+        assert_eq!(
+            history(std::slice::from_ref(&entry), 1_000 + 7_200, Style::PLAIN),
+            "#7       2h ago  HIGH  main@0123456  2 file(s)  renamed the cache\n"
+        );
+        assert_eq!(history(&[], 0, Style::PLAIN), "No analyses recorded yet.\n");
+
+        let mut loud = entry;
+        loud.result.summary = vec!["renamed\x1b[2J the\ncache".into()];
+        assert!(history(&[loud], 1_000, Style::PLAIN).ends_with("  renamed[2J the cache\n"));
+    }
+
+    #[test]
+    fn status_names_the_backend_in_stub_mode() {
+        let status = DaemonStatus {
+            version: "0.1.0".into(),
+            model_id: "Cactus:".into(),
+            llm_loaded: false,
+            busy: false,
+            uptime_secs: 90,
+            config: None,
+        };
+        let text = super::status(&status, Path::new("/run/senior.sock"), Style::PLAIN);
+        assert!(text.contains("model    none (cactus backend in stub mode)\n"), "got:\n{}", text);
+        assert!(text.contains("uptime   1m\n") && text.contains("socket   /run/senior.sock\n"), "got:\n{}", text);
+    }
+
+    #[test]
+    fn ages_round_down() {
+        assert_eq!(age(59), "just now");
+        assert_eq!(age(61), "1m ago");
+        assert_eq!(age(2 * 86400 + 5), "2d ago");
+    }
+}
@@ -0,0 +1,426 @@
+use crate::llm::backend::{BackendKind, Sampling, DEFAULT_OPENAI_BASE_URL, DEFAULT_PROMPT_TOKENS};
+use crate::llm::BackendConfig;
+use crate::paths::{self, expand, Locations, StorageFile};
+use crate::store::cache::{parse_ttl, DEFAULT_TTL};
+use anyhow::{anyhow, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A repository's own settings, read from its root.
+pub const REPO_CONFIG_NAME: &str = ".senior.toml";
+
+/// `senior.toml`. Every key is optional; unknown keys are errors so a typo
+/// does not silently fall back to a default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GlobalFile {
+    llm: LlmFile,
+    analysis: AnalysisFile,
+    storage: StorageFile,
+    risk: Vec<RiskRuleFile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LlmFile {
+    backend: Option<String>,
+    /// Weights directory, model name or fixture file, as for `BackendConfig::model`.
+    model: Option<String>,
+    base_url: Option<String>,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AnalysisFile {
+    prompt_tokens: Option<usize>,
+    cache_ttl_secs: Option<u64>,
+    ignore: Vec<String>,
+}
+
+/// `[[risk]]`: changing a file under `paths` makes the change at least `level`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RiskRuleFile {
+    paths: Vec<String>,
+    level: String,
+    #[serde(default)]
+    reason: String,
+}
+
+/// `.senior.toml`: only what may differ between repositories.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RepoFile {
+    analysis: RepoAnalysisFile,
+    risk: Vec<RiskRuleFile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RepoAnalysisFile {
+    prompt_tokens: Option<usize>,
+    ignore: Vec<String>,
+}
+
+/// Daemon settings: defaults, overridden by `senior.toml`, overridden by
+/// environment variables. Command-line flags, where there are any, win last.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The file that was read, if any.
+    pub source: Option<PathBuf>,
+    pub backend: BackendConfig,
+    pub analysis: Analysis,
+    /// Socket and data directory named in the file.
+    pub storage: Locations,
+}
+
+/// Settings that shape one analysis. A repository's `.senior.toml` can change
+/// the prompt budget and add ignore globs and risk rules.
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub prompt_tokens: usize,
+    /// Zero disables the analysis cache.
+    pub cache_ttl: Duration,
+    /// Changed files matching these are left out of the analysis.
+    pub ignore: Globs,
+    pub risk_rules: Vec<RiskRule>,
+}
+
+impl Default for Analysis {
+    fn default() -> Self {
+        Self {
+            prompt_tokens: DEFAULT_PROMPT_TOKENS,
+            cache_ttl: DEFAULT_TTL,
+            ignore: Globs::default(),
+            risk_rules: Vec::new(),
+        }
+    }
+}
+
+/// Path globs, matched against repo-relative paths. `*` crosses directories,
+/// so `*.lock` matches lockfiles anywhere.
+#[derive(Debug, Clone)]
+pub struct Globs {
+    patterns: Vec<String>,
+    set: GlobSet,
+}
+
+impl Default for Globs {
+    fn default() -> Self {
+        Self { patterns: Vec::new(), set: GlobSet::empty() }
+    }
+}
+
+impl Globs {
+    fn extend(&mut self, patterns: &[String]) -> Result<()> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in self.patterns.iter().chain(patterns) {
+            builder.add(Glob::new(pattern).map_err(|e| anyhow!("invalid glob '{}': {}", pattern, e))?);
+        }
+        self.set = builder.build()?;
+        self.patterns.extend(patterns.iter().cloned());
+        Ok(())
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.set.is_match(path)
+    }
+
+    /// The first pattern `path` matches.
+    pub fn matching(&self, path: &str) -> Option<&str> {
+        self.set.matches(path).first().map(|&i| self.patterns[i].as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RiskRule {
+    pub paths: Globs,
+    /// `low`, `med` or `high`.
+    pub level: String,
+    /// Shown as a risk reason; empty to name the matching glob instead.
+    pub reason: String,
+}
+
+impl Config {
+    /// Read the config file `paths::config_file` picks for `path` (the
+    /// `--config` flag) and layer the environment on top. Any invalid value is
+    /// an error naming the file or variable it came from.
+    pub fn load(path: Option<PathBuf>, env: &dyn Fn(&str) -> Option<String>) -> Result<Self> {
+        let source = paths::config_file(path, env);
+        let file: GlobalFile = match &source {
+            Some(path) => parse(path)?,
+            None => GlobalFile::default(),
+        };
+        let base = source.as_deref().and_then(Path::parent).unwrap_or(Path::new("."));
+        let at = |e: anyhow::Error| match &source {
+            Some(path) => anyhow!("{}: {}", path.display(), e),
+            None => e,
+        };
+
+        let backend = backend(&file.llm, base, env).map_err(at)?;
+        let mut analysis = Analysis::default();
+        apply_analysis(&mut analysis, file.analysis.prompt_tokens, &file.analysis.ignore, &file.risk).map_err(at)?;
+        if let Some(secs) = file.analysis.cache_ttl_secs {
+            analysis.cache_ttl = Duration::from_secs(secs);
+        }
+        if let Some(raw) = env("SENIOR_PROMPT_TOKENS") {
+            analysis.prompt_tokens = positive("SENIOR_PROMPT_TOKENS", &raw)?;
+        }
+        if let Some(raw) = env("SENIOR_CACHE_TTL_SECS") {
+            analysis.cache_ttl = parse_ttl(&raw)?;
+        }
+        let storage = file.storage.locations(base, env);
+        Ok(Self { source, backend, analysis, storage })
+    }
+}
+
+impl Analysis {
+    /// These settings with the `.senior.toml` at `root` applied, if there is one.
+    pub fn for_repo(&self, root: &Path) -> Result<Self> {
+        let path = root.join(REPO_CONFIG_NAME);
+        if !path.exists() {
+            return Ok(self.clone());
+        }
+        let file: RepoFile = parse(&path)?;
+        let mut settings = self.clone();
+        apply_analysis(&mut settings, file.analysis.prompt_tokens, &file.analysis.ignore, &file.risk)
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        Ok(settings)
+    }
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T> {
+    let text = std::fs::read_to_string(path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+    toml::from_str(&text).map_err(|e| anyhow!("{}: {}", path.display(), e.to_string().trim_end()))
+}
+
+fn backend(file: &LlmFile, base: &Path, env: &dyn Fn(&str) -> Option<String>) -> Result<BackendConfig> {
+    let file_kind = BackendKind::parse(file.backend.as_deref().unwrap_or_default())
+        .map_err(|e| anyhow!("llm.backend: {}", e))?;
+    let kind = match env("SENIOR_LLM_BACKEND") {
+        Some(name) => BackendKind::parse(&name)?,
+        None => file_kind,
+    };
+    let model_var = match kind {
+        BackendKind::Cactus => "CACTUS_MODEL_PATH",
+        BackendKind::OpenAi => "SENIOR_OPENAI_MODEL",
+        BackendKind::Scripted => "SENIOR_LLM_SCRIPT",
+    };
+    // The file's model belongs to the file's backend; another backend chosen
+    // through the environment gets its model from the environment too.
+    let file_model = file.model.as_deref().filter(|_| kind == file_kind).map(|m| match kind {
+        BackendKind::OpenAi => m.to_string(),
+        BackendKind::Cactus | BackendKind::Scripted => expand(m, base, env).to_string_lossy().into_owned(),
+    });
+    let mut sampling = Sampling::default();
+    if let Some(max_tokens) = file.max_tokens {
+        if max_tokens == 0 {
+            return Err(anyhow!("llm.max_tokens must be positive"));
+        }
+        sampling.max_tokens = max_tokens;
+    }
+    if let Some(temperature) = file.temperature {
+        if !(0.0..=2.0).contains(&temperature) {
+            return Err(anyhow!("llm.temperature must be between 0 and 2, got {}", temperature));
+        }
+        sampling.temperature = temperature;
+    }
+    Ok(BackendConfig {
+        kind,
+        model: env(model_var).or(file_model).unwrap_or_default(),
+        base_url: env("SENIOR_OPENAI_BASE_URL")
+            .or_else(|| file.base_url.clone())
+            .unwrap_or_else(|| DEFAULT_OPENAI_BASE_URL.to_string()),
+        sampling,
+    })
+}
+
+/// Fold one file's `[analysis]` and `[[risk]]` into `settings`.
+fn apply_analysis(
+    settings: &mut Analysis,
+    prompt_tokens: Option<usize>,
+    ignore: &[String],
+    risk: &[RiskRuleFile],
+) -> Result<()> {
+    if let Some(tokens) = prompt_tokens {
+        if tokens == 0 {
+            return Err(anyhow!("analysis.prompt_tokens must be positive"));
+        }
+        settings.prompt_tokens = tokens;
+    }
+    settings.ignore.extend(ignore).map_err(|e| anyhow!("analysis.ignore: {}", e))?;
+    for (i, rule) in risk.iter().enumerate() {
+        let at = |e: anyhow::Error| anyhow!("risk rule {}: {}", i + 1, e);
+        if !matches!(rule.level.as_str(), "low" | "med" | "high") {
+            return Err(at(anyhow!("level must be low, med or high, got '{}'", rule.level)));
+        }
+        if rule.paths.is_empty() {
+            return Err(at(anyhow!("paths must name at least one glob")));
+        }
+        let mut paths = Globs::default();
+        paths.extend(&rule.paths).map_err(at)?;
+        settings.risk_rules.push(RiskRule { paths, level: rule.level.clone(), reason: rule.reason.clone() });
+    }
+    Ok(())
+}
+
+fn positive(name: &str, raw: &str) -> Result<usize> {
+    match raw.trim().parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(anyhow!("{} must be a positive integer, got '{}'", name, raw)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{load_config, TempDir};
+    use std::fs;
+
+    /// A config file in a fresh temp directory, removed on drop.
+    struct TempConfig {
+        dir: TempDir,
+    }
+
+    impl TempConfig {
+        fn new(name: &str, global: &str) -> Self {
+            let dir = TempDir::new(&format!("config-{}", name));
+            dir.write("senior.toml", global);
+            Self { dir }
+        }
+
+        fn path(&self) -> PathBuf {
+            self.dir.root.join("senior.toml")
+        }
+
+        fn load(&self, vars: &[(&str, &str)]) -> Result<Config> {
+            load_config(&self.path(), vars)
+        }
+    }
+
+    const FULL: &str = r#"
+        [llm]
+        backend = "cactus"
+        model = "models/gemma"
+        max_tokens = 256
+        temperature = 0.0
+
+        [analysis]
+        prompt_tokens = 4096
+        cache_ttl_secs = 0
+        ignore = ["*.lock", "dist/**"]
+
+        [storage]
+        socket = "~/senior.sock"
+        data_dir = "/var/lib/senior"
+
+        [[risk]]
+        paths = ["migrations/**"]
+        level = "high"
+        reason = "Migrations need a rollout plan"
+    "#;
+
+    #[test]
+    fn without_a_file_everything_defaults() {
+        let config = Config::load(None, &|_| None).unwrap();
+        assert!(config.source.is_none());
+        assert_eq!(config.backend.kind, BackendKind::Cactus);
+        assert_eq!(config.backend.model, "");
+        assert_eq!(config.backend.sampling, Sampling::default());
+        assert_eq!(config.analysis.prompt_tokens, DEFAULT_PROMPT_TOKENS);
+        assert_eq!(config.analysis.cache_ttl, DEFAULT_TTL);
+    }
+
+    #[test]
+    fn file_sets_every_section() {
+        let tmp = TempConfig::new("full", FULL);
+        let config = tmp.load(&[("HOME", "/home/me")]).unwrap();
+        assert_eq!(config.source, Some(tmp.path()));
+        assert_eq!(config.backend.model, tmp.dir.root.join("models/gemma").to_string_lossy());
+        assert_eq!(config.backend.sampling, Sampling { max_tokens: 256, temperature: 0.0 });
+        assert_eq!(config.analysis.prompt_tokens, 4096);
+        assert_eq!(config.analysis.cache_ttl, Duration::ZERO);
+        assert!(config.analysis.ignore.is_match("Cargo.lock"));
+        assert!(config.analysis.ignore.is_match("web/yarn.lock"));
+        assert!(config.analysis.ignore.is_match("dist/app.js"));
+        assert!(!config.analysis.ignore.is_match("src/dist.rs"));
+        assert_eq!(config.analysis.risk_rules[0].level, "high");
+        assert_eq!(config.storage.socket, Some(PathBuf::from("/home/me/senior.sock")));
+        assert_eq!(config.storage.data_dir, Some(PathBuf::from("/var/lib/senior")));
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let tmp = TempConfig::new("env", FULL);
+        let config = tmp
+            .load(&[
+                ("CACTUS_MODEL_PATH", "/models/env"),
+                ("SENIOR_PROMPT_TOKENS", "1024"),
+                ("SENIOR_CACHE_TTL_SECS", "60"),
+            ])
+            .unwrap();
+        assert_eq!(config.backend.model, "/models/env");
+        assert_eq!(config.analysis.prompt_tokens, 1024);
+        assert_eq!(config.analysis.cache_ttl, Duration::from_secs(60));
+
+        let config = tmp.load(&[("SENIOR_LLM_BACKEND", "openai")]).unwrap();
+        assert_eq!(config.backend.kind, BackendKind::OpenAi);
+        assert_eq!(config.backend.model, "", "the file's model is for its own backend");
+    }
+
+    #[test]
+    fn invalid_values_name_the_file_and_key() {
+        let cases = [
+            ("[llm]\nbackend = \"gpt\"\n", "llm.backend: unknown LLM backend 'gpt'"),
+            ("[llm]\ntemperature = 3.0\n", "llm.temperature must be between 0 and 2"),
+            ("[analysis]\nprompt_tokens = 0\n", "analysis.prompt_tokens must be positive"),
+            ("[analysis]\nignore = [\"src/[\"]\n", "analysis.ignore: invalid glob 'src/['"),
+            ("[[risk]]\npaths = [\"a\"]\nlevel = \"severe\"\n", "risk rule 1: level must be low, med or high"),
+            ("[[risk]]\npaths = []\nlevel = \"high\"\n", "risk rule 1: paths must name at least one glob"),
+            ("[analysis]\nprompt_tokenz = 1\n", "unknown field `prompt_tokenz`"),
+            ("[llm\n", "senior.toml"),
+        ];
+        for (i, (text, expected)) in cases.iter().enumerate() {
+            let tmp = TempConfig::new(&format!("bad-{}", i), text);
+            let err = tmp.load(&[]).unwrap_err().to_string();
+            assert!(err.starts_with(&tmp.path().display().to_string()), "got: {}", err);
+            assert!(err.contains(expected), "expected '{}' in: {}", expected, err);
+        }
+        let err = TempConfig::new("bad-env", "").load(&[("SENIOR_PROMPT_TOKENS", "lots")]).unwrap_err();
+        assert!(err.to_string().starts_with("SENIOR_PROMPT_TOKENS must be a positive integer"), "got: {}", err);
+    }
+
+    #[test]
+    fn explicit_file_must_exist_but_default_may_not() {
+        let missing = Config::load(Some(PathBuf::from("/nonexistent/senior.toml")), &|_| None);
+        assert!(missing.unwrap_err().to_string().contains("cannot read /nonexistent/senior.toml"));
+        let config = Config::load(None, &|name| (name == "HOME").then(|| "/nonexistent".to_string())).unwrap();
+        assert!(config.source.is_none());
+    }
+
+    #[test]
+    fn repo_file_extends_analysis_settings() {
+        let tmp = TempConfig::new("repo", FULL);
+        let global = tmp.load(&[]).unwrap().analysis;
+        assert_eq!(global.for_repo(&tmp.dir.root.join("nowhere")).unwrap().prompt_tokens, 4096);
+
+        fs::write(
+            tmp.dir.root.join(REPO_CONFIG_NAME),
+            "[analysis]\nprompt_tokens = 512\nignore = [\"docs/**\"]\n\n[[risk]]\npaths = [\"src/auth/**\"]\nlevel = \"med\"\n",
+        )
+        .unwrap();
+        let repo = global.for_repo(&tmp.dir.root).unwrap();
+        assert_eq!(repo.prompt_tokens, 512);
+        assert!(repo.ignore.is_match("docs/a.md") && repo.ignore.is_match("Cargo.lock"));
+        assert_eq!(repo.risk_rules.len(), 2);
+        assert_eq!(repo.risk_rules[1].paths.matching("src/auth/login.rs"), Some("src/auth/**"));
+
+        fs::write(tmp.dir.root.join(REPO_CONFIG_NAME), "[llm]\nmodel = \"x\"\n").unwrap();
+        let err = global.for_repo(&tmp.dir.root).unwrap_err().to_string();
+        assert!(err.contains(".senior.toml") && err.contains("unknown field `llm`"), "got: {}", err);
+    }
+}
@@ -0,0 +1,285 @@
+//! Running git for the daemon and the `senior` client alike, so both take
+//! diffs the same way.
+
+use crate::protocol::DiffSelector;
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use std::process::Command;
+
+/// Lines of context around each hunk. Matches what the extension used to send.
+const CONTEXT_LINES: &str = "--unified=5";
+
+/// Passed to every `git diff`, so the user's git config cannot colour the
+/// patch, hand it to an external diff tool or change the `a/` and `b/`
+/// prefixes `parse_diff` strips.
+const DIFF_FLAGS: &[&str] = &["--no-color", "--no-ext-diff", "--src-prefix=a/", "--dst-prefix=b/", CONTEXT_LINES];
+
+/// The top level of the repository containing `repo_root`, which may be one
+/// of its subdirectories. Diff paths are relative to it, so the workspace
+/// index, blast radius and `.senior.toml` must be too. Blocking.
+pub fn toplevel(repo_root: &str) -> Result<String> {
+    let root = Path::new(repo_root);
+    if !root.is_absolute() || !root.is_dir() {
+        return Err(anyhow!("repo_root must be an absolute directory path, got '{}'", repo_root));
+    }
+    Ok(run(root, &["rev-parse", "--show-toplevel"])?.trim().to_string())
+}
+
+/// The root of the git repository containing `dir`, or `dir` itself made
+/// absolute when it is not in one. Analyses and history are keyed by it.
+pub fn workspace(dir: &Path) -> String {
+    run(dir, &["rev-parse", "--show-toplevel"])
+        .ok()
+        .map(|out| out.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .or_else(|| dir.canonicalize().ok().map(|p| p.display().to_string()))
+        .unwrap_or_else(|| dir.display().to_string())
+}
+
+/// Compute the unified diff described by `selector` for the repository at
+/// `repo_root` by shelling out to `git`. Blocking — call from `spawn_blocking`.
+pub fn diff(repo_root: &str, selector: &DiffSelector) -> Result<String> {
+    let root = Path::new(repo_root);
+    if !root.is_absolute() || !root.is_dir() {
+        return Err(anyhow!("repo_root must be an absolute directory path, got '{}'", repo_root));
+    }
+
+    match selector {
+        DiffSelector::WorkingTree => run_diff(root, &["HEAD"]),
+        DiffSelector::Staged => run_diff(root, &["--cached"]),
+        DiffSelector::CommitRange { from, to } => {
+            let to = to.as_deref().unwrap_or("HEAD");
+            run_diff(root, &[check_ref(from)?, check_ref(to)?])
+        }
+        DiffSelector::Branch { base } => {
+            let range = format!("{}...HEAD", check_ref(base)?);
+            run_diff(root, &[&range])
+        }
+        DiffSelector::LastChange => {
+            // Uncommitted work first; with a clean tree fall back to the last commit.
+            let working = run_diff(root, &["HEAD"])?;
+            if !working.trim().is_empty() || run(root, &["rev-parse", "--verify", "HEAD~1"]).is_err() {
+                return Ok(working);
+            }
+            run_diff(root, &["HEAD~1", "HEAD"])
+        }
+    }
+}
+
+/// `git diff args…` in `root` with `DIFF_FLAGS`.
+pub fn run_diff(root: &Path, args: &[&str]) -> Result<String> {
+    let args: Vec<&str> = ["diff"].iter().chain(args).chain(DIFF_FLAGS).copied().collect();
+    run(root, &args)
+}
+
+/// Current branch and commit of the repository at `repo_root`. Either is
+/// empty when git cannot say: a detached HEAD has no branch, and a repository
+/// without commits has no commit. Blocking — call from `spawn_blocking`.
+pub fn head(repo_root: &str) -> (String, String) {
+    let root = Path::new(repo_root);
+    let read = |args: &[&str]| run(root, args).map(|out| out.trim().to_string()).unwrap_or_default();
+    (
+        read(&["symbolic-ref", "--quiet", "--short", "HEAD"]),
+        read(&["rev-parse", "--quiet", "--verify", "HEAD"]),
+    )
+}
+
+/// Refuse anything git could mistake for an option.
+fn check_ref(r: &str) -> Result<&str> {
+    if r.is_empty() || r.starts_with('-') {
+        return Err(anyhow!("invalid git revision '{}'", r));
+    }
+    Ok(r)
+}
+
+/// Run git in `root` and return its stdout. Inherits the environment, so
+/// inside a hook it sees the index git is about to commit.
+pub fn run(root: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .map_err(|e| anyhow!("failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    /// Throwaway git repository under the system temp dir, removed on drop.
+    struct TempRepo {
+        dir: TempDir,
+    }
+
+    impl TempRepo {
+        fn new(name: &str) -> Self {
+            let repo = Self { dir: TempDir::new(&format!("git-{}", name)) };
+            repo.git(&["init", "-q", "-b", "main"]);
+            repo.git(&["config", "user.email", "test@example.com"]);
+            repo.git(&["config", "user.name", "test"]);
+            repo
+        }
+
+        fn git(&self, args: &[&str]) {
+            run(&self.dir.root, args).unwrap();
+        }
+
+        fn write(&self, path: &str, contents: &str) {
+            self.dir.write(path, contents);
+        }
+
+        fn commit(&self, path: &str, contents: &str, msg: &str) {
+            self.write(path, contents);
+            self.git(&["add", path]);
+            self.git(&["commit", "-q", "-m", msg]);
+        }
+
+        fn path(&self) -> &str {
+            self.dir.root.to_str().unwrap()
+        }
+    }
+
+    #[test]
+    fn head_names_branch_and_commit() {
+        let repo = TempRepo::new("head");
+        assert_eq!(head(repo.path()), ("main".to_string(), String::new()));
+
+        repo.commit("a.rs", "fn a() {}\n", "init");
+        let (branch, commit) = head(repo.path());
+        assert_eq!(branch, "main");
+        assert_eq!(commit.len(), 40, "got: {}", commit);
+
+        repo.git(&["checkout", "-q", "--detach"]);
+        assert_eq!(head(repo.path()), (String::new(), commit));
+    }
+
+    #[test]
+    fn working_tree_includes_unstaged_edits() {
+        let repo = TempRepo::new("working");
+        repo.commit("a.rs", "fn a() {}\n", "init");
+        repo.write("a.rs", "fn a() { todo!() }\n");
+        let diff = diff(repo.path(), &DiffSelector::WorkingTree).unwrap();
+        assert!(diff.contains("diff --git a/a.rs b/a.rs"), "got: {}", diff);
+        assert!(diff.contains("+fn a() { todo!() }"));
+    }
+
+    #[test]
+    fn staged_ignores_unstaged_edits() {
+        let repo = TempRepo::new("staged");
+        repo.commit("a.rs", "one\n", "init");
+        repo.commit("b.rs", "two\n", "second");
+        repo.write("a.rs", "one changed\n");
+        repo.git(&["add", "a.rs"]);
+        repo.write("b.rs", "two changed\n");
+        let diff = diff(repo.path(), &DiffSelector::Staged).unwrap();
+        assert!(diff.contains("a/a.rs"));
+        assert!(!diff.contains("a/b.rs"), "got: {}", diff);
+    }
+
+    #[test]
+    fn commit_range_defaults_to_head() {
+        let repo = TempRepo::new("range");
+        repo.commit("a.rs", "v1\n", "one");
+        repo.commit("a.rs", "v2\n", "two");
+        let sel = DiffSelector::CommitRange { from: "HEAD~1".into(), to: None };
+        let diff = diff(repo.path(), &sel).unwrap();
+        assert!(diff.contains("-v1") && diff.contains("+v2"), "got: {}", diff);
+    }
+
+    #[test]
+    fn branch_diffs_against_merge_base() {
+        let repo = TempRepo::new("branch");
+        repo.commit("a.rs", "base\n", "base");
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        repo.commit("feature.rs", "new\n", "feature work");
+        repo.git(&["checkout", "-q", "main"]);
+        repo.commit("main.rs", "main only\n", "main moves on");
+        repo.git(&["checkout", "-q", "feature"]);
+        let diff = diff(repo.path(), &DiffSelector::Branch { base: "main".into() }).unwrap();
+        assert!(diff.contains("feature.rs"));
+        assert!(!diff.contains("main.rs"), "merge-base diff must not include main's commits: {}", diff);
+    }
+
+    #[test]
+    fn last_change_falls_back_to_previous_commit_when_clean() {
+        let repo = TempRepo::new("last");
+        repo.commit("a.rs", "v1\n", "one");
+        repo.commit("a.rs", "v2\n", "two");
+        let diff = diff(repo.path(), &DiffSelector::LastChange).unwrap();
+        assert!(diff.contains("+v2"), "got: {}", diff);
+    }
+
+    #[test]
+    fn last_change_prefers_uncommitted_work() {
+        let repo = TempRepo::new("last-dirty");
+        repo.commit("a.rs", "v1\n", "one");
+        repo.commit("a.rs", "v2\n", "two");
+        repo.write("a.rs", "v3\n");
+        let diff = diff(repo.path(), &DiffSelector::LastChange).unwrap();
+        assert!(diff.contains("-v2") && diff.contains("+v3"), "got: {}", diff);
+    }
+
+    #[test]
+    fn last_change_on_single_clean_commit_is_empty() {
+        let repo = TempRepo::new("last-single");
+        repo.commit("a.rs", "v1\n", "one");
+        assert_eq!(diff(repo.path(), &DiffSelector::LastChange).unwrap(), "");
+    }
+
+    #[test]
+    fn option_like_revision_is_rejected() {
+        let repo = TempRepo::new("reject");
+        repo.commit("a.rs", "v1\n", "one");
+        let sel = DiffSelector::CommitRange { from: "--output=/tmp/x".into(), to: None };
+        let err = diff(repo.path(), &sel).unwrap_err();
+        assert!(err.to_string().contains("invalid git revision"), "got: {}", err);
+    }
+
+    #[test]
+    fn user_diff_config_does_not_change_the_patch() {
+        let repo = TempRepo::new("user-config");
+        repo.commit("a.rs", "v1\n", "one");
+        repo.git(&["config", "color.ui", "always"]);
+        repo.git(&["config", "diff.mnemonicPrefix", "true"]);
+        repo.git(&["config", "diff.noprefix", "true"]);
+        repo.git(&["config", "diff.external", "false"]);
+        repo.write("a.rs", "v2\n");
+        let diff = diff(repo.path(), &DiffSelector::WorkingTree).unwrap();
+        assert!(diff.starts_with("diff --git a/a.rs b/a.rs\n"), "got: {:?}", diff);
+        assert!(diff.contains("\n-v1\n+v2\n") && !diff.contains('\x1b'), "got: {:?}", diff);
+    }
+
+    #[test]
+    fn toplevel_of_a_subdirectory_is_the_repository_root() {
+        let repo = TempRepo::new("toplevel");
+        repo.commit("sub/a.rs", "v1\n", "one");
+        let sub = repo.dir.root.join("sub");
+        let top = toplevel(sub.to_str().unwrap()).unwrap();
+        assert_eq!(Path::new(&top).canonicalize().unwrap(), repo.dir.root.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn relative_repo_root_is_rejected() {
+        let err = diff("some/relative/path", &DiffSelector::WorkingTree).unwrap_err();
+        assert!(err.to_string().contains("absolute"), "got: {}", err);
+        let err = toplevel("some/relative/path").unwrap_err();
+        assert!(err.to_string().contains("absolute"), "got: {}", err);
+    }
+
+    #[test]
+    fn git_failure_surfaces_stderr() {
+        let dir = TempDir::new("git-notrepo");
+        let err = diff(dir.root.to_str().unwrap(), &DiffSelector::WorkingTree).unwrap_err();
+        assert!(err.to_string().contains("git diff HEAD"), "got: {}", err);
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// Why an analysis stopped before producing a result.
+pub const REASON_CANCELLED: &str = "cancelled by client";
+pub const REASON_SUPERSEDED: &str = "superseded by a newer analysis of the same workspace";
+
+/// Tracks in-flight analyses so they can be cancelled by request id, or
+/// superseded when a newer analysis of the same workspace arrives.
+#[derive(Default)]
+pub struct Inflight {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    next_seq: u64,
+    by_id: HashMap<String, Arc<Flag>>,
+    /// Workspace → the newest superseding analysis registered for it.
+    latest: HashMap<String, Arc<Flag>>,
+}
+
+#[derive(Default)]
+struct Flag {
+    seq: u64,
+    reason: Mutex<Option<&'static str>>,
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl Flag {
+    fn cancel(&self, reason: &'static str) {
+        let mut slot = self.reason.lock().expect("inflight mutex poisoned");
+        if slot.is_none() {
+            *slot = Some(reason);
+        }
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+}
+
+impl Inflight {
+    /// Register a new analysis. With `supersede` and a `workspace`, any older
+    /// analysis registered the same way for that workspace is cancelled.
+    pub fn register(
+        self: &Arc<Self>,
+        id: Option<&str>,
+        workspace: Option<&str>,
+        supersede: bool,
+    ) -> Ticket {
+        let mut state = self.state.lock().expect("inflight mutex poisoned");
+        state.next_seq += 1;
+        let flag = Arc::new(Flag { seq: state.next_seq, ..Flag::default() });
+
+        if let Some(id) = id {
+            state.by_id.insert(id.to_string(), flag.clone());
+        }
+        let workspace = workspace.filter(|_| supersede).map(String::from);
+        if let Some(ws) = &workspace {
+            if let Some(previous) = state.latest.insert(ws.clone(), flag.clone()) {
+                previous.cancel(REASON_SUPERSEDED);
+            }
+        }
+
+        Ticket {
+            registry: self.clone(),
+            id: id.map(String::from),
+            workspace,
+            flag,
+        }
+    }
+
+    /// Cancel the analysis registered under `id`. Returns false if no such
+    /// analysis is in flight (already finished, or never existed).
+    pub fn cancel(&self, id: &str) -> bool {
+        let state = self.state.lock().expect("inflight mutex poisoned");
+        match state.by_id.get(id) {
+            Some(flag) => {
+                flag.cancel(REASON_CANCELLED);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn release(&self, ticket: &Ticket) {
+        let mut state = self.state.lock().expect("inflight mutex poisoned");
+        // Only remove entries that still point at this ticket — an id may have
+        // been reused, and the workspace slot may belong to a newer analysis.
+        if let Some(id) = &ticket.id {
+            if state.by_id.get(id).is_some_and(|f| f.seq == ticket.flag.seq) {
+                state.by_id.remove(id);
+            }
+        }
+        if let Some(ws) = &ticket.workspace {
+            if state.latest.get(ws).is_some_and(|f| f.seq == ticket.flag.seq) {
+                state.latest.remove(ws);
+            }
+        }
+    }
+}
+
+/// Handle for one registered analysis; unregisters itself on drop.
+pub struct Ticket {
+    registry: Arc<Inflight>,
+    id: Option<String>,
+    workspace: Option<String>,
+    flag: Arc<Flag>,
+}
+
+impl Ticket {
+    /// The reason this analysis was cancelled, if it was.
+    pub fn cancelled(&self) -> Option<&'static str> {
+        if self.flag.cancelled.load(Ordering::SeqCst) {
+            *self.flag.reason.lock().expect("inflight mutex poisoned")
+        } else {
+            None
+        }
+    }
+
+    /// Whether the analysis has been cancelled, as a check that blocking
+    /// code can carry onto another thread.
+    pub fn watch(&self) -> impl Fn() -> bool + Send + 'static {
+        let flag = self.flag.clone();
+        move || flag.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolve once the analysis is cancelled, for use in `select!`.
+    pub async fn wait_cancelled(&self) -> &'static str {
+        loop {
+            let notified = self.flag.notify.notified();
+            if let Some(reason) = self.cancelled() {
+                return reason;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        self.registry.release(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn cancel_by_id() {
+        let inflight = Arc::new(Inflight::default());
+        let ticket = inflight.register(Some("a"), None, false);
+        assert_eq!(ticket.cancelled(), None);
+        assert!(inflight.cancel("a"));
+        assert_eq!(ticket.cancelled(), Some(REASON_CANCELLED));
+    }
+
+    #[test]
+    fn watch_sees_cancellation_from_another_thread() {
+        let inflight = Arc::new(Inflight::default());
+        let ticket = inflight.register(Some("a"), None, false);
+        let watch = ticket.watch();
+        assert!(!std::thread::spawn({
+            let watch = ticket.watch();
+            move || watch()
+        })
+        .join()
+        .unwrap());
+        inflight.cancel("a");
+        assert!(watch());
+    }
+
+    #[test]
+    fn cancel_unknown_id_returns_false() {
+        let inflight = Arc::new(Inflight::default());
+        assert!(!inflight.cancel("missing"));
+    }
+
+    #[test]
+    fn finished_ticket_can_no_longer_be_cancelled() {
+        let inflight = Arc::new(Inflight::default());
+        drop(inflight.register(Some("a"), None, false));
+        assert!(!inflight.cancel("a"));
+    }
+
+    #[test]
+    fn newer_analysis_supersedes_older_for_same_workspace() {
+        let inflight = Arc::new(Inflight::default());
+        let first = inflight.register(Some("1"), Some("/repo"), true);
+        let second = inflight.register(Some("2"), Some("/repo"), true);
+        assert_eq!(first.cancelled(), Some(REASON_SUPERSEDED));
+        assert_eq!(second.cancelled(), None);
+    }
+
+    #[test]
+    fn supersede_is_scoped_to_workspace() {
+        let inflight = Arc::new(Inflight::default());
+        let a = inflight.register(None, Some("/repo-a"), true);
+        let _b = inflight.register(None, Some("/repo-b"), true);
+        assert_eq!(a.cancelled(), None);
+    }
+
+    #[test]
+    fn non_superseding_requests_neither_cancel_nor_get_cancelled() {
+        let inflight = Arc::new(Inflight::default());
+        let quiet = inflight.register(None, Some("/repo"), false);
+        let loud = inflight.register(None, Some("/repo"), true);
+        assert_eq!(quiet.cancelled(), None);
+        let _another = inflight.register(None, Some("/repo"), false);
+        assert_eq!(loud.cancelled(), None);
+    }
+
+    #[test]
+    fn releasing_old_ticket_keeps_newer_registration() {
+        let inflight = Arc::new(Inflight::default());
+        let first = inflight.register(Some("x"), Some("/repo"), true);
+        let second = inflight.register(Some("x"), Some("/repo"), true);
+        drop(first);
+        assert!(inflight.cancel("x"), "id must still point at the newer ticket");
+        assert_eq!(second.cancelled(), Some(REASON_CANCELLED));
+    }
+
+    #[test]
+    fn first_reason_wins() {
+        let inflight = Arc::new(Inflight::default());
+        let first = inflight.register(Some("1"), Some("/repo"), true);
+        let _second = inflight.register(None, Some("/repo"), true);
+        inflight.cancel("1");
+        assert_eq!(first.cancelled(), Some(REASON_SUPERSEDED));
+    }
+
+    #[tokio::test]
+    async fn wait_cancelled_wakes_on_cancel() {
+        let inflight = Arc::new(Inflight::default());
+        let ticket = inflight.register(Some("a"), None, false);
+        let canceller = inflight.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            canceller.cancel("a");
+        });
+        let reason = tokio::time::timeout(Duration::from_secs(1), ticket.wait_cancelled())
+            .await
+            .expect("wait_cancelled must resolve");
+        assert_eq!(reason, REASON_CANCELLED);
+    }
+}
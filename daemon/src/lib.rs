@@ -0,0 +1,10 @@
+//! What `senior-daemon` shares with the `senior` command-line client: the wire
+//! protocol, where the socket lives, how git is run and how results are
+//! rendered as reports.
+
+pub mod git;
+pub mod paths;
+pub mod protocol;
+pub mod render;
+#[cfg(test)]
+mod temp_dir;
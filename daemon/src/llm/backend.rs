@@ -0,0 +1,157 @@
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+/// An inference engine the analyzer and voice paths can talk to.
+///
+/// Implementations are blocking: callers run them on `spawn_blocking` threads
+/// so the tokio runtime stays responsive while a model is generating.
+pub trait LlmBackend: Send + Sync {
+    /// Plain chat completion with a system prompt and a single user turn.
+    fn complete(&self, system_prompt: &str, user_message: &str) -> Result<String>;
+
+    /// Completion with OpenAI-style tool definitions. Returns the
+    /// `function_calls` array, each entry shaped `{"name": .., "arguments": {..}}`.
+    fn complete_with_tools(
+        &self,
+        user_message: &str,
+        tools_json: &str,
+    ) -> Result<Vec<serde_json::Value>>;
+
+    /// `complete`, calling `on_token` with each chunk of output as it is
+    /// generated. Backends that cannot stream fall back to one blocking call.
+    fn complete_streaming(
+        &self,
+        system_prompt: &str,
+        user_message: &str,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<String> {
+        let _ = on_token;
+        self.complete(system_prompt, user_message)
+    }
+
+    /// `complete_with_tools`, calling `on_token` with raw model output as it
+    /// is generated.
+    fn complete_with_tools_streaming(
+        &self,
+        user_message: &str,
+        tools_json: &str,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<Vec<serde_json::Value>> {
+        let _ = on_token;
+        self.complete_with_tools(user_message, tools_json)
+    }
+
+    /// Tokens `text` takes up in the model's context. Backends without
+    /// access to their tokenizer estimate.
+    fn count_tokens(&self, text: &str) -> usize {
+        estimate_tokens(text)
+    }
+}
+
+/// Rough token count for code and English prose: about four characters each.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Which backend to construct at startup: `llm.backend` in the config file or
+/// `SENIOR_LLM_BACKEND`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    Cactus,
+    OpenAi,
+    Scripted,
+}
+
+impl BackendKind {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "cactus" => Ok(Self::Cactus),
+            "openai" => Ok(Self::OpenAi),
+            "scripted" => Ok(Self::Scripted),
+            other => Err(anyhow!(
+                "unknown LLM backend '{}' (expected: cactus, openai, scripted)",
+                other
+            )),
+        }
+    }
+}
+
+pub const DEFAULT_OPENAI_BASE_URL: &str = "http://127.0.0.1:8080/v1";
+
+/// Leaves room for the tool schema and the answer in a 4k context.
+pub const DEFAULT_PROMPT_TOKENS: usize = 2048;
+
+/// Generation settings sent with every completion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    pub max_tokens: u32,
+    pub temperature: f32,
+}
+
+impl Default for Sampling {
+    /// Enough for a review; near-greedy so the JSON stays well-formed.
+    fn default() -> Self {
+        Self { max_tokens: 512, temperature: 0.1 }
+    }
+}
+
+/// Everything needed to construct a backend; see `config::Config::load`.
+#[derive(Debug, Clone)]
+pub struct BackendConfig {
+    pub kind: BackendKind,
+    /// Backend specific: the weights directory for Cactus, the model name for
+    /// OpenAI-compatible servers, the fixture file for the scripted backend.
+    /// Empty means no model is configured.
+    pub model: String,
+    pub base_url: String,
+    pub sampling: Sampling,
+}
+
+/// Construct the configured backend.
+pub fn load_backend(config: &BackendConfig) -> Result<Arc<dyn LlmBackend>> {
+    match config.kind {
+        BackendKind::Cactus => Ok(Arc::new(super::CactusLlm::new(&config.model, config.sampling)?)),
+        BackendKind::OpenAi => Ok(Arc::new(super::openai::OpenAiLlm::new(
+            &config.base_url,
+            &config.model,
+            config.sampling,
+        )?)),
+        BackendKind::Scripted => Ok(Arc::new(super::scripted::ScriptedLlm::load(&config.model)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_backend_kind_defaults_to_cactus() {
+        assert_eq!(BackendKind::parse("").unwrap(), BackendKind::Cactus);
+        assert_eq!(BackendKind::parse("cactus").unwrap(), BackendKind::Cactus);
+        assert_eq!(BackendKind::parse(" Cactus ").unwrap(), BackendKind::Cactus);
+    }
+
+    #[test]
+    fn parse_backend_kind_openai() {
+        assert_eq!(BackendKind::parse("openai").unwrap(), BackendKind::OpenAi);
+        assert_eq!(BackendKind::parse("OpenAI").unwrap(), BackendKind::OpenAi);
+    }
+
+    #[test]
+    fn parse_backend_kind_scripted() {
+        assert_eq!(BackendKind::parse("scripted").unwrap(), BackendKind::Scripted);
+    }
+
+    #[test]
+    fn estimate_rounds_up_to_whole_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abc"), 1);
+        assert_eq!(estimate_tokens("abcdefghi"), 3);
+    }
+
+    #[test]
+    fn parse_unknown_backend_kind_is_error() {
+        let err = BackendKind::parse("gpt-9").unwrap_err();
+        assert!(err.to_string().contains("unknown LLM backend"), "got: {}", err);
+    }
+}
@@ -1,3 +1,6 @@
+use super::envelope::{parse_cactus_response, parse_function_calls};
+use super::backend::{estimate_tokens, Sampling};
+use super::LlmBackend;
 use anyhow::{anyhow, Result};
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int, c_void};
@@ -26,11 +29,20 @@ extern "C" {
 
     fn cactus_reset(model: *mut c_void);
 
+    fn cactus_tokenize(
+        model: *mut c_void,
+        text: *const c_char,
+        token_buffer: *mut u32,
+        token_buffer_len: usize,
+        out_token_len: *mut usize,
+    ) -> c_int;
+
     fn cactus_get_last_error() -> *const c_char;
 }
 
 pub struct CactusLlm {
     model: Mutex<*mut c_void>,
+    sampling: Sampling,
 }
 
 // Safety: cactus_model_t is an opaque pointer, access serialized via Mutex
@@ -38,7 +50,7 @@ unsafe impl Send for CactusLlm {}
 unsafe impl Sync for CactusLlm {}
 
 impl CactusLlm {
-    pub fn new(model_path: &str) -> Result<Self> {
+    pub fn new(model_path: &str, sampling: Sampling) -> Result<Self> {
         let model_path_c = CString::new(model_path)?;
         let corpus_dir_c = CString::new("")?;
 
@@ -60,76 +72,29 @@ impl CactusLlm {
 
         Ok(Self {
             model: Mutex::new(model),
+            sampling,
         })
     }
 
-    pub fn complete(&self, system_prompt: &str, user_message: &str) -> Result<String> {
-        let messages = serde_json::json!([
-            { "role": "system", "content": system_prompt },
-            { "role": "user", "content": user_message }
-        ]);
-        let messages_c = CString::new(messages.to_string())?;
-
-        let options = serde_json::json!({
-            "max_tokens": 512,
-            "temperature": 0.1
-        });
-        let options_c = CString::new(options.to_string())?;
-
-        let raw_json = self.run_complete(messages_c, options_c, std::ptr::null())?;
-        parse_cactus_response(&raw_json)
-    }
-
-    /// Call the model using native function-calling.
-    /// We intentionally avoid `force_tools` because it can hang on some models;
-    /// instead we rely on the model's tool-calling fine-tune and parse the
-    /// `function_calls` array from the cactus response envelope.
-    pub fn complete_with_tools(
-        &self,
-        user_message: &str,
-        tools_json: &str,
-    ) -> Result<Vec<serde_json::Value>> {
-        let messages = serde_json::json!([
-            { "role": "user", "content": user_message }
-        ]);
-        let messages_c = CString::new(messages.to_string())?;
-
-        let options = serde_json::json!({
-            "max_tokens": 512,
-            "temperature": 0.1,
-            "confidence_threshold": 0.0,
-            "tool_rag_top_k": 0
-        });
-        let options_c = CString::new(options.to_string())?;
-        let tools_c = CString::new(tools_json)?;
-
-        let raw_json = self.run_complete(messages_c, options_c, tools_c.as_ptr())?;
-        debug!("cactus raw response (tools): {}", raw_json);
-
-        let parsed: serde_json::Value = serde_json::from_str(&raw_json)
-            .map_err(|e| anyhow!("failed to parse cactus envelope: {}: {}", e, raw_json))?;
-
-        if parsed["success"].as_bool() != Some(true) {
-            let err = parsed["error"].as_str().unwrap_or("unknown error");
-            return Err(anyhow!("cactus returned failure: {}", err));
-        }
-
-        let calls = parsed["function_calls"]
-            .as_array()
-            .cloned()
-            .unwrap_or_default();
-        Ok(calls)
-    }
-
     fn run_complete(
         &self,
         messages_c: CString,
         options_c: CString,
         tools_ptr: *const c_char,
+        on_token: Option<&mut dyn FnMut(&str)>,
     ) -> Result<String> {
         // 8KB response buffer
         let mut response_buf: Vec<c_char> = vec![0; 8192];
 
+        // The callback's user_data is a pointer to this fat `&mut dyn FnMut`,
+        // which lives on our stack for the whole (synchronous) cactus_complete call.
+        let mut on_token = on_token;
+        let (callback, user_data): (Option<extern "C" fn(*const c_char, u32, *mut c_void)>, *mut c_void) =
+            match on_token.as_mut() {
+                Some(cb) => (Some(token_trampoline), cb as *mut &mut dyn FnMut(&str) as *mut c_void),
+                None => (None, std::ptr::null_mut()),
+            };
+
         let model = self.model.lock().unwrap();
         // Clear any cached conversation state from previous calls so each
         // invocation starts from a fresh context.
@@ -142,8 +107,8 @@ impl CactusLlm {
                 response_buf.len(),
                 options_c.as_ptr(),
                 tools_ptr,
-                None,
-                std::ptr::null_mut(),
+                callback,
+                user_data,
             )
         };
 
@@ -168,113 +133,150 @@ impl CactusLlm {
         debug!("cactus raw response: {}", raw_json);
         Ok(raw_json)
     }
-}
 
-/// Parse the cactus response envelope: `{"success":true,"response":"..."}`.
-/// Extracted as a pure function so it can be unit tested without FFI.
-fn parse_cactus_response(raw_json: &str) -> Result<String> {
-    let parsed: serde_json::Value = serde_json::from_str(raw_json)
-        .map_err(|e| anyhow!("failed to parse cactus response JSON: {}: {}", e, raw_json))?;
+    fn chat(
+        &self,
+        system_prompt: &str,
+        user_message: &str,
+        on_token: Option<&mut dyn FnMut(&str)>,
+    ) -> Result<String> {
+        let messages = serde_json::json!([
+            { "role": "system", "content": system_prompt },
+            { "role": "user", "content": user_message }
+        ]);
+        let messages_c = CString::new(messages.to_string())?;
 
-    if parsed["success"].as_bool() != Some(true) {
-        let err = parsed["error"].as_str().unwrap_or("unknown error");
-        return Err(anyhow!("cactus returned failure: {}", err));
-    }
+        let options = serde_json::json!({
+            "max_tokens": self.sampling.max_tokens,
+            "temperature": self.sampling.temperature
+        });
+        let options_c = CString::new(options.to_string())?;
 
-    let text = parsed["response"]
-        .as_str()
-        .ok_or_else(|| anyhow!("cactus response missing 'response' field: {}", raw_json))?
-        .to_string();
+        let raw_json = self.run_complete(messages_c, options_c, std::ptr::null(), on_token)?;
+        parse_cactus_response(&raw_json)
+    }
 
-    Ok(text)
-}
+    /// Call the model using native function-calling.
+    /// We intentionally avoid `force_tools` because it can hang on some models;
+    /// instead we rely on the model's tool-calling fine-tune and parse the
+    /// `function_calls` array from the cactus response envelope.
+    fn chat_with_tools(
+        &self,
+        user_message: &str,
+        tools_json: &str,
+        on_token: Option<&mut dyn FnMut(&str)>,
+    ) -> Result<Vec<serde_json::Value>> {
+        let messages = serde_json::json!([
+            { "role": "user", "content": user_message }
+        ]);
+        let messages_c = CString::new(messages.to_string())?;
 
-impl Drop for CactusLlm {
-    fn drop(&mut self) {
-        let model = self.model.lock().unwrap();
-        if !model.is_null() {
-            unsafe { cactus_destroy(*model) };
-        }
-    }
-}
+        let options = serde_json::json!({
+            "max_tokens": self.sampling.max_tokens,
+            "temperature": self.sampling.temperature,
+            "confidence_threshold": 0.0,
+            "tool_rag_top_k": 0
+        });
+        let options_c = CString::new(options.to_string())?;
+        let tools_c = CString::new(tools_json)?;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let raw_json = self.run_complete(messages_c, options_c, tools_c.as_ptr(), on_token)?;
+        debug!("cactus raw response (tools): {}", raw_json);
 
-    #[test]
-    fn parse_valid_envelope_returns_response_text() {
-        let json = r#"{"success":true,"response":"Hello world"}"#;
-        let result = parse_cactus_response(json);
-        assert_eq!(result.unwrap(), "Hello world");
+        parse_function_calls(&raw_json)
     }
+}
 
-    #[test]
-    fn parse_envelope_with_extra_fields_returns_response_text() {
-        let json = r#"{"success":true,"response":"hi","confidence":0.85,"tokens":12}"#;
-        let result = parse_cactus_response(json);
-        assert_eq!(result.unwrap(), "hi");
+/// Token callback handed to `cactus_complete`; forwards each decoded token to
+/// the Rust closure smuggled through `user_data`. A panic in the closure must
+/// not unwind into C, so it stops here: the panic hook has already reported
+/// it, and the token is dropped while inference carries on.
+extern "C" fn token_trampoline(token: *const c_char, _token_id: u32, user_data: *mut c_void) {
+    if token.is_null() || user_data.is_null() {
+        return;
     }
+    let on_token = unsafe { &mut *(user_data as *mut &mut dyn FnMut(&str)) };
+    let text = unsafe { CStr::from_ptr(token) }.to_string_lossy();
+    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| on_token(&text)));
+}
 
-    #[test]
-    fn parse_failure_envelope_with_error_field() {
-        let json = r#"{"success":false,"error":"out of memory"}"#;
-        let err = parse_cactus_response(json).unwrap_err();
-        assert!(err.to_string().contains("out of memory"), "got: {}", err);
+impl LlmBackend for CactusLlm {
+    fn complete(&self, system_prompt: &str, user_message: &str) -> Result<String> {
+        self.chat(system_prompt, user_message, None)
     }
 
-    #[test]
-    fn parse_failure_envelope_without_error_field_uses_unknown() {
-        let json = r#"{"success":false}"#;
-        let err = parse_cactus_response(json).unwrap_err();
-        assert!(err.to_string().contains("unknown error"), "got: {}", err);
+    fn complete_with_tools(
+        &self,
+        user_message: &str,
+        tools_json: &str,
+    ) -> Result<Vec<serde_json::Value>> {
+        self.chat_with_tools(user_message, tools_json, None)
     }
 
-    #[test]
-    fn parse_success_true_but_missing_response_field_is_error() {
-        let json = r#"{"success":true}"#;
-        let err = parse_cactus_response(json).unwrap_err();
-        assert!(err.to_string().contains("missing 'response' field"), "got: {}", err);
+    fn complete_streaming(
+        &self,
+        system_prompt: &str,
+        user_message: &str,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<String> {
+        self.chat(system_prompt, user_message, Some(on_token))
     }
 
-    #[test]
-    fn parse_response_field_non_string_is_error() {
-        let json = r#"{"success":true,"response":42}"#;
-        let err = parse_cactus_response(json).unwrap_err();
-        assert!(err.to_string().contains("missing 'response' field"), "got: {}", err);
+    fn complete_with_tools_streaming(
+        &self,
+        user_message: &str,
+        tools_json: &str,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<Vec<serde_json::Value>> {
+        self.chat_with_tools(user_message, tools_json, Some(on_token))
     }
 
-    #[test]
-    fn parse_invalid_json_returns_parse_error() {
-        let err = parse_cactus_response("not json at all").unwrap_err();
-        assert!(
-            err.to_string().contains("failed to parse cactus response JSON"),
-            "got: {}", err
-        );
+    fn count_tokens(&self, text: &str) -> usize {
+        let Ok(text_c) = CString::new(text) else { return estimate_tokens(text) };
+        // A token covers at least one byte; leave room for BOS/EOS markers.
+        let mut tokens = vec![0u32; text.len() + 8];
+        let mut count = 0usize;
+        let model = self.model.lock().unwrap();
+        let ret = unsafe {
+            cactus_tokenize(*model, text_c.as_ptr(), tokens.as_mut_ptr(), tokens.len(), &mut count)
+        };
+        if ret < 0 {
+            debug!("cactus_tokenize failed (ret={}); estimating", ret);
+            return estimate_tokens(text);
+        }
+        count
     }
+}
 
-    #[test]
-    fn parse_empty_string_returns_parse_error() {
-        let err = parse_cactus_response("").unwrap_err();
-        assert!(
-            err.to_string().contains("failed to parse cactus response JSON"),
-            "got: {}", err
-        );
+impl Drop for CactusLlm {
+    fn drop(&mut self) {
+        let model = self.model.lock().unwrap();
+        if !model.is_null() {
+            unsafe { cactus_destroy(*model) };
+        }
     }
+}
 
-    #[test]
-    fn parse_json_null_is_treated_as_failure() {
-        // null["success"].as_bool() is None, not Some(true) → failure path
-        let err = parse_cactus_response("null").unwrap_err();
-        assert!(err.to_string().contains("cactus returned failure"), "got: {}", err);
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     #[test]
-    fn parse_response_empty_string_is_ok() {
-        // empty string response is valid — caller decides if it's useful
-        let json = r#"{"success":true,"response":""}"#;
-        let result = parse_cactus_response(json);
-        assert_eq!(result.unwrap(), "");
+    fn panicking_token_callback_does_not_unwind_into_c() {
+        let mut seen = Vec::new();
+        let mut on_token = |t: &str| {
+            if t == "boom" {
+                panic!("callback bug");
+            }
+            seen.push(t.to_string());
+        };
+        let mut callback: &mut dyn FnMut(&str) = &mut on_token;
+        let user_data = &mut callback as *mut &mut dyn FnMut(&str) as *mut c_void;
+        for token in ["a", "boom", "b"] {
+            let token = CString::new(token).unwrap();
+            token_trampoline(token.as_ptr(), 0, user_data);
+        }
+        assert_eq!(seen, ["a", "b"]);
     }
 
     #[test]
@@ -282,7 +284,7 @@ mod tests {
     fn test_cactus_complete_live() {
         let model_path = std::env::var("CACTUS_MODEL_PATH")
             .expect("CACTUS_MODEL_PATH must be set to run live tests");
-        let llm = CactusLlm::new(&model_path).expect("failed to init model");
+        let llm = CactusLlm::new(&model_path, Sampling::default()).expect("failed to init model");
         let result = llm.complete("You are helpful. Be brief.", "Say hello in one word.");
         assert!(result.is_ok(), "complete failed: {:?}", result);
         let text = result.unwrap();
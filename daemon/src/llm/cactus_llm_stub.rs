@@ -1,21 +1,25 @@
+use super::backend::Sampling;
+use super::LlmBackend;
 use anyhow::{anyhow, Result};
 
 pub struct CactusLlm;
 
 impl CactusLlm {
-    pub fn new(_model_path: &str) -> Result<Self> {
+    pub fn new(_model_path: &str, _sampling: Sampling) -> Result<Self> {
         Err(anyhow!(
             "Cactus support is not compiled in. Set CACTUS_LIB_DIR at build time to enable local LLM inference."
         ))
     }
+}
 
-    pub fn complete(&self, _system_prompt: &str, _user_message: &str) -> Result<String> {
+impl LlmBackend for CactusLlm {
+    fn complete(&self, _system_prompt: &str, _user_message: &str) -> Result<String> {
         Err(anyhow!(
             "Cactus support is not compiled in. Rebuild with CACTUS_LIB_DIR set to use LLM completion."
         ))
     }
 
-    pub fn complete_with_tools(
+    fn complete_with_tools(
         &self,
         _user_message: &str,
         _tools_json: &str,
@@ -1,8 +1,13 @@
+pub mod backend;
 #[cfg(senior_has_cactus)]
 pub mod cactus_llm;
 #[cfg(not(senior_has_cactus))]
 pub mod cactus_llm_stub;
+pub mod envelope;
+pub mod openai;
+pub mod scripted;
 pub mod voice;
+pub use backend::{load_backend, BackendConfig, LlmBackend};
 #[cfg(senior_has_cactus)]
 pub use cactus_llm::CactusLlm;
 #[cfg(not(senior_has_cactus))]
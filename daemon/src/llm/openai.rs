@@ -0,0 +1,278 @@
+use super::backend::Sampling;
+use super::LlmBackend;
+use anyhow::{anyhow, Result};
+use std::time::Duration;
+use tracing::debug;
+
+/// Backend for any server exposing an OpenAI-compatible `/v1/chat/completions`
+/// endpoint — llama.cpp `server`, Ollama, vLLM and friends. Plain HTTP only and
+/// without credentials: these servers are expected to run on localhost, and
+/// the diff and any token would otherwise cross the network in the clear.
+pub struct OpenAiLlm {
+    agent: ureq::Agent,
+    endpoint: String,
+    model: String,
+    sampling: Sampling,
+}
+
+impl OpenAiLlm {
+    /// `base_url` is the API root including `/v1`, e.g. `http://127.0.0.1:8080/v1`.
+    pub fn new(base_url: &str, model: &str, sampling: Sampling) -> Result<Self> {
+        if !base_url.starts_with("http://") {
+            return Err(anyhow!(
+                "OpenAI-compatible backend needs a plain http:// base URL, got '{}'",
+                base_url
+            ));
+        }
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(5))
+            // Local models can take minutes on a cold start or a long diff.
+            .timeout_read(Duration::from_secs(300))
+            .build();
+        Ok(Self {
+            agent,
+            endpoint: format!("{}/chat/completions", base_url.trim_end_matches('/')),
+            model: model.to_string(),
+            sampling,
+        })
+    }
+
+    fn post(&self, body: serde_json::Value) -> Result<serde_json::Value> {
+        let resp = match self.agent.post(&self.endpoint).send_json(body) {
+            Ok(resp) => resp,
+            Err(ureq::Error::Status(code, resp)) => {
+                let text = resp.into_string().unwrap_or_default();
+                return Err(anyhow!("{} returned HTTP {}: {}", self.endpoint, code, text));
+            }
+            Err(e) => return Err(anyhow!("request to {} failed: {}", self.endpoint, e)),
+        };
+        let raw: serde_json::Value = resp
+            .into_json()
+            .map_err(|e| anyhow!("failed to parse chat completion JSON: {}", e))?;
+        debug!("openai raw response: {}", raw);
+        Ok(raw)
+    }
+}
+
+impl LlmBackend for OpenAiLlm {
+    fn complete(&self, system_prompt: &str, user_message: &str) -> Result<String> {
+        let body = serde_json::json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": system_prompt },
+                { "role": "user", "content": user_message }
+            ],
+            "max_tokens": self.sampling.max_tokens,
+            "temperature": self.sampling.temperature
+        });
+        parse_chat_response(&self.post(body)?)
+    }
+
+    fn complete_with_tools(
+        &self,
+        user_message: &str,
+        tools_json: &str,
+    ) -> Result<Vec<serde_json::Value>> {
+        let tools: serde_json::Value = serde_json::from_str(tools_json)
+            .map_err(|e| anyhow!("invalid tools JSON: {}", e))?;
+        let body = serde_json::json!({
+            "model": self.model,
+            "messages": [
+                { "role": "user", "content": user_message }
+            ],
+            "tools": tools,
+            "max_tokens": self.sampling.max_tokens,
+            "temperature": self.sampling.temperature
+        });
+        parse_tool_calls(&self.post(body)?)
+    }
+}
+
+fn first_message(raw: &serde_json::Value) -> Result<&serde_json::Value> {
+    if let Some(err) = raw.get("error") {
+        let msg = err["message"].as_str().or(err.as_str()).unwrap_or("unknown error");
+        return Err(anyhow!("chat completion failed: {}", msg));
+    }
+    raw["choices"]
+        .get(0)
+        .map(|c| &c["message"])
+        .filter(|m| m.is_object())
+        .ok_or_else(|| anyhow!("chat completion has no choices: {}", raw))
+}
+
+/// Extract `choices[0].message.content`. Pure so it can be tested without a server.
+fn parse_chat_response(raw: &serde_json::Value) -> Result<String> {
+    let message = first_message(raw)?;
+    message["content"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| anyhow!("chat completion message has no text content: {}", message))
+}
+
+/// Convert OpenAI `tool_calls` into the `{"name", "arguments"}` shape the Cactus
+/// envelope uses, so the analyzer does not care which backend answered.
+/// OpenAI sends `arguments` as a JSON-encoded string; it is decoded here.
+fn parse_tool_calls(raw: &serde_json::Value) -> Result<Vec<serde_json::Value>> {
+    let message = first_message(raw)?;
+    let calls = message["tool_calls"]
+        .as_array()
+        .map(|calls| {
+            calls
+                .iter()
+                .filter_map(|call| {
+                    let function = &call["function"];
+                    let name = function["name"].as_str()?;
+                    let arguments = match &function["arguments"] {
+                        serde_json::Value::String(s) => serde_json::from_str(s)
+                            .unwrap_or_else(|_| serde_json::Value::String(s.clone())),
+                        other => other.clone(),
+                    };
+                    Some(serde_json::json!({ "name": name, "arguments": arguments }))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(calls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    /// Serve exactly one HTTP request with `body` and hand back what the client sent.
+    fn mock_server(status: u16, body: &'static str) -> (String, mpsc::Receiver<(String, serde_json::Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(v) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    content_length = v.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                headers.push_str(&line);
+            }
+            let mut req_body = vec![0; content_length];
+            reader.read_exact(&mut req_body).unwrap();
+            tx.send((headers, serde_json::from_slice(&req_body).unwrap())).unwrap();
+            let mut stream = stream;
+            write!(
+                stream,
+                "HTTP/1.1 {} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+        (format!("http://{}/v1", addr), rx)
+    }
+
+    #[test]
+    fn complete_sends_chat_request_and_returns_content() {
+        let (url, rx) = mock_server(
+            200,
+            r#"{"choices":[{"message":{"role":"assistant","content":"hello there"}}]}"#,
+        );
+        let sampling = Sampling { max_tokens: 256, temperature: 0.0 };
+        let llm = OpenAiLlm::new(&url, "qwen2.5-coder", sampling).unwrap();
+        assert_eq!(llm.complete("be brief", "hi").unwrap(), "hello there");
+
+        let (headers, body) = rx.recv().unwrap();
+        assert!(headers.starts_with("POST /v1/chat/completions"), "got: {}", headers);
+        assert!(!headers.to_lowercase().contains("authorization"), "got: {}", headers);
+        assert_eq!(body["model"], "qwen2.5-coder");
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][1]["content"], "hi");
+        assert_eq!(body["max_tokens"], 256);
+        assert_eq!(body["temperature"], 0.0);
+    }
+
+    #[test]
+    fn complete_with_tools_forwards_schema_and_decodes_arguments() {
+        let (url, rx) = mock_server(
+            200,
+            r#"{"choices":[{"message":{"role":"assistant","content":null,"tool_calls":[{"id":"c1","type":"function","function":{"name":"submit_review","arguments":"{\"risk_level\":\"high\"}"}}]}}]}"#,
+        );
+        let llm = OpenAiLlm::new(&url, "m", Sampling::default()).unwrap();
+        let tools = r#"[{"type":"function","function":{"name":"submit_review","parameters":{}}}]"#;
+        let calls = llm.complete_with_tools("review this", tools).unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0]["name"], "submit_review");
+        assert_eq!(calls[0]["arguments"]["risk_level"], "high");
+
+        let (headers, body) = rx.recv().unwrap();
+        assert!(!headers.contains("Authorization"), "got: {}", headers);
+        assert_eq!(body["tools"][0]["function"]["name"], "submit_review");
+    }
+
+    #[test]
+    fn http_error_status_includes_body() {
+        let (url, _rx) = mock_server(500, r#"{"error":{"message":"model not loaded"}}"#);
+        let llm = OpenAiLlm::new(&url, "m", Sampling::default()).unwrap();
+        let err = llm.complete("s", "u").unwrap_err();
+        assert!(err.to_string().contains("HTTP 500"), "got: {}", err);
+        assert!(err.to_string().contains("model not loaded"), "got: {}", err);
+    }
+
+    #[test]
+    fn new_rejects_non_http_url() {
+        assert!(OpenAiLlm::new("https://api.example.com/v1", "m", Sampling::default()).is_err());
+        assert!(OpenAiLlm::new("localhost:8080", "m", Sampling::default()).is_err());
+    }
+
+    #[test]
+    fn parse_chat_response_error_object_is_error() {
+        let raw = serde_json::json!({"error": {"message": "context length exceeded"}});
+        let err = parse_chat_response(&raw).unwrap_err();
+        assert!(err.to_string().contains("context length exceeded"), "got: {}", err);
+    }
+
+    #[test]
+    fn parse_chat_response_without_choices_is_error() {
+        let err = parse_chat_response(&serde_json::json!({"choices": []})).unwrap_err();
+        assert!(err.to_string().contains("no choices"), "got: {}", err);
+    }
+
+    #[test]
+    fn parse_chat_response_null_content_is_error() {
+        let raw = serde_json::json!({"choices": [{"message": {"content": null}}]});
+        assert!(parse_chat_response(&raw).is_err());
+    }
+
+    #[test]
+    fn parse_tool_calls_without_tool_calls_is_empty() {
+        let raw = serde_json::json!({"choices": [{"message": {"content": "no tools today"}}]});
+        assert!(parse_tool_calls(&raw).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_tool_calls_keeps_object_arguments() {
+        // Some servers (older Ollama) send arguments as an object rather than a string.
+        let raw = serde_json::json!({"choices": [{"message": {"tool_calls": [
+            {"function": {"name": "submit_review", "arguments": {"risk_level": "low"}}}
+        ]}}]});
+        let calls = parse_tool_calls(&raw).unwrap();
+        assert_eq!(calls[0]["arguments"]["risk_level"], "low");
+    }
+
+    #[test]
+    fn parse_tool_calls_unparseable_arguments_kept_as_string() {
+        let raw = serde_json::json!({"choices": [{"message": {"tool_calls": [
+            {"function": {"name": "submit_review", "arguments": "{not json"}}
+        ]}}]});
+        let calls = parse_tool_calls(&raw).unwrap();
+        assert_eq!(calls[0]["arguments"], "{not json");
+    }
+}
@@ -0,0 +1,208 @@
+use super::envelope::{parse_cactus_response, parse_function_calls};
+use super::LlmBackend;
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+/// Replays canned Cactus envelopes from a fixture file instead of running a
+/// model, so the full analyze → parse → fallback pipeline can be exercised
+/// deterministically in tests and demos.
+///
+/// Fixture layout — one table per call kind, keyed by [`prompt_hash`] of the
+/// user message, with `"*"` as the catch-all:
+///
+/// ```json
+/// {
+///   "tools":    { "*": {"success": true, "function_calls": []} },
+///   "complete": { "3f2a9c0d1e4b5a67": {"success": true, "response": "{...}"} }
+/// }
+/// ```
+///
+/// An entry that is a JSON string is replayed verbatim as the raw envelope,
+/// which lets fixtures simulate corrupt output from the engine.
+pub struct ScriptedLlm {
+    complete: HashMap<String, serde_json::Value>,
+    tools: HashMap<String, serde_json::Value>,
+}
+
+impl ScriptedLlm {
+    pub fn load(fixture_path: &str) -> Result<Self> {
+        let raw = std::fs::read_to_string(fixture_path)
+            .with_context(|| format!("failed to read LLM script '{}'", fixture_path))?;
+        Self::from_json(&raw).with_context(|| format!("invalid LLM script '{}'", fixture_path))
+    }
+
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let parsed: serde_json::Value = serde_json::from_str(raw)?;
+        let table = |key: &str| -> Result<HashMap<String, serde_json::Value>> {
+            match &parsed[key] {
+                serde_json::Value::Null => Ok(HashMap::new()),
+                serde_json::Value::Object(map) => Ok(map.clone().into_iter().collect()),
+                _ => Err(anyhow!("'{}' must be an object keyed by prompt hash", key)),
+            }
+        };
+        Ok(Self {
+            complete: table("complete")?,
+            tools: table("tools")?,
+        })
+    }
+
+    fn replay(
+        table: &HashMap<String, serde_json::Value>,
+        kind: &str,
+        user_message: &str,
+    ) -> Result<String> {
+        let hash = prompt_hash(user_message);
+        let entry = match table.get(&hash) {
+            Some(entry) => entry,
+            None => table.get("*").ok_or_else(|| {
+                warn!("no scripted {} response for prompt {}", kind, hash);
+                anyhow!("no scripted {} response for prompt {}", kind, hash)
+            })?,
+        };
+        debug!("replaying scripted {} response for prompt {}", kind, hash);
+        Ok(match entry {
+            serde_json::Value::String(raw) => raw.clone(),
+            other => other.to_string(),
+        })
+    }
+}
+
+impl LlmBackend for ScriptedLlm {
+    fn complete(&self, _system_prompt: &str, user_message: &str) -> Result<String> {
+        parse_cactus_response(&Self::replay(&self.complete, "complete", user_message)?)
+    }
+
+    fn complete_with_tools(
+        &self,
+        user_message: &str,
+        _tools_json: &str,
+    ) -> Result<Vec<serde_json::Value>> {
+        parse_function_calls(&Self::replay(&self.tools, "tools", user_message)?)
+    }
+
+    fn complete_streaming(
+        &self,
+        system_prompt: &str,
+        user_message: &str,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<String> {
+        let text = self.complete(system_prompt, user_message)?;
+        stream_words(&text, on_token);
+        Ok(text)
+    }
+
+    fn complete_with_tools_streaming(
+        &self,
+        user_message: &str,
+        tools_json: &str,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<Vec<serde_json::Value>> {
+        let calls = self.complete_with_tools(user_message, tools_json)?;
+        if !calls.is_empty() {
+            stream_words(&serde_json::Value::Array(calls.clone()).to_string(), on_token);
+        }
+        Ok(calls)
+    }
+}
+
+/// Replay `text` a word at a time, the way a model would stream it.
+fn stream_words(text: &str, on_token: &mut dyn FnMut(&str)) {
+    for chunk in text.split_inclusive(' ') {
+        on_token(chunk);
+    }
+}
+
+/// Fixture key for a prompt: the first 16 hex digits of its SHA-256.
+pub fn prompt_hash(user_message: &str) -> String {
+    let digest = Sha256::digest(user_message.as_bytes());
+    digest.iter().take(8).map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompt_hash_is_stable_and_short() {
+        assert_eq!(prompt_hash("hello"), "2cf24dba5fb0a30e");
+        assert_eq!(prompt_hash("hello").len(), 16);
+        assert_ne!(prompt_hash("hello"), prompt_hash("hello "));
+    }
+
+    #[test]
+    fn exact_hash_wins_over_wildcard() {
+        let fixture = serde_json::json!({
+            "complete": {
+                prompt_hash("which file?"): {"success": true, "response": "auth.rs"},
+                "*": {"success": true, "response": "no idea"}
+            }
+        });
+        let llm = ScriptedLlm::from_json(&fixture.to_string()).unwrap();
+        assert_eq!(llm.complete("sys", "which file?").unwrap(), "auth.rs");
+        assert_eq!(llm.complete("sys", "anything else").unwrap(), "no idea");
+    }
+
+    #[test]
+    fn tools_table_replays_function_calls() {
+        let fixture = r#"{"tools":{"*":{"success":true,"function_calls":[{"name":"submit_review","arguments":{}}]}}}"#;
+        let llm = ScriptedLlm::from_json(fixture).unwrap();
+        let calls = llm.complete_with_tools("diff", "[]").unwrap();
+        assert_eq!(calls[0]["name"], "submit_review");
+    }
+
+    #[test]
+    fn missing_entry_is_error_naming_the_hash() {
+        let llm = ScriptedLlm::from_json("{}").unwrap();
+        let err = llm.complete("sys", "hello").unwrap_err();
+        assert!(err.to_string().contains("2cf24dba5fb0a30e"), "got: {}", err);
+    }
+
+    #[test]
+    fn failure_envelope_surfaces_as_error() {
+        let fixture = r#"{"tools":{"*":{"success":false,"error":"cactus_complete failed"}}}"#;
+        let llm = ScriptedLlm::from_json(fixture).unwrap();
+        let err = llm.complete_with_tools("diff", "[]").unwrap_err();
+        assert!(err.to_string().contains("cactus_complete failed"), "got: {}", err);
+    }
+
+    #[test]
+    fn string_entry_is_replayed_as_raw_envelope() {
+        let fixture = r#"{"complete":{"*":"segfault"}}"#;
+        let llm = ScriptedLlm::from_json(fixture).unwrap();
+        let err = llm.complete("sys", "hi").unwrap_err();
+        assert!(err.to_string().contains("failed to parse cactus response JSON"), "got: {}", err);
+    }
+
+    #[test]
+    fn streaming_replays_text_word_by_word() {
+        let fixture = r#"{"complete":{"*":{"success":true,"response":"all good here"}}}"#;
+        let llm = ScriptedLlm::from_json(fixture).unwrap();
+        let mut tokens = Vec::new();
+        let text = llm.complete_streaming("sys", "hi", &mut |t| tokens.push(t.to_string())).unwrap();
+        assert_eq!(text, "all good here");
+        assert_eq!(tokens, vec!["all ", "good ", "here"]);
+    }
+
+    #[test]
+    fn streaming_tool_miss_emits_no_tokens() {
+        let fixture = r#"{"tools":{"*":{"success":true,"function_calls":[]}}}"#;
+        let llm = ScriptedLlm::from_json(fixture).unwrap();
+        let mut count = 0;
+        let calls = llm.complete_with_tools_streaming("diff", "[]", &mut |_| count += 1).unwrap();
+        assert!(calls.is_empty());
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn non_object_table_is_rejected() {
+        assert!(ScriptedLlm::from_json(r#"{"tools":[1,2]}"#).is_err());
+    }
+
+    #[test]
+    fn load_missing_file_names_the_path() {
+        let err = ScriptedLlm::load("/nonexistent/script.json").err().unwrap();
+        assert!(err.to_string().contains("/nonexistent/script.json"), "got: {}", err);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::llm::CactusLlm;
+use crate::llm::LlmBackend;
 use crate::protocol::AnalysisResult;
 use anyhow::Result;
 
@@ -6,11 +6,11 @@ pub const SYSTEM_PROMPT: &str = "You are Senior, a voice-first AI pair programme
     Be conversational and brief — max 2 sentences. \
     You are speaking aloud, not writing. No markdown, no lists, just natural spoken words.";
 
-pub fn greet(llm: &CactusLlm, last_analysis: Option<&AnalysisResult>) -> Result<String> {
+pub fn greet(llm: &dyn LlmBackend, last_analysis: Option<&AnalysisResult>) -> Result<String> {
     llm.complete(SYSTEM_PROMPT, &build_greet_prompt(last_analysis))
 }
 
-pub fn answer(llm: &CactusLlm, question: &str, context: Option<&AnalysisResult>) -> Result<String> {
+pub fn answer(llm: &dyn LlmBackend, question: &str, context: Option<&AnalysisResult>) -> Result<String> {
     llm.complete(SYSTEM_PROMPT, &build_answer_prompt(question, context))
 }
 
@@ -60,10 +60,12 @@ mod tests {
                 path: "src/auth.rs".to_string(),
                 score: 0.6,
                 why: vec!["+10 -3 lines".to_string()],
+                change: "modified".to_string(),
             }],
             impacted_symbols: vec![],
             suggested_actions: vec![],
             confidence: 0.8,
+            cached: false,
         }
     }
 
@@ -1,77 +1,223 @@
 mod analyzer;
+mod args;
+mod config;
+mod inflight;
 mod llm;
-mod protocol;
+mod once;
 mod store;
+#[cfg(test)]
+mod test_support;
 
 use anyhow::Result;
-use protocol::{Request, Response};
-use std::path::Path;
-use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{UnixListener, UnixStream};
-use tracing::{error, info};
+use analyzer::impact::Progress;
+use inflight::{Inflight, Ticket};
+use protocol::{Request, RequestEnvelope, Response, ResponseEnvelope};
+use senior_daemon::{git, paths, protocol, render};
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::UnixListener;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{mpsc, Semaphore};
+use tracing::{debug, error, info};
 
-const DEFAULT_SOCKET_PATH: &str = "/tmp/senior.sock";
+/// A workspace's index is walked at most this often. Every save triggers an
+/// analysis, and the changed files come from the diff anyway; only callers
+/// added since the last walk are missed in between.
+const INDEX_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Sender for intermediate frames of a streaming request.
+type Frames = mpsc::UnboundedSender<Response>;
+
+/// Daemon-wide state shared by every connection.
+struct State {
+    /// Audit log and workspace index; both live in the daemon's SQLite database.
+    audit: Arc<store::AuditLog>,
+    /// When each workspace's index was last refreshed, keyed by root.
+    index_refreshed: Mutex<HashMap<String, Instant>>,
+    /// Swapped whole by `reload`; each request works with the one that was
+    /// current when it arrived.
+    loaded: RwLock<Arc<Loaded>>,
+    inflight: Arc<Inflight>,
+    /// Analyses queue here for the model one at a time, so a cancelled or
+    /// superseded analysis can be dropped before it ever reaches inference.
+    /// Shared across reloads, so a new model waits for the old one's last run.
+    inference: Arc<Semaphore>,
+    /// The `--config` flag, read again on every reload.
+    config_path: Option<PathBuf>,
+    /// Held while a reload loads its model, so reloads happen one at a time.
+    reloading: tokio::sync::Mutex<()>,
+    started: Instant,
+}
+
+impl State {
+    fn new(audit: Arc<store::AuditLog>, loaded: Loaded, config_path: Option<PathBuf>) -> Self {
+        Self {
+            audit,
+            index_refreshed: Mutex::new(HashMap::new()),
+            loaded: RwLock::new(Arc::new(loaded)),
+            inflight: Arc::new(Inflight::default()),
+            inference: Arc::new(Semaphore::new(1)),
+            config_path,
+            reloading: tokio::sync::Mutex::new(()),
+            started: Instant::now(),
+        }
+    }
+
+    fn loaded(&self) -> Arc<Loaded> {
+        self.loaded.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Whether the index of the workspace at `root` is due a refresh, i.e.
+    /// was not refreshed in the last `INDEX_REFRESH_INTERVAL`. Claims the
+    /// refresh when it is, so analyses arriving together walk the tree once.
+    fn index_due(&self, root: &str) -> bool {
+        let mut refreshed = self.index_refreshed.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        if refreshed.get(root).is_some_and(|at| now.duration_since(*at) < INDEX_REFRESH_INTERVAL) {
+            return false;
+        }
+        refreshed.insert(root.to_string(), now);
+        true
+    }
+}
+
+/// The model, and the settings read along with it.
+struct Loaded {
+    llm: Option<Arc<dyn llm::LlmBackend>>,
+    /// Identifies the loaded model in cache keys, so switching models misses.
+    model_id: String,
+    /// Prompt budget, cache TTL, ignore globs and risk rules from the global
+    /// config; a workspace's `.senior.toml` adds to them per analysis.
+    analysis: config::Analysis,
+    /// The `senior.toml` these came from, if any.
+    config_source: Option<PathBuf>,
+}
+
+impl Loaded {
+    fn new(llm: Option<Arc<dyn llm::LlmBackend>>, config: &config::Config) -> Self {
+        Self {
+            llm,
+            model_id: format!("{:?}:{}", config.backend.kind, config.backend.model),
+            analysis: config.analysis.clone(),
+            config_source: config.source.clone(),
+        }
+    }
+}
+
+/// Load the backend `backend` names, or none when it names no model.
+fn load_llm(backend: &llm::BackendConfig) -> Result<Option<Arc<dyn llm::LlmBackend>>> {
+    if backend.model.is_empty() {
+        tracing::warn!(
+            "no model configured for the {:?} backend — running in stub mode. \
+             Set CACTUS_MODEL_PATH (cactus), SENIOR_OPENAI_MODEL (openai) or \
+             SENIOR_LLM_SCRIPT (scripted), or llm.model in senior.toml, to enable LLM inference.",
+            backend.kind
+        );
+        return Ok(None);
+    }
+    let llm = llm::load_backend(backend)?;
+    info!("{:?} LLM backend loaded: {}", backend.kind, backend.model);
+    Ok(Some(llm))
+}
+
+/// Read the config file and environment again and swap in the backend and
+/// settings they name. Requests already running finish on the previous model,
+/// which is freed when the last of them is done. An invalid config or a model
+/// that fails to load leaves everything as it was. Storage locations are only
+/// read at startup.
+async fn reload(state: &State) -> Result<Arc<Loaded>> {
+    let _one_at_a_time = state.reloading.lock().await;
+    let path = state.config_path.clone();
+    let loaded = tokio::task::spawn_blocking(move || -> Result<Loaded> {
+        let config = config::Config::load(path, &|name| std::env::var(name).ok())?;
+        let llm = load_llm(&config.backend)?;
+        Ok(Loaded::new(llm, &config))
+    })
+    .await??;
+    let loaded = Arc::new(loaded);
+    *state.loaded.write().unwrap_or_else(PoisonError::into_inner) = loaded.clone();
+    info!("reloaded: now using {}", loaded.model_id);
+    Ok(loaded)
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
         .init();
 
-    let socket_path = std::env::var("SENIOR_SOCKET_PATH")
-        .unwrap_or_else(|_| DEFAULT_SOCKET_PATH.to_string());
+    let args = args::parse(std::env::args().skip(1))?;
+    if args.help {
+        print!("{}", args::USAGE);
+        return Ok(());
+    }
+    let env = |name: &str| std::env::var(name).ok();
+    let config = config::Config::load(args.config.clone(), &env)?;
+    if args.once {
+        let result = once::run(args.diff_file.as_deref(), &config)?;
+        println!("{}", serde_json::to_string(&result)?);
+        return Ok(());
+    }
+    // Installed before the socket appears, so a SIGHUP never finds the
+    // default action (exit) in place once clients can connect.
+    let mut hangups = signal(SignalKind::hangup())?;
+    let flags = paths::Locations { socket: args.socket, data_dir: args.data_dir };
+    let storage = paths::StoragePaths::resolve(flags, config.storage.clone(), &env)?;
+    storage.prepare()?;
 
     // Remove stale socket
-    if Path::new(&socket_path).exists() {
-        std::fs::remove_file(&socket_path)?;
+    if storage.socket.exists() {
+        std::fs::remove_file(&storage.socket)?;
     }
 
-    let listener = UnixListener::bind(&socket_path)?;
-    info!("senior daemon listening on {}", socket_path);
+    let listener = paths::bind_private(&storage.socket)?;
+    listener.set_nonblocking(true)?;
+    let listener = UnixListener::from_std(listener)?;
+    info!("senior daemon listening on {}", storage.socket.display());
 
-    let audit = Arc::new(store::AuditLog::open("/tmp/senior-audit.db")?);
+    let db_path = storage.db();
+    let audit = Arc::new(store::AuditLog::open(&db_path.to_string_lossy())?);
+    paths::restrict(&db_path)?;
+    info!("audit log at {}", db_path.display());
 
-    let model_path = std::env::var("CACTUS_MODEL_PATH").unwrap_or_else(|_| {
-        tracing::warn!(
-            "CACTUS_MODEL_PATH is not set — running in stub mode. \
-             Set it to your model weights directory to enable LLM inference."
-        );
-        String::new()
+    if let Some(source) = &config.source {
+        info!("configuration read from {}", source.display());
+    }
+    let backend = &config.backend;
+    let llm = load_llm(backend).unwrap_or_else(|e| {
+        tracing::warn!("{:?} LLM backend failed to load '{}': {} — running in stub mode", backend.kind, backend.model, e);
+        None
     });
+    let state = Arc::new(State::new(audit, Loaded::new(llm, &config), args.config));
 
-    let llm: Option<Arc<llm::CactusLlm>> = if model_path.is_empty() {
-        None
-    } else {
-        match llm::CactusLlm::new(&model_path) {
-            Ok(l) => {
-                info!("Cactus LLM loaded from {}", model_path);
-                Some(Arc::new(l))
-            }
-            Err(e) => {
-                tracing::warn!("Cactus LLM failed to load from '{}': {} — running in stub mode", model_path, e);
-                None
+    let reloader = state.clone();
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            if let Err(e) = reload(&reloader).await {
+                error!("reload on SIGHUP failed: {}", e);
             }
         }
-    };
+    });
 
     loop {
         let (stream, _) = listener.accept().await?;
-        let audit = audit.clone();
-        let llm = llm.clone();
+        let state = state.clone();
         tokio::spawn(async move {
-            if let Err(e) = handle_connection(stream, audit, llm).await {
+            if let Err(e) = handle_connection(stream, state).await {
                 error!("connection error: {}", e);
             }
         });
     }
 }
 
-async fn handle_connection(
-    stream: UnixStream,
-    audit: Arc<store::AuditLog>,
-    llm: Option<Arc<llm::CactusLlm>>,
-) -> Result<()> {
+async fn handle_connection<S>(stream: S, state: Arc<State>) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite,
+{
     let (reader, mut writer) = tokio::io::split(stream);
     let mut reader = BufReader::new(reader);
     let mut line = String::new();
@@ -88,100 +234,766 @@ async fn handle_connection(
             continue;
         }
 
-        let response = match serde_json::from_str::<Request>(trimmed) {
-            Ok(Request::Ping) => Response::Pong,
-            Ok(Request::AnalyzeDiff(payload)) => {
-                if let Err(e) = audit.log("analyze_diff", &payload.active_file) {
-                    tracing::warn!("audit log write failed: {}", e);
+        let (id, request) = match serde_json::from_str::<RequestEnvelope>(trimmed) {
+            Ok(env) => (env.id, env.request),
+            Err(e) => {
+                // Echo the id back if the line was at least JSON with one.
+                let id = serde_json::from_str::<serde_json::Value>(trimmed)
+                    .ok()
+                    .and_then(|v| v["id"].as_str().map(String::from));
+                let response = Response::Error { message: format!("parse error: {}", e) };
+                write_frame(&mut writer, &id, response).await?;
+                continue;
+            }
+        };
+
+        // Streaming requests push intermediate frames through this channel while
+        // `dispatch` runs; they are written out as they arrive, before the final
+        // response. A cancelled analysis returns while its inference thread may
+        // still hold a sender, so stop forwarding as soon as `dispatch` is done.
+        let (frames_tx, mut frames_rx) = mpsc::unbounded_channel::<Response>();
+        let dispatched = dispatch(request, id.as_deref(), &state, frames_tx);
+        tokio::pin!(dispatched);
+        let response = loop {
+            tokio::select! {
+                biased;
+                Some(frame) = frames_rx.recv() => write_frame(&mut writer, &id, frame).await?,
+                response = &mut dispatched => break response,
+            }
+        };
+        while let Ok(frame) = frames_rx.try_recv() {
+            write_frame(&mut writer, &id, frame).await?;
+        }
+        drop(frames_rx);
+        write_frame(&mut writer, &id, response).await?;
+    }
+
+    Ok(())
+}
+
+/// Handle one request. `frames` carries streaming frames for requests that
+/// asked for them; everything else simply drops it.
+async fn dispatch(request: Request, id: Option<&str>, state: &State, frames: Frames) -> Response {
+    let audit = &state.audit;
+    let loaded = state.loaded();
+    let llm = &loaded.llm;
+    match request {
+        Request::Ping => Response::Pong,
+        Request::Reload => match reload(state).await {
+            Ok(loaded) => Response::Reloaded { model_id: loaded.model_id.clone(), llm_loaded: loaded.llm.is_some() },
+            Err(e) => Response::Error { message: format!("reload failed: {}", e) },
+        },
+        Request::Status => Response::Status(protocol::DaemonStatus {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            model_id: loaded.model_id.clone(),
+            llm_loaded: llm.is_some(),
+            busy: state.inference.available_permits() == 0,
+            uptime_secs: state.started.elapsed().as_secs(),
+            config: loaded.config_source.as_ref().map(|p| p.display().to_string()),
+        }),
+        Request::Render(p) => Response::Rendered { format: p.format, text: render::render(&p.result, p.format) },
+        Request::Cancel(payload) => {
+            let found = state.inflight.cancel(&payload.id);
+            debug!("cancel id={} found={}", payload.id, found);
+            Response::CancelAck { id: payload.id, found }
+        }
+        Request::AnalyzeDiff(payload) => {
+            if let Err(e) = audit.log("analyze_diff", &payload.active_file) {
+                tracing::warn!("audit log write failed: {}", e);
+            }
+            debug!(
+                "analyze_diff trigger={} files_touched={}",
+                payload.trigger,
+                payload.files_touched.len()
+            );
+            let job = Job {
+                root: payload.workspace,
+                active_file: payload.active_file,
+                trigger: payload.trigger,
+                started: Instant::now(),
+            };
+            let ticket = state.inflight.register(id, job.root.as_deref(), payload.supersede);
+            run_analysis(&payload.diff, job, state, &loaded, &ticket, payload.stream.then_some(frames)).await
+        }
+        Request::AnalyzeRepo(payload) => {
+            if let Err(e) = audit.log("analyze_repo", &payload.repo_root) {
+                tracing::warn!("audit log write failed: {}", e);
+            }
+            debug!(
+                "analyze_repo trigger={} selector={:?} active_file={}",
+                payload.trigger, payload.selector, payload.active_file
+            );
+            let mut job = Job {
+                root: Some(payload.repo_root.clone()),
+                active_file: payload.active_file,
+                trigger: payload.trigger,
+                started: Instant::now(),
+            };
+            let ticket = state.inflight.register(id, Some(&payload.repo_root), payload.supersede);
+            let frames = payload.stream.then_some(frames);
+            if let Some(tx) = &frames {
+                let _ = tx.send(Response::AnalysisProgress {
+                    stage: "diffing".to_string(),
+                    detail: format!("{:?}", payload.selector),
+                });
+            }
+            let repo_root = payload.repo_root.clone();
+            let selector = payload.selector.clone();
+            let diffed = tokio::task::spawn_blocking(move || -> Result<(String, String)> {
+                let top = git::toplevel(&repo_root)?;
+                let diff = git::diff(&top, &selector)?;
+                Ok((top, diff))
+            });
+            match diffed.await {
+                Ok(Ok((_, diff))) if diff.trim().is_empty() => Response::Error {
+                    message: "No changes detected in this repo.".to_string(),
+                },
+                Ok(Ok((top, diff))) => {
+                    // Diff paths are relative to the top level, even when the
+                    // editor opened a subdirectory.
+                    job.root = Some(top);
+                    run_analysis(&diff, job, state, &loaded, &ticket, frames).await
                 }
-                let files = analyzer::diff::parse_diff(&payload.diff);
-                match &llm {
-                    Some(llm_ref) => {
-                        // LLM inference is synchronous C FFI — move to blocking thread
-                        // so the tokio async runtime stays responsive for other connections
-                        let llm_clone = llm_ref.clone();
-                        let files_clone = files.clone();
-                        let diff_clone = payload.diff.clone();
-                        match tokio::task::spawn_blocking(move || {
-                            analyzer::impact::analyze(&llm_clone, &files_clone, &diff_clone)
-                        }).await {
-                            Ok(Ok(result)) => Response::AnalysisResult(result),
-                            Ok(Err(e)) => Response::Error { message: e.to_string() },
-                            Err(e) => Response::Error { message: format!("inference panicked: {}", e) },
-                        }
+                Ok(Err(e)) => Response::Error { message: e.to_string() },
+                Err(e) => Response::Error { message: format!("git diff panicked: {}", e) },
+            }
+        }
+        Request::History(payload) => {
+            let db = audit.clone();
+            match tokio::task::spawn_blocking(move || db.history(&payload)).await {
+                Ok(Ok(entries)) => Response::History { entries },
+                Ok(Err(e)) => Response::Error { message: e.to_string() },
+                Err(e) => Response::Error { message: format!("history query panicked: {}", e) },
+            }
+        }
+        Request::Greet(payload) => {
+            match llm {
+                Some(llm_ref) => {
+                    let llm_clone = llm_ref.clone();
+                    let analysis = payload.last_analysis.clone();
+                    match tokio::task::spawn_blocking(move || {
+                        llm::voice::greet(llm_clone.as_ref(), analysis.as_ref())
+                    }).await {
+                        Ok(Ok(text)) => Response::VoiceAnswer { text },
+                        Ok(Err(e)) => Response::VoiceAnswer {
+                            text: format!("Hey, I had trouble thinking. {}", e),
+                        },
+                        Err(e) => Response::Error { message: format!("greet panicked: {}", e) },
+                    }
+                }
+                None => Response::VoiceAnswer {
+                    text: if payload.last_analysis.is_some() {
+                        "Hey, you have some changes. The LLM is not loaded so I cannot say more.".to_string()
+                    } else {
+                        "Hey, no changes yet. What would you like to work on?".to_string()
                     },
-                    None => Response::AnalysisResult(protocol::AnalysisResult {
-                        summary: vec![
-                            format!("Stub: {} file(s) changed", files.len()),
-                            "Set CACTUS_MODEL_PATH to enable real analysis".to_string(),
-                        ],
-                        risk_level: "low".to_string(),
-                        risk_reasons: vec!["LLM not loaded".to_string()],
-                        impacted_files: files.iter().map(|f| protocol::ImpactedFile {
-                            path: f.path.clone(),
-                            score: 0.5,
-                            why: vec![format!("+{} -{} lines", f.added_lines, f.removed_lines)],
-                        }).collect(),
-                        impacted_symbols: vec![],
-                        suggested_actions: vec![],
-                        confidence: 0.0,
-                    }),
+                },
+            }
+        }
+        Request::VoiceQuery(payload) => {
+            match llm {
+                Some(llm_ref) => {
+                    let llm_clone = llm_ref.clone();
+                    let question = payload.question.clone();
+                    let context = payload.context.clone();
+                    match tokio::task::spawn_blocking(move || {
+                        llm::voice::answer(llm_clone.as_ref(), &question, context.as_ref())
+                    }).await {
+                        Ok(Ok(text)) => Response::VoiceAnswer { text },
+                        Ok(Err(e)) => Response::VoiceAnswer {
+                            text: format!("Sorry, I could not process that. {}", e),
+                        },
+                        Err(e) => Response::Error { message: format!("voice_query panicked: {}", e) },
+                    }
                 }
+                None => Response::VoiceAnswer {
+                    text: "The LLM is not loaded so I cannot answer right now.".to_string(),
+                },
             }
-            Ok(Request::Greet(payload)) => {
-                match &llm {
-                    Some(llm_ref) => {
-                        let llm_clone = llm_ref.clone();
-                        let analysis = payload.last_analysis.clone();
-                        match tokio::task::spawn_blocking(move || {
-                            llm::voice::greet(&llm_clone, analysis.as_ref())
-                        }).await {
-                            Ok(Ok(text)) => Response::VoiceAnswer { text },
-                            Ok(Err(e)) => Response::VoiceAnswer {
-                                text: format!("Hey, I had trouble thinking. {}", e),
-                            },
-                            Err(e) => Response::Error { message: format!("greet panicked: {}", e) },
-                        }
+        }
+    }
+}
+
+async fn write_frame<W>(writer: &mut W, id: &Option<String>, response: Response) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let envelope = ResponseEnvelope { id: id.clone(), response };
+    let mut out = serde_json::to_string(&envelope)?;
+    out.push('\n');
+    writer.write_all(out.as_bytes()).await?;
+    Ok(())
+}
+
+/// Fold in what the workspace says about the change: refresh the index (if
+/// `State::index_due`), re-score changed files by how many others import
+/// them, then add files that reference the changed symbols. Anything other
+/// than a result, or a missing or relative root, passes through. A failed
+/// step is logged and leaves the result as the previous step left it.
+async fn with_workspace(response: Response, state: &State, root: Option<String>) -> Response {
+    let Response::AnalysisResult(mut result) = response else { return response };
+    let Some(root) = root.filter(|r| Path::new(r).is_absolute() && Path::new(r).is_dir()) else {
+        return Response::AnalysisResult(result);
+    };
+    let db = state.audit.clone();
+    let refresh = state.index_due(&root);
+    match tokio::task::spawn_blocking(move || {
+        let root = Path::new(&root);
+        if refresh {
+            match db.refresh_index(root, analyzer::symbols::is_source, analyzer::graph::imports) {
+                Ok(stats) => debug!("workspace index refreshed: {:?}", stats),
+                Err(e) => {
+                    tracing::warn!("workspace index refresh failed: {}", e);
+                    return result;
+                }
+            }
+        }
+        if let Err(e) = analyzer::graph::apply_blast_radius(&db, root, &mut result) {
+            tracing::warn!("import graph scoring failed: {}", e);
+        }
+        if let Err(e) = analyzer::references::add_callers(&db, root, &mut result) {
+            tracing::warn!("reference search failed: {}", e);
+        }
+        result
+    }).await {
+        Ok(result) => Response::AnalysisResult(result),
+        Err(e) => Response::Error { message: format!("workspace analysis panicked: {}", e) },
+    }
+}
+
+/// Who asked for an analysis, and about which workspace.
+struct Job {
+    root: Option<String>,
+    active_file: String,
+    trigger: String,
+    started: Instant,
+}
+
+/// Analyse `diff` under the settings of the job's workspace: leave out ignored
+/// files, review the rest, apply the risk rules, fold in the workspace and
+/// record the result in the history. A workspace with an invalid
+/// `.senior.toml` gets an error naming the problem.
+async fn run_analysis(
+    diff: &str,
+    job: Job,
+    state: &State,
+    loaded: &Loaded,
+    ticket: &Ticket,
+    frames: Option<Frames>,
+) -> Response {
+    let settings = match job.root.as_deref().map(Path::new).filter(|r| r.is_absolute() && r.is_dir()) {
+        Some(root) => match loaded.analysis.for_repo(root) {
+            Ok(settings) => settings,
+            Err(e) => return Response::Error { message: e.to_string() },
+        },
+        None => loaded.analysis.clone(),
+    };
+    let mut files = analyzer::diff::parse_diff(diff);
+    files.retain(|f| !settings.ignore.is_match(&f.path));
+    let origin = Origin {
+        repo: job.root.clone().unwrap_or_default(),
+        trigger: job.trigger,
+        diff_hash: analyzer::impact::diff_hash(&files),
+        files: files.iter().map(|f| f.path.clone()).collect(),
+        model: if loaded.llm.is_some() { loaded.model_id.clone() } else { String::new() },
+        started: job.started,
+    };
+    let mut response = analyze_diff(files, job.active_file, &settings, loaded, state, ticket, frames).await;
+    if let Response::AnalysisResult(result) = &mut response {
+        analyzer::rules::apply(result, &origin.files, &settings.risk_rules);
+    }
+    let response = with_workspace(response, state, job.root).await;
+    record_analysis(state, origin, &response).await;
+    response
+}
+
+/// What an analysis was asked about, kept aside for its history entry.
+struct Origin {
+    repo: String,
+    trigger: String,
+    diff_hash: String,
+    files: Vec<String>,
+    /// Empty when no model was loaded.
+    model: String,
+    started: Instant,
+}
+
+/// Add a finished analysis to the history, with the branch and commit its
+/// workspace had checked out. Anything other than a result is not recorded;
+/// a failed write is logged.
+async fn record_analysis(state: &State, origin: Origin, response: &Response) {
+    let Response::AnalysisResult(result) = response else { return };
+    let mut record = store::history::AnalysisRecord {
+        repo: origin.repo,
+        trigger: origin.trigger,
+        diff_hash: origin.diff_hash,
+        files: origin.files,
+        model: origin.model,
+        latency_ms: origin.started.elapsed().as_millis() as u64,
+        result: result.clone(),
+        ..Default::default()
+    };
+    let db = state.audit.clone();
+    let written = tokio::task::spawn_blocking(move || {
+        if Path::new(&record.repo).is_absolute() && Path::new(&record.repo).is_dir() {
+            (record.branch, record.head) = git::head(&record.repo);
+        }
+        db.record_analysis(&record)
+    })
+    .await;
+    match written {
+        Ok(Ok(id)) => debug!("analysis recorded as #{}", id),
+        Ok(Err(e)) => tracing::warn!("analysis history write failed: {}", e),
+        Err(e) => tracing::warn!("analysis history write panicked: {}", e),
+    }
+}
+
+fn cancelled(reason: &str) -> Response {
+    Response::Cancelled { reason: reason.to_string() }
+}
+
+/// Run the parsed diff `files` through the LLM, or build the stub report when
+/// no backend is loaded. Hunks in `active_file` get first claim on the prompt
+/// budget from `settings`. A diff reviewed before by the same model, within
+/// the cache TTL, is answered from the cache without queueing; otherwise only
+/// the files whose hunks changed since they were last reviewed go to the
+/// model, and the result is cached only if every file was reviewed. Returns
+/// `Cancelled` as soon as `ticket` is cancelled, whether the analysis is still
+/// queued for the model or already running.
+async fn analyze_diff(
+    files: Vec<analyzer::diff::DiffFile>,
+    active_file: String,
+    settings: &config::Analysis,
+    loaded: &Loaded,
+    state: &State,
+    ticket: &Ticket,
+    frames: Option<Frames>,
+) -> Response {
+    if let Some(reason) = ticket.cancelled() {
+        return cancelled(reason);
+    }
+    match &loaded.llm {
+        Some(llm_ref) => {
+            let options = analyzer::impact::PromptOptions {
+                budget: settings.prompt_tokens,
+                active_file: Some(active_file).filter(|f| !f.is_empty()),
+            };
+            let ttl = settings.cache_ttl;
+            let cache_key = (!ttl.is_zero())
+                .then(|| analyzer::impact::cache_key(&files, &loaded.model_id, &options));
+            if let Some(key) = &cache_key {
+                match state.audit.cached_result(key, ttl) {
+                    Ok(Some(result)) => {
+                        debug!("analysis cache hit: {}", key);
+                        return Response::AnalysisResult(result);
                     }
-                    None => Response::VoiceAnswer {
-                        text: if payload.last_analysis.is_some() {
-                            "Hey, you have some changes. The LLM is not loaded so I cannot say more.".to_string()
-                        } else {
-                            "Hey, no changes yet. What would you like to work on?".to_string()
-                        },
-                    },
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("analysis cache lookup failed: {}", e),
                 }
             }
-            Ok(Request::VoiceQuery(payload)) => {
-                match &llm {
-                    Some(llm_ref) => {
-                        let llm_clone = llm_ref.clone();
-                        let question = payload.question.clone();
-                        let context = payload.context.clone();
-                        match tokio::task::spawn_blocking(move || {
-                            llm::voice::answer(&llm_clone, &question, context.as_ref())
-                        }).await {
-                            Ok(Ok(text)) => Response::VoiceAnswer { text },
-                            Ok(Err(e)) => Response::VoiceAnswer {
-                                text: format!("Sorry, I could not process that. {}", e),
-                            },
-                            Err(e) => Response::Error { message: format!("voice_query panicked: {}", e) },
+            let permit = match state.inference.clone().try_acquire_owned() {
+                Ok(permit) => permit,
+                Err(_) => {
+                    if let Some(tx) = &frames {
+                        let _ = tx.send(Response::AnalysisProgress {
+                            stage: "queued".to_string(),
+                            detail: "waiting for the model".to_string(),
+                        });
+                    }
+                    tokio::select! {
+                        permit = state.inference.clone().acquire_owned() => permit.expect("inference semaphore closed"),
+                        reason = ticket.wait_cancelled() => return cancelled(reason),
+                    }
+                }
+            };
+            // LLM inference is synchronous C FFI — move to blocking thread
+            // so the tokio async runtime stays responsive for other connections.
+            // FFI calls cannot be interrupted, so the permit moves into the
+            // thread and is only released once the model is actually free.
+            let llm_clone = llm_ref.clone();
+            let audit = state.audit.clone();
+            let model_id = loaded.model_id.clone();
+            let is_cancelled = ticket.watch();
+            let inference = tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                let mut on_progress = |progress: Progress| {
+                    let Some(tx) = &frames else { return };
+                    let frame = match progress {
+                        Progress::Stage { stage, detail } => Response::AnalysisProgress {
+                            stage: stage.to_string(),
+                            detail,
+                        },
+                        Progress::Token(text) => Response::Token { text: text.to_string() },
+                    };
+                    let _ = tx.send(frame);
+                };
+                if ttl.is_zero() {
+                    // Nothing is cached, so completeness does not matter.
+                    return analyzer::impact::analyze(llm_clone.as_ref(), &files, &options, &is_cancelled, &mut on_progress)
+                        .map(|result| (result, false));
+                }
+                let lookup = |key: &str| {
+                    audit.cached_result(key, ttl).unwrap_or_else(|e| {
+                        tracing::warn!("file review cache lookup failed: {}", e);
+                        None
+                    })
+                };
+                let save = |key: &str, piece: &protocol::AnalysisResult| {
+                    if let Err(e) = audit.cache_result(key, piece, ttl) {
+                        tracing::warn!("could not cache file review: {}", e);
+                    }
+                };
+                let pieces = analyzer::impact::Pieces { model: &model_id, lookup: &lookup, save: &save };
+                analyzer::impact::analyze_incremental(llm_clone.as_ref(), &files, &options, &pieces, &is_cancelled, &mut on_progress)
+            });
+            let joined = tokio::select! {
+                joined = inference => joined,
+                reason = ticket.wait_cancelled() => return cancelled(reason),
+            };
+            match joined {
+                Ok(Ok((result, complete))) => {
+                    if let Some(key) = cache_key.as_ref().filter(|_| complete) {
+                        if let Err(e) = state.audit.cache_result(key, &result, ttl) {
+                            tracing::warn!("could not cache analysis: {}", e);
                         }
                     }
-                    None => Response::VoiceAnswer {
-                        text: "The LLM is not loaded so I cannot answer right now.".to_string(),
-                    },
+                    Response::AnalysisResult(result)
                 }
+                Ok(Err(e)) => Response::Error { message: e.to_string() },
+                Err(e) => Response::Error { message: format!("inference panicked: {}", e) },
             }
-            Err(e) => Response::Error {
-                message: format!("parse error: {}", e),
-            },
+        },
+        None => Response::AnalysisResult(stub_result(&files)),
+    }
+}
+
+/// The report when no model is loaded: the changed files and symbols, at low risk.
+fn stub_result(files: &[analyzer::diff::DiffFile]) -> protocol::AnalysisResult {
+    protocol::AnalysisResult {
+        summary: vec![
+            format!("Stub: {} file(s) changed", files.len()),
+            "Set CACTUS_MODEL_PATH to enable real analysis".to_string(),
+        ],
+        risk_level: "low".to_string(),
+        risk_reasons: vec!["LLM not loaded".to_string()],
+        impacted_files: files.iter().map(|f| analyzer::impact::impacted_file(f, 0.5)).collect(),
+        impacted_symbols: analyzer::symbols::extract(files),
+        suggested_actions: vec![],
+        confidence: 0.0,
+        cached: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::sync::mpsc as std_mpsc;
+
+    /// Deterministic backend: always submits the same review via tool call.
+    /// Plain completions answer with a fixed sentence, or fail when
+    /// `complete_fails` is set.
+    struct FakeBackend {
+        complete_fails: bool,
+    }
+
+    impl llm::LlmBackend for FakeBackend {
+        fn complete(&self, _system_prompt: &str, _user_message: &str) -> Result<String> {
+            if self.complete_fails {
+                Err(anyhow!("model exploded"))
+            } else {
+                Ok("Looks fine to me.".to_string())
+            }
+        }
+
+        fn complete_with_tools(
+            &self,
+            _user_message: &str,
+            _tools_json: &str,
+        ) -> Result<Vec<serde_json::Value>> {
+            Ok(vec![serde_json::json!({
+                "name": "submit_review",
+                "arguments": {
+                    "summary": ["renamed a helper"],
+                    "risk_level": "med",
+                    "risk_reasons": ["public API changed"],
+                    "suggested_actions": [{"label": "Update callers", "explanation": "Signature changed"}]
+                }
+            })])
+        }
+    }
+
+    /// Backend whose tool call blocks until the test releases it, so analyses
+    /// can be observed while running or queued behind it.
+    struct GatedBackend {
+        started: Mutex<std_mpsc::Sender<()>>,
+        release: Mutex<std_mpsc::Receiver<()>>,
+    }
+
+    impl llm::LlmBackend for GatedBackend {
+        fn complete(&self, _system_prompt: &str, _user_message: &str) -> Result<String> {
+            Ok(String::new())
+        }
+
+        fn complete_with_tools(&self, user_message: &str, tools_json: &str) -> Result<Vec<serde_json::Value>> {
+            self.started.lock().unwrap().send(()).unwrap();
+            self.release.lock().unwrap().recv().unwrap();
+            FakeBackend { complete_fails: false }.complete_with_tools(user_message, tools_json)
+        }
+    }
+
+    /// Returns the backend, a receiver signalled each time a tool call starts,
+    /// and a sender that lets one blocked call finish per message.
+    fn gated_backend() -> (Arc<dyn llm::LlmBackend>, std_mpsc::Receiver<()>, std_mpsc::Sender<()>) {
+        let (started_tx, started_rx) = std_mpsc::channel();
+        let (release_tx, release_rx) = std_mpsc::channel();
+        let backend = GatedBackend { started: Mutex::new(started_tx), release: Mutex::new(release_rx) };
+        (Arc::new(backend), started_rx, release_tx)
+    }
+
+    fn test_state(llm: Option<Arc<dyn llm::LlmBackend>>) -> Arc<State> {
+        let loaded = Loaded {
+            llm,
+            model_id: String::new(),
+            analysis: config::Analysis { cache_ttl: Duration::ZERO, ..Default::default() },
+            config_source: None,
+        };
+        Arc::new(State::new(Arc::new(store::AuditLog::open(":memory:").unwrap()), loaded, None))
+    }
+
+    /// Send one request line on a fresh connection and return every frame
+    /// written back before the daemon closes it.
+    async fn frames(state: Arc<State>, request: serde_json::Value) -> Vec<serde_json::Value> {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let handle = tokio::spawn(handle_connection(server, state));
+
+        let (reader, mut writer) = tokio::io::split(client);
+        let mut msg = request.to_string();
+        msg.push('\n');
+        writer.write_all(msg.as_bytes()).await.unwrap();
+        writer.shutdown().await.unwrap();
+
+        let mut out = Vec::new();
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            out.push(serde_json::from_str(line.trim()).unwrap());
+        }
+        handle.await.unwrap().unwrap();
+        out
+    }
+
+    /// Run one request line through `handle_connection` and return the parsed response.
+    async fn roundtrip(llm: Option<Arc<dyn llm::LlmBackend>>, request: serde_json::Value) -> serde_json::Value {
+        frames(test_state(llm), request).await.remove(0)
+    }
+
+    /// Block (off the runtime) until the gated backend reports a started call.
+    async fn wait_started(started: std_mpsc::Receiver<()>) -> std_mpsc::Receiver<()> {
+        tokio::task::spawn_blocking(move || {
+            started.recv_timeout(Duration::from_secs(5)).expect("backend call never started");
+            started
+        })
+        .await
+        .unwrap()
+    }
+
+    fn superseding_request(id: &str) -> serde_json::Value {
+        let mut req = analyze_request();
+        req["id"] = id.into();
+        req["payload"]["workspace"] = "/ws".into();
+        req["payload"]["supersede"] = true.into();
+        req["payload"]["stream"] = true.into();
+        req
+    }
+
+    fn analyze_request() -> serde_json::Value {
+        serde_json::json!({
+            "type": "analyze_diff",
+            "payload": {
+                "diff": "diff --git a/src/lib.rs b/src/lib.rs\n@@ -1 +1 @@\n-fn old() {}\n+fn new() {}\n",
+                "files_touched": ["src/lib.rs"],
+                "active_file": "src/lib.rs",
+                "trigger": "save"
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn analyze_diff_uses_backend_tool_call() {
+        let llm: Arc<dyn llm::LlmBackend> = Arc::new(FakeBackend { complete_fails: false });
+        let resp = roundtrip(Some(llm), analyze_request()).await;
+        assert_eq!(resp["type"], "analysis_result", "got: {}", resp);
+        assert_eq!(resp["payload"]["risk_level"], "med");
+        assert_eq!(resp["payload"]["summary"][0], "renamed a helper");
+        assert_eq!(resp["payload"]["impacted_files"][0]["path"], "src/lib.rs");
+    }
+
+    #[tokio::test]
+    async fn analyze_diff_without_backend_returns_stub_result() {
+        let resp = roundtrip(None, analyze_request()).await;
+        assert_eq!(resp["type"], "analysis_result", "got: {}", resp);
+        assert_eq!(resp["payload"]["risk_reasons"][0], "LLM not loaded");
+    }
+
+    #[test]
+    fn workspace_index_is_refreshed_at_most_once_per_interval() {
+        let state = test_state(None);
+        assert!(state.index_due("/w"));
+        assert!(!state.index_due("/w"), "a second analysis reuses the fresh index");
+        assert!(state.index_due("/other"));
+
+        let stale = Instant::now() - INDEX_REFRESH_INTERVAL;
+        state.index_refreshed.lock().unwrap().insert("/w".to_string(), stale);
+        assert!(state.index_due("/w"));
+    }
+
+    #[tokio::test]
+    async fn voice_query_returns_backend_text() {
+        let llm: Arc<dyn llm::LlmBackend> = Arc::new(FakeBackend { complete_fails: false });
+        let req = serde_json::json!({"type": "voice_query", "payload": {"question": "safe?", "context": null}});
+        let resp = roundtrip(Some(llm), req).await;
+        assert_eq!(resp["type"], "voice_answer");
+        assert_eq!(resp["payload"]["text"], "Looks fine to me.");
+    }
+
+    #[tokio::test]
+    async fn greet_backend_error_is_spoken_not_raised() {
+        let llm: Arc<dyn llm::LlmBackend> = Arc::new(FakeBackend { complete_fails: true });
+        let req = serde_json::json!({"type": "greet", "payload": {"last_analysis": null}});
+        let resp = roundtrip(Some(llm), req).await;
+        assert_eq!(resp["type"], "voice_answer");
+        let text = resp["payload"]["text"].as_str().unwrap();
+        assert!(text.contains("model exploded"), "got: {}", text);
+    }
+
+    #[tokio::test]
+    async fn cancel_unknown_id_is_acknowledged_as_not_found() {
+        let req = serde_json::json!({"id": "c1", "type": "cancel", "payload": {"id": "nope"}});
+        let resp = roundtrip(None, req).await;
+        assert_eq!(resp["id"], "c1");
+        assert_eq!(resp["type"], "cancel_ack");
+        assert_eq!(resp["payload"], serde_json::json!({"id": "nope", "found": false}));
+    }
+
+    #[tokio::test]
+    async fn cancel_stops_waiting_on_a_running_analysis() {
+        let (llm, started, release) = gated_backend();
+        let state = test_state(Some(llm));
+        let mut req = analyze_request();
+        req["id"] = "a1".into();
+        let running = tokio::spawn(frames(state.clone(), req));
+        let _started = wait_started(started).await;
+
+        let cancel = serde_json::json!({"type": "cancel", "payload": {"id": "a1"}});
+        let ack = frames(state.clone(), cancel).await.remove(0);
+        assert_eq!(ack["payload"]["found"], true, "got: {}", ack);
+
+        let resp = running.await.unwrap().pop().unwrap();
+        assert_eq!(resp["id"], "a1");
+        assert_eq!(resp["type"], "cancelled", "got: {}", resp);
+        assert_eq!(resp["payload"]["reason"], inflight::REASON_CANCELLED);
+        release.send(()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reload_swaps_the_model_while_running_analyses_finish_on_the_old_one() {
+        let dir = crate::test_support::TempDir::new("reload");
+        let script = format!("{}/tests/fixtures/llm/tool_call.json", env!("CARGO_MANIFEST_DIR"));
+        let config = dir.write("senior.toml", format!("[llm]\nbackend = \"scripted\"\nmodel = \"{}\"\n", script));
+        let (llm, started, release) = gated_backend();
+        let loaded = Loaded {
+            llm: Some(llm),
+            model_id: "gated".to_string(),
+            analysis: config::Analysis::default(),
+            config_source: None,
         };
+        let state = Arc::new(State::new(Arc::new(store::AuditLog::open(":memory:").unwrap()), loaded, Some(config.clone())));
+        let running = tokio::spawn(frames(state.clone(), analyze_request()));
+        let _started = wait_started(started).await;
 
-        let mut out = serde_json::to_string(&response)?;
-        out.push('\n');
-        writer.write_all(out.as_bytes()).await?;
+        let reloaded = frames(state.clone(), serde_json::json!({"type": "reload", "payload": null})).await.remove(0);
+        assert_eq!(reloaded["type"], "reloaded", "got: {}", reloaded);
+        assert_eq!(reloaded["payload"]["model_id"], format!("Scripted:{}", script));
+        assert_eq!(reloaded["payload"]["llm_loaded"], true);
+
+        release.send(()).unwrap();
+        let old = running.await.unwrap().pop().unwrap();
+        assert_eq!(old["payload"]["summary"][0], "renamed a helper", "got: {}", old);
+        let new = frames(state, analyze_request()).await.pop().unwrap();
+        assert_eq!(new["payload"]["summary"][0], "switched the cache to a HashMap", "got: {}", new);
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn failed_reload_keeps_the_current_model() {
+        let state = test_state(Some(Arc::new(FakeBackend { complete_fails: false })));
+        let state = Arc::new(State::new(
+            state.audit.clone(),
+            Loaded {
+                llm: state.loaded().llm.clone(),
+                model_id: "fake".to_string(),
+                analysis: config::Analysis::default(),
+                config_source: None,
+            },
+            Some(PathBuf::from("/nonexistent/senior.toml")),
+        ));
+        let resp = frames(state.clone(), serde_json::json!({"type": "reload", "payload": null})).await.remove(0);
+        assert_eq!(resp["type"], "error");
+        assert!(resp["payload"]["message"].as_str().unwrap().starts_with("reload failed: cannot read"), "got: {}", resp);
+        assert_eq!(state.loaded().model_id, "fake");
+    }
+
+    #[tokio::test]
+    async fn newer_analysis_supersedes_older_one_for_same_workspace() {
+        let (llm, started, release) = gated_backend();
+        let state = test_state(Some(llm));
+        let first = tokio::spawn(frames(state.clone(), superseding_request("old")));
+        let started = wait_started(started).await;
+
+        let second = tokio::spawn(frames(state.clone(), superseding_request("new")));
+        let old = first.await.unwrap().pop().unwrap();
+        assert_eq!(old["type"], "cancelled", "got: {}", old);
+        assert_eq!(old["payload"]["reason"], inflight::REASON_SUPERSEDED);
+
+        // The superseded call still occupies the model until it returns; the
+        // newer analysis queues behind it and then runs to completion.
+        release.send(()).unwrap();
+        let _started = wait_started(started).await;
+        release.send(()).unwrap();
+        let new = second.await.unwrap();
+        assert!(new.iter().any(|f| f["payload"]["stage"] == "queued"), "got: {:?}", new);
+        let result = new.last().unwrap();
+        assert_eq!(result["id"], "new");
+        assert_eq!(result["type"], "analysis_result", "got: {}", result);
+    }
+
+    #[tokio::test]
+    async fn queued_analysis_is_dropped_before_reaching_the_model() {
+        let (llm, started, release) = gated_backend();
+        let state = test_state(Some(llm));
+        let mut blocker = analyze_request();
+        blocker["id"] = "blocker".into();
+        let blocker = tokio::spawn(frames(state.clone(), blocker));
+        let started = wait_started(started).await;
+
+        let mut queued = analyze_request();
+        queued["id"] = "queued".into();
+        let queued = tokio::spawn(frames(state.clone(), queued));
+        // Retry until the queued request has registered itself.
+        let mut found = false;
+        for _ in 0..100 {
+            let cancel = serde_json::json!({"type": "cancel", "payload": {"id": "queued"}});
+            if frames(state.clone(), cancel).await[0]["payload"]["found"] == true {
+                found = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(found, "queued analysis never registered");
+        assert_eq!(queued.await.unwrap().pop().unwrap()["type"], "cancelled");
+
+        release.send(()).unwrap();
+        assert_eq!(blocker.await.unwrap().pop().unwrap()["type"], "analysis_result");
+        // Only the blocker ever reached the backend.
+        assert!(started.recv_timeout(Duration::from_millis(100)).is_err());
+    }
 }
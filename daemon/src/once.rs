@@ -0,0 +1,105 @@
+use crate::{analyzer, config, llm};
+use crate::protocol::AnalysisResult;
+use anyhow::{anyhow, Result};
+use std::io::Read;
+use std::path::Path;
+
+/// `--once`: review the patch at `diff_file` (stdin when `None` or `-`) with
+/// the configured model and return the result, without a socket, database or
+/// cache. Unlike the daemon, an empty diff, no model or one that fails to load
+/// is an error rather than a stub report, so a script notices.
+pub fn run(diff_file: Option<&Path>, config: &config::Config) -> Result<AnalysisResult> {
+    let diff = match diff_file.filter(|p| *p != Path::new("-")) {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?,
+        None => {
+            let mut diff = String::new();
+            std::io::stdin().read_to_string(&mut diff)?;
+            diff
+        }
+    };
+    if diff.trim().is_empty() {
+        return Err(anyhow!("nothing to review: the diff is empty"));
+    }
+    if config.backend.model.is_empty() {
+        return Err(anyhow!(
+            "no model configured for the {:?} backend; set llm.model in senior.toml, or CACTUS_MODEL_PATH, \
+             SENIOR_OPENAI_MODEL or SENIOR_LLM_SCRIPT",
+            config.backend.kind
+        ));
+    }
+    let llm = llm::load_backend(&config.backend)?;
+    review(llm.as_ref(), &diff, &config.analysis)
+}
+
+/// Review `diff` as the daemon would with `settings`: ignored files left out,
+/// then the model's review, then the risk rules.
+fn review(llm: &dyn llm::LlmBackend, diff: &str, settings: &config::Analysis) -> Result<AnalysisResult> {
+    let mut files = analyzer::diff::parse_diff(diff);
+    files.retain(|f| !settings.ignore.is_match(&f.path));
+    let options = analyzer::impact::PromptOptions { budget: settings.prompt_tokens, active_file: None };
+    let mut result = analyzer::impact::analyze(llm, &files, &options, &|| false, &mut |_| {})?;
+    let paths: Vec<String> = files.iter().map(|f| f.path.clone()).collect();
+    analyzer::rules::apply(&mut result, &paths, &settings.risk_rules);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::LlmBackend;
+    use crate::test_support::{config, TempDir};
+    use std::sync::Mutex;
+
+    const DIFF: &str = "diff --git a/migrations/001.sql b/migrations/001.sql\n@@ -0,0 +1 @@\n+DROP TABLE users;\n\
+                        diff --git a/Cargo.lock b/Cargo.lock\n@@ -1 +1 @@\n-a\n+b\n";
+
+    /// Rates every change low risk and keeps the prompts it was sent.
+    #[derive(Default)]
+    struct LowRisk {
+        prompts: Mutex<Vec<String>>,
+    }
+
+    impl LlmBackend for LowRisk {
+        fn complete(&self, _system_prompt: &str, _user_message: &str) -> Result<String> {
+            Err(anyhow!("not used"))
+        }
+
+        fn complete_with_tools(&self, user_message: &str, _tools_json: &str) -> Result<Vec<serde_json::Value>> {
+            self.prompts.lock().unwrap().push(user_message.to_string());
+            Ok(vec![serde_json::json!({
+                "name": "submit_review",
+                "arguments": {"summary": ["Dropped a table."], "risk_level": "low", "risk_reasons": [], "suggested_actions": []}
+            })])
+        }
+    }
+
+    #[test]
+    fn review_applies_ignore_globs_and_risk_rules() {
+        let config = config(
+            "once",
+            "[analysis]\nignore = [\"*.lock\"]\n\n[[risk]]\npaths = [\"migrations/**\"]\nlevel = \"high\"\n",
+        );
+
+        let llm = LowRisk::default();
+        let result = review(&llm, DIFF, &config.analysis).unwrap();
+        assert!(!llm.prompts.lock().unwrap().iter().any(|p| p.contains("Cargo.lock")));
+        assert_eq!(result.summary[0], "Dropped a table.");
+        assert_eq!(result.impacted_files.len(), 1);
+        assert_eq!(result.impacted_files[0].path, "migrations/001.sql");
+        assert_eq!(result.risk_level, "high");
+        assert!(result.risk_reasons.iter().any(|r| r.starts_with("Matches risk rule `migrations/**`")));
+    }
+
+    #[test]
+    fn an_empty_diff_or_no_model_is_an_error() {
+        let dir = TempDir::new("once-errors");
+        let empty = dir.write("empty.patch", "\n");
+        let patch = dir.write("change.patch", DIFF);
+        let no_model = config("once-no-model", "[llm]\nbackend = \"openai\"\n");
+
+        let err = run(Some(&empty), &no_model).unwrap_err();
+        assert!(err.to_string().contains("the diff is empty"), "got: {}", err);
+        let err = run(Some(&patch), &no_model).unwrap_err();
+        assert!(err.to_string().contains("no model configured"), "got: {}", err);
+    }
+}
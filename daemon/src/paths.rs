@@ -0,0 +1,291 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::fs::{self, DirBuilder, Permissions};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+
+const SOCKET_NAME: &str = "senior.sock";
+const DB_NAME: &str = "audit.db";
+
+/// A socket and data directory asked for by one source of settings; either
+/// may be left to the next source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Locations {
+    pub socket: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+}
+
+/// `[storage]` in `senior.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageFile {
+    pub socket: Option<String>,
+    pub data_dir: Option<String>,
+}
+
+impl StorageFile {
+    /// The locations it names, read as `expand` does from the directory
+    /// `base` of the file.
+    pub fn locations(&self, base: &Path, env: &dyn Fn(&str) -> Option<String>) -> Locations {
+        Locations {
+            socket: self.socket.as_deref().map(|p| expand(p, base, env)),
+            data_dir: self.data_dir.as_deref().map(|p| expand(p, base, env)),
+        }
+    }
+}
+
+/// The settings file to read: `path` (a `--config` flag) or `SENIOR_CONFIG`,
+/// which must exist, or else `$XDG_CONFIG_HOME/senior/senior.toml`
+/// (`~/.config/senior/senior.toml`) if it exists.
+pub fn config_file(path: Option<PathBuf>, env: &dyn Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    path.or_else(|| env("SENIOR_CONFIG").map(PathBuf::from)).or_else(|| {
+        let absolute = |name: &str| env(name).map(PathBuf::from).filter(|p| p.is_absolute());
+        let dir = absolute("XDG_CONFIG_HOME").or_else(|| absolute("HOME").map(|home| home.join(".config")))?;
+        Some(dir.join("senior").join("senior.toml")).filter(|p| p.exists())
+    })
+}
+
+/// The `[storage]` locations of the settings file `config_file` picks, for a
+/// client that needs the daemon's socket but none of its other settings,
+/// which are left unchecked.
+pub fn configured(path: Option<PathBuf>, env: &dyn Fn(&str) -> Option<String>) -> Result<Locations> {
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    struct StorageOnly {
+        storage: StorageFile,
+    }
+    let Some(path) = config_file(path, env) else { return Ok(Locations::default()) };
+    let text = fs::read_to_string(&path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+    let file: StorageOnly =
+        toml::from_str(&text).map_err(|e| anyhow!("{}: {}", path.display(), e.to_string().trim_end()))?;
+    Ok(file.storage.locations(path.parent().unwrap_or(Path::new(".")), env))
+}
+
+/// `~/x` is under `$HOME`; a relative path is relative to `base`.
+pub fn expand(path: &str, base: &Path, env: &dyn Fn(&str) -> Option<String>) -> PathBuf {
+    if let (Some(rest), Some(home)) = (path.strip_prefix("~/"), env("HOME")) {
+        return Path::new(&home).join(rest);
+    }
+    base.join(path)
+}
+
+/// Where the daemon listens and keeps its database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoragePaths {
+    pub socket: PathBuf,
+    /// Holds the SQLite database; private to the user.
+    pub data_dir: PathBuf,
+}
+
+impl StoragePaths {
+    /// Each location comes from its override if given (a command-line flag,
+    /// else `SENIOR_SOCKET_PATH` / `SENIOR_DATA_DIR`, else the config file),
+    /// otherwise from the XDG base directories: the data directory is
+    /// `$XDG_DATA_HOME/senior` (`~/.local/share/senior` by default) and the
+    /// socket lives in `$XDG_RUNTIME_DIR`, or in the data directory when there
+    /// is no runtime directory, as on macOS. `env` reads an environment variable.
+    pub fn resolve(flags: Locations, configured: Locations, env: &dyn Fn(&str) -> Option<String>) -> Result<Self> {
+        // The XDG spec says relative paths in these variables are to be ignored.
+        let absolute = |name: &str| env(name).map(PathBuf::from).filter(|p| p.is_absolute());
+        let data_dir = flags.data_dir.or_else(|| env("SENIOR_DATA_DIR").map(PathBuf::from)).or(configured.data_dir);
+        let data_dir = match data_dir {
+            Some(dir) => dir,
+            None => absolute("XDG_DATA_HOME")
+                .or_else(|| absolute("HOME").map(|home| home.join(".local/share")))
+                .ok_or_else(|| anyhow!("cannot find a data directory: set HOME, XDG_DATA_HOME or SENIOR_DATA_DIR"))?
+                .join("senior"),
+        };
+        let socket = flags
+            .socket
+            .or_else(|| env("SENIOR_SOCKET_PATH").map(PathBuf::from))
+            .or(configured.socket)
+            .unwrap_or_else(|| absolute("XDG_RUNTIME_DIR").unwrap_or_else(|| data_dir.clone()).join(SOCKET_NAME));
+        Ok(Self { socket, data_dir })
+    }
+
+    pub fn db(&self) -> PathBuf {
+        self.data_dir.join(DB_NAME)
+    }
+
+    /// Create the data directory and the socket's directory, readable by the
+    /// user alone, where they are missing. Existing ones are left as they are:
+    /// either may be a shared one such as `$HOME` or `/tmp` that was named
+    /// explicitly, and the database and socket are restricted themselves.
+    pub fn prepare(&self) -> Result<()> {
+        for dir in [Some(self.data_dir.as_path()), self.socket.parent()].into_iter().flatten() {
+            if !dir.as_os_str().is_empty() && !dir.exists() {
+                private_dir(dir)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn private_dir(dir: &Path) -> Result<()> {
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .map_err(|e| anyhow!("cannot create {}: {}", dir.display(), e))
+}
+
+/// Make `path` readable and writable by the user alone.
+pub fn restrict(path: &Path) -> Result<()> {
+    fs::set_permissions(path, Permissions::from_mode(0o600))
+        .map_err(|e| anyhow!("cannot restrict permissions of {}: {}", path.display(), e))
+}
+
+/// Listen at `socket` such that only the user can ever connect. The socket is
+/// bound in a private directory beside it, restricted, then renamed into
+/// place, so there is no moment at which it exists with looser permissions.
+pub fn bind_private(socket: &Path) -> Result<UnixListener> {
+    let name = socket.file_name().ok_or_else(|| anyhow!("{} does not name a socket", socket.display()))?;
+    let staging = socket.with_file_name(format!(".{}.bind", std::process::id()));
+    let _ = fs::remove_dir_all(&staging);
+    DirBuilder::new()
+        .mode(0o700)
+        .create(&staging)
+        .map_err(|e| anyhow!("cannot create {}: {}", staging.display(), e))?;
+    let staged = staging.join(name);
+    let bound = UnixListener::bind(&staged)
+        .map_err(|e| anyhow!("cannot bind {}: {}", socket.display(), e))
+        .and_then(|listener| restrict(&staged).map(|_| listener))
+        .and_then(|listener| {
+            fs::rename(&staged, socket).map_err(|e| anyhow!("cannot move the socket to {}: {}", socket.display(), e))?;
+            Ok(listener)
+        });
+    let _ = fs::remove_dir_all(&staging);
+    bound
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use std::collections::HashMap;
+
+    fn locations(socket: Option<&str>, data_dir: Option<&str>) -> Locations {
+        Locations { socket: socket.map(PathBuf::from), data_dir: data_dir.map(PathBuf::from) }
+    }
+
+    fn resolve_layered(
+        vars: &[(&str, &str)],
+        flags: Locations,
+        configured: Locations,
+    ) -> Result<StoragePaths> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        StoragePaths::resolve(flags, configured, &|name| vars.get(name).cloned())
+    }
+
+    fn resolve(vars: &[(&str, &str)], socket: Option<&str>, data_dir: Option<&str>) -> Result<StoragePaths> {
+        resolve_layered(vars, locations(socket, data_dir), Locations::default())
+    }
+
+    fn paths(socket: &str, data_dir: &str) -> StoragePaths {
+        StoragePaths { socket: socket.into(), data_dir: data_dir.into() }
+    }
+
+    #[test]
+    fn defaults_follow_xdg() {
+        let vars = [("HOME", "/home/me"), ("XDG_RUNTIME_DIR", "/run/user/1000")];
+        assert_eq!(
+            resolve(&vars, None, None).unwrap(),
+            paths("/run/user/1000/senior.sock", "/home/me/.local/share/senior")
+        );
+        let vars = [("HOME", "/home/me"), ("XDG_DATA_HOME", "/data")];
+        assert_eq!(resolve(&vars, None, None).unwrap(), paths("/data/senior/senior.sock", "/data/senior"));
+    }
+
+    #[test]
+    fn relative_xdg_dirs_are_ignored() {
+        let vars = [("HOME", "/home/me"), ("XDG_DATA_HOME", "data"), ("XDG_RUNTIME_DIR", "run")];
+        assert_eq!(
+            resolve(&vars, None, None).unwrap(),
+            paths("/home/me/.local/share/senior/senior.sock", "/home/me/.local/share/senior")
+        );
+    }
+
+    #[test]
+    fn flags_beat_environment_beats_defaults() {
+        let vars = [
+            ("HOME", "/home/me"),
+            ("SENIOR_SOCKET_PATH", "/env/s.sock"),
+            ("SENIOR_DATA_DIR", "/env/data"),
+        ];
+        assert_eq!(resolve(&vars, None, None).unwrap(), paths("/env/s.sock", "/env/data"));
+        assert_eq!(
+            resolve(&vars, Some("/flag/s.sock"), Some("/flag/data")).unwrap(),
+            paths("/flag/s.sock", "/flag/data")
+        );
+    }
+
+    #[test]
+    fn config_file_sits_between_environment_and_defaults() {
+        let configured = || locations(Some("/conf/s.sock"), Some("/conf/data"));
+        let vars = [("HOME", "/home/me"), ("XDG_RUNTIME_DIR", "/run/user/1000")];
+        assert_eq!(
+            resolve_layered(&vars, Locations::default(), configured()).unwrap(),
+            paths("/conf/s.sock", "/conf/data")
+        );
+        let vars = [("HOME", "/home/me"), ("SENIOR_DATA_DIR", "/env/data")];
+        assert_eq!(
+            resolve_layered(&vars, locations(Some("/flag/s.sock"), None), configured()).unwrap(),
+            paths("/flag/s.sock", "/env/data")
+        );
+    }
+
+    #[test]
+    fn no_home_and_no_override_is_an_error() {
+        assert!(resolve(&[], None, None).is_err());
+        assert_eq!(resolve(&[], None, Some("/d")).unwrap(), paths("/d/senior.sock", "/d"));
+    }
+
+    #[test]
+    fn prepare_makes_private_directories() {
+        let dir = TempDir::new("paths");
+        let root = &dir.root;
+        let storage = paths(root.join("run/senior.sock").to_str().unwrap(), root.join("data/senior").to_str().unwrap());
+        storage.prepare().unwrap();
+
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&storage.data_dir), 0o700);
+        assert_eq!(mode(&root.join("run")), 0o700);
+
+        let shared = root.join("shared");
+        fs::create_dir(&shared).unwrap();
+        fs::set_permissions(&shared, Permissions::from_mode(0o755)).unwrap();
+        paths(shared.join("senior.sock").to_str().unwrap(), shared.to_str().unwrap()).prepare().unwrap();
+        assert_eq!(mode(&shared), 0o755, "a directory that already existed is not locked down");
+    }
+
+    #[test]
+    fn client_reads_only_storage_from_the_config_file() {
+        let dir = TempDir::new("configured");
+        let file = dir.write("senior.toml", "[llm]\nbackend = \"not checked\"\n\n[storage]\nsocket = \"run/s.sock\"\ndata_dir = \"~/d\"\n");
+        let env = |name: &str| match name {
+            "HOME" => Some("/home/me".to_string()),
+            "SENIOR_CONFIG" => Some(file.to_string_lossy().into_owned()),
+            _ => None,
+        };
+
+        let storage = configured(None, &env).unwrap();
+        assert_eq!(storage.socket, Some(dir.root.join("run/s.sock")));
+        assert_eq!(storage.data_dir, Some(PathBuf::from("/home/me/d")));
+        assert_eq!(configured(None, &|_| None).unwrap(), Locations::default());
+        assert!(configured(Some(dir.root.join("missing.toml")), &env).is_err());
+    }
+
+    #[test]
+    fn socket_is_bound_private_and_in_place() {
+        let dir = TempDir::new("bind");
+        let socket = dir.root.join("senior.sock");
+
+        let _listener = bind_private(&socket).unwrap();
+
+        assert_eq!(fs::metadata(&socket).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(std::os::unix::net::UnixStream::connect(&socket).is_ok());
+        let left: Vec<_> = fs::read_dir(&dir.root).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(left, vec!["senior.sock"], "the staging directory is removed");
+    }
+}
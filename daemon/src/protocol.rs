@@ -1,38 +1,186 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
+/// Wire form of a request: `{"id": .., "type": .., "payload": ..}`.
+/// `id` is optional; when present, every response frame for the request echoes it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequestEnvelope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub request: Request,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", content = "payload")]
 pub enum Request {
     #[serde(rename = "ping")]
     Ping,
     #[serde(rename = "analyze_diff")]
     AnalyzeDiff(AnalyzeDiffPayload),
+    #[serde(rename = "analyze_repo")]
+    AnalyzeRepo(AnalyzeRepoPayload),
     #[serde(rename = "greet")]
     Greet(GreetPayload),
     #[serde(rename = "voice_query")]
     VoiceQuery(VoiceQueryPayload),
+    #[serde(rename = "cancel")]
+    Cancel(CancelPayload),
+    #[serde(rename = "history")]
+    History(HistoryPayload),
+    /// Re-read the configuration and swap in the model it names.
+    #[serde(rename = "reload")]
+    Reload,
+    #[serde(rename = "status")]
+    Status,
+    #[serde(rename = "render")]
+    Render(RenderPayload),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AnalyzeDiffPayload {
     pub diff: String,
     pub files_touched: Vec<String>,
     pub active_file: String,
     pub trigger: String,
+    /// Emit `analysis_progress` / `token` frames before the final result.
+    #[serde(default)]
+    pub stream: bool,
+    /// Workspace root the diff belongs to; scopes `supersede`.
+    #[serde(default)]
+    pub workspace: Option<String>,
+    /// Cancel older superseding analyses of the same workspace.
+    #[serde(default)]
+    pub supersede: bool,
+}
+
+/// Ask the daemon to compute the diff itself instead of shipping it over the socket.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnalyzeRepoPayload {
+    /// Absolute path to the repository (or any directory inside it).
+    pub repo_root: String,
+    pub selector: DiffSelector,
+    #[serde(default)]
+    pub active_file: String,
+    #[serde(default = "default_trigger")]
+    pub trigger: String,
+    /// Emit `analysis_progress` / `token` frames before the final result.
+    #[serde(default)]
+    pub stream: bool,
+    /// Cancel older superseding analyses of the same `repo_root`.
+    #[serde(default)]
+    pub supersede: bool,
+}
+
+fn default_trigger() -> String {
+    "manual".to_string()
+}
+
+/// Which changes an `analyze_repo` request covers.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiffSelector {
+    /// Staged and unstaged changes against HEAD.
+    WorkingTree,
+    /// Only what is in the index.
+    Staged,
+    /// `from..to`; `to` defaults to HEAD.
+    CommitRange { from: String, to: Option<String> },
+    /// The current branch against its merge-base with `base`.
+    Branch { base: String },
+    /// Working tree if dirty, otherwise the last commit.
+    LastChange,
+}
+
+/// Cancel the in-flight request that was sent with this `id`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CancelPayload {
+    pub id: String,
+}
+
+/// Past analyses, newest first. Every filter is optional.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryPayload {
+    /// Only analyses of this workspace root.
+    #[serde(default)]
+    pub repo: Option<String>,
+    /// Only analyses whose diff touched this file, repo-relative or absolute.
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Unix seconds, inclusive.
+    #[serde(default)]
+    pub since: Option<i64>,
+    /// Unix seconds, inclusive.
+    #[serde(default)]
+    pub until: Option<i64>,
+    #[serde(default = "default_history_limit")]
+    pub limit: usize,
+}
+
+fn default_history_limit() -> usize {
+    50
+}
+
+/// Turn a result, typically one the daemon sent earlier, into a report.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenderPayload {
+    pub result: AnalysisResult,
+    #[serde(default)]
+    pub format: RenderFormat,
+}
+
+/// Report formats `render` produces; see the `render` module.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderFormat {
+    /// Plain text, as `senior analyze` prints it.
+    #[default]
+    Text,
+    Markdown,
+    /// SARIF 2.1.0 JSON.
+    Sarif,
+    /// JUnit XML.
+    Junit,
+}
+
+impl RenderFormat {
+    pub const ALL: [RenderFormat; 4] = [RenderFormat::Text, RenderFormat::Markdown, RenderFormat::Sarif, RenderFormat::Junit];
+
+    /// The format named as on the wire: `text`, `markdown`, `sarif` or `junit`.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderFormat::Text => "text",
+            RenderFormat::Markdown => "markdown",
+            RenderFormat::Sarif => "sarif",
+            RenderFormat::Junit => "junit",
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GreetPayload {
     pub last_analysis: Option<AnalysisResult>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct VoiceQueryPayload {
     pub question: String,
     pub context: Option<AnalysisResult>,
 }
 
-#[derive(Debug, Serialize)]
+/// Wire form of a response frame; `id` echoes the request's id, if it had one.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResponseEnvelope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub response: Response,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", content = "payload")]
 pub enum Response {
     #[serde(rename = "pong")]
@@ -43,9 +191,49 @@ pub enum Response {
     Error { message: String },
     #[serde(rename = "voice_answer")]
     VoiceAnswer { text: String },
+    /// Streaming only: the analysis reached a new stage.
+    #[serde(rename = "analysis_progress")]
+    AnalysisProgress { stage: String, detail: String },
+    /// Streaming only: a chunk of raw model output.
+    #[serde(rename = "token")]
+    Token { text: String },
+    /// The analysis was dropped before producing a result.
+    #[serde(rename = "cancelled")]
+    Cancelled { reason: String },
+    /// Reply to `cancel`; `found` is false if `id` was not in flight.
+    #[serde(rename = "cancel_ack")]
+    CancelAck { id: String, found: bool },
+    /// Reply to `history`.
+    #[serde(rename = "history")]
+    History { entries: Vec<HistoryEntry> },
+    /// Reply to `reload`: the model now in use, and whether it loaded or the
+    /// daemon is in stub mode.
+    #[serde(rename = "reloaded")]
+    Reloaded { model_id: String, llm_loaded: bool },
+    /// Reply to `status`.
+    #[serde(rename = "status")]
+    Status(DaemonStatus),
+    /// Reply to `render`.
+    #[serde(rename = "rendered")]
+    Rendered { format: RenderFormat, text: String },
 }
 
+/// What the daemon is running, for `status`.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DaemonStatus {
+    pub version: String,
+    /// As in `reloaded`.
+    pub model_id: String,
+    pub llm_loaded: bool,
+    /// An analysis holds the model right now.
+    pub busy: bool,
+    pub uptime_secs: u64,
+    /// The `senior.toml` the current settings came from, if any.
+    #[serde(default)]
+    pub config: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct AnalysisResult {
     pub summary: Vec<String>,
     pub risk_level: String,
@@ -54,6 +242,41 @@ pub struct AnalysisResult {
     pub impacted_symbols: Vec<ImpactedSymbol>,
     pub suggested_actions: Vec<SuggestedAction>,
     pub confidence: f32,
+    /// Reused from an earlier analysis of the same diff rather than computed.
+    #[serde(default)]
+    pub cached: bool,
+}
+
+/// `risk_level` as 0, 1 or 2 for `low`, `med` (or `medium`) and `high`,
+/// ignoring case and surrounding space; anything else counts as low.
+pub fn risk_rank(level: &str) -> u8 {
+    match level.trim().to_ascii_lowercase().as_str() {
+        "high" => 2,
+        "med" | "medium" => 1,
+        _ => 0,
+    }
+}
+
+/// One recorded analysis.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    pub id: i64,
+    /// Unix seconds when the analysis finished.
+    pub ts: i64,
+    /// Workspace root; empty when the request did not name one.
+    pub repo: String,
+    /// Branch and commit checked out at the time; empty when unknown.
+    pub branch: String,
+    pub head: String,
+    pub trigger: String,
+    /// `analyzer::impact::diff_hash` of the analysed diff.
+    pub diff_hash: String,
+    /// Paths the diff touched.
+    pub files: Vec<String>,
+    /// Backend and model that produced the result; empty in stub mode.
+    pub model: String,
+    pub latency_ms: u64,
+    pub result: AnalysisResult,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -61,14 +284,22 @@ pub struct ImpactedFile {
     pub path: String,
     pub score: f32,
     pub why: Vec<String>,
+    /// `added`, `modified`, `deleted`, `renamed` or `copied` for files in the
+    /// diff; empty for files found elsewhere in the workspace.
+    #[serde(default)]
+    pub change: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ImpactedSymbol {
     pub name: String,
+    /// `function`, `method`, `type` or `constant`.
     pub kind: String,
     pub file: String,
     pub score: f32,
+    /// `added`, `modified` or `deleted`.
+    #[serde(default)]
+    pub change: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -95,6 +326,186 @@ mod tests {
         assert!(matches!(req, Request::AnalyzeDiff(_)));
     }
 
+    #[test]
+    fn test_deserialize_analyze_repo_defaults() {
+        let raw = r#"{"type":"analyze_repo","payload":{"repo_root":"/src/app","selector":{"kind":"working_tree"}}}"#;
+        let req: Request = serde_json::from_str(raw).unwrap();
+        if let Request::AnalyzeRepo(p) = req {
+            assert_eq!(p.repo_root, "/src/app");
+            assert_eq!(p.selector, DiffSelector::WorkingTree);
+            assert_eq!(p.active_file, "");
+            assert_eq!(p.trigger, "manual");
+        } else {
+            panic!("expected AnalyzeRepo");
+        }
+    }
+
+    #[test]
+    fn test_deserialize_history_defaults() {
+        let raw = r#"{"type":"history","payload":{"file":"src/a.rs","since":1700000000}}"#;
+        let req: Request = serde_json::from_str(raw).unwrap();
+        if let Request::History(p) = req {
+            assert_eq!(p.repo, None);
+            assert_eq!(p.file.as_deref(), Some("src/a.rs"));
+            assert_eq!(p.since, Some(1_700_000_000));
+            assert_eq!(p.until, None);
+            assert_eq!(p.limit, 50);
+        } else {
+            panic!("expected History");
+        }
+    }
+
+    #[test]
+    fn test_deserialize_diff_selectors() {
+        let range: DiffSelector = serde_json::from_str(r#"{"kind":"commit_range","from":"v1.0"}"#).unwrap();
+        assert_eq!(range, DiffSelector::CommitRange { from: "v1.0".into(), to: None });
+        let branch: DiffSelector = serde_json::from_str(r#"{"kind":"branch","base":"main"}"#).unwrap();
+        assert_eq!(branch, DiffSelector::Branch { base: "main".into() });
+        let staged: DiffSelector = serde_json::from_str(r#"{"kind":"staged"}"#).unwrap();
+        assert_eq!(staged, DiffSelector::Staged);
+        assert!(serde_json::from_str::<DiffSelector>(r#"{"kind":"stash"}"#).is_err());
+    }
+
+    #[test]
+    fn test_envelope_without_id() {
+        let raw = r#"{"type":"ping","payload":null}"#;
+        let env: RequestEnvelope = serde_json::from_str(raw).unwrap();
+        assert_eq!(env.id, None);
+        assert!(matches!(env.request, Request::Ping));
+    }
+
+    #[test]
+    fn test_envelope_with_id_and_stream_flag() {
+        let raw = r#"{"id":"req-7","type":"analyze_repo","payload":{"repo_root":"/r","selector":{"kind":"staged"},"stream":true}}"#;
+        let env: RequestEnvelope = serde_json::from_str(raw).unwrap();
+        assert_eq!(env.id.as_deref(), Some("req-7"));
+        if let Request::AnalyzeRepo(p) = env.request {
+            assert!(p.stream);
+        } else {
+            panic!("expected AnalyzeRepo");
+        }
+    }
+
+    #[test]
+    fn test_envelope_unknown_type_is_error() {
+        let raw = r#"{"id":"1","type":"nope","payload":{}}"#;
+        assert!(serde_json::from_str::<RequestEnvelope>(raw).is_err());
+    }
+
+    #[test]
+    fn test_response_envelope_echoes_id() {
+        let env = ResponseEnvelope {
+            id: Some("req-7".to_string()),
+            response: Response::Token { text: "risk".to_string() },
+        };
+        let val: serde_json::Value = serde_json::to_value(&env).unwrap();
+        assert_eq!(val["id"], "req-7");
+        assert_eq!(val["type"], "token");
+        assert_eq!(val["payload"]["text"], "risk");
+    }
+
+    #[test]
+    fn test_response_envelope_omits_missing_id() {
+        let env = ResponseEnvelope { id: None, response: Response::Pong };
+        let json = serde_json::to_string(&env).unwrap();
+        assert_eq!(json, r#"{"type":"pong"}"#);
+    }
+
+    #[test]
+    fn test_serialize_analysis_progress() {
+        let resp = Response::AnalysisProgress { stage: "reviewing".into(), detail: "2 file(s)".into() };
+        let val: serde_json::Value = serde_json::to_value(&resp).unwrap();
+        assert_eq!(val["type"], "analysis_progress");
+        assert_eq!(val["payload"]["stage"], "reviewing");
+    }
+
+    #[test]
+    fn test_deserialize_cancel() {
+        let raw = r#"{"type":"cancel","payload":{"id":"req-3"}}"#;
+        let req: Request = serde_json::from_str(raw).unwrap();
+        if let Request::Cancel(p) = req {
+            assert_eq!(p.id, "req-3");
+        } else {
+            panic!("expected Cancel");
+        }
+    }
+
+    #[test]
+    fn test_analyze_diff_supersede_fields_default_off() {
+        let raw = r#"{"type":"analyze_diff","payload":{"diff":"","files_touched":[],"active_file":"","trigger":"save"}}"#;
+        if let Request::AnalyzeDiff(p) = serde_json::from_str(raw).unwrap() {
+            assert!(!p.supersede);
+            assert_eq!(p.workspace, None);
+        } else {
+            panic!("expected AnalyzeDiff");
+        }
+    }
+
+    #[test]
+    fn test_serialize_cancelled_and_ack() {
+        let val = serde_json::to_value(Response::Cancelled { reason: "cancelled by client".into() }).unwrap();
+        assert_eq!(val["type"], "cancelled");
+        assert_eq!(val["payload"]["reason"], "cancelled by client");
+        let val = serde_json::to_value(Response::CancelAck { id: "r".into(), found: false }).unwrap();
+        assert_eq!(val["type"], "cancel_ack");
+        assert_eq!(val["payload"]["found"], false);
+    }
+
+    #[test]
+    fn test_reload_round_trip() {
+        let req: Request = serde_json::from_str(r#"{"type":"reload"}"#).unwrap();
+        assert!(matches!(req, Request::Reload));
+        let val = serde_json::to_value(Response::Reloaded { model_id: "Cactus:/m".into(), llm_loaded: true }).unwrap();
+        assert_eq!(val["type"], "reloaded");
+        assert_eq!(val["payload"]["model_id"], "Cactus:/m");
+        assert_eq!(val["payload"]["llm_loaded"], true);
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let raw = r#"{"type":"render","payload":{"result":{"summary":[],"risk_level":"low","risk_reasons":[],"impacted_files":[],"impacted_symbols":[],"suggested_actions":[],"confidence":0.5}}}"#;
+        if let Request::Render(p) = serde_json::from_str(raw).unwrap() {
+            assert_eq!(p.format, RenderFormat::Text, "format defaults to text");
+            assert_eq!(p.result.risk_level, "low");
+        } else {
+            panic!("expected Render");
+        }
+        let val = serde_json::to_value(Response::Rendered { format: RenderFormat::Sarif, text: "{}".into() }).unwrap();
+        assert_eq!(val["type"], "rendered");
+        assert_eq!(val["payload"]["format"], "sarif");
+        assert!(RenderFormat::ALL.into_iter().all(|f| RenderFormat::parse(f.name()) == Some(f)));
+        assert_eq!(serde_json::to_value(RenderFormat::Junit).unwrap(), "junit");
+    }
+
+    #[test]
+    fn test_request_envelope_round_trip() {
+        let env = RequestEnvelope {
+            id: None,
+            request: Request::AnalyzeRepo(AnalyzeRepoPayload {
+                repo_root: "/r".into(),
+                selector: DiffSelector::CommitRange { from: "main".into(), to: None },
+                active_file: String::new(),
+                trigger: "cli".into(),
+                stream: true,
+                supersede: false,
+            }),
+        };
+        let json = serde_json::to_value(&env).unwrap();
+        assert!(json.get("id").is_none());
+        assert_eq!(json["type"], "analyze_repo");
+        assert_eq!(json["payload"]["selector"]["kind"], "commit_range");
+        let back: RequestEnvelope = serde_json::from_value(json).unwrap();
+        assert!(matches!(back.request, Request::AnalyzeRepo(p) if p.repo_root == "/r" && p.stream));
+    }
+
+    #[test]
+    fn test_response_envelope_round_trip() {
+        let raw = r#"{"id":"x","type":"status","payload":{"version":"0.1.0","model_id":"Cactus:","llm_loaded":false,"busy":false,"uptime_secs":3}}"#;
+        let env: ResponseEnvelope = serde_json::from_str(raw).unwrap();
+        assert_eq!(env.id.as_deref(), Some("x"));
+        assert!(matches!(env.response, Response::Status(s) if s.uptime_secs == 3 && s.config.is_none()));
+    }
+
     #[test]
     fn test_serialize_pong() {
         let resp = Response::Pong;
@@ -121,6 +532,7 @@ mod tests {
             impacted_symbols: vec![],
             suggested_actions: vec![],
             confidence: 0.9,
+            cached: false,
         });
         let json = serde_json::to_string(&result).unwrap();
         let val: serde_json::Value = serde_json::from_str(&json).unwrap();
@@ -164,12 +576,14 @@ mod tests {
             path: "src/auth.rs".to_string(),
             score: 0.9,
             why: vec!["big change".to_string()],
+            change: "modified".to_string(),
         };
         let json = serde_json::to_string(&f).unwrap();
         let val: serde_json::Value = serde_json::from_str(&json).unwrap();
         assert_eq!(val["path"], "src/auth.rs");
         assert_eq!(val["score"], 0.9);
         assert_eq!(val["why"][0], "big change");
+        assert_eq!(val["change"], "modified");
     }
 
     #[test]
@@ -214,6 +628,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_risk_rank_orders_levels_in_any_case() {
+        assert!(risk_rank("high") > risk_rank("med") && risk_rank("med") > risk_rank("low"));
+        assert_eq!(risk_rank("HIGH "), 2);
+        assert_eq!(risk_rank("Medium"), risk_rank("med"));
+        assert_eq!(risk_rank("unknown"), 0);
+    }
+
     #[test]
     fn test_serialize_voice_answer() {
         let resp = Response::VoiceAnswer { text: "Looks good.".to_string() };
@@ -0,0 +1,80 @@
+use super::{text, Style};
+use crate::protocol::{risk_rank, AnalysisResult};
+use std::fmt::Write;
+
+/// A JUnit XML report with one test case, `risk`, that fails when the change
+/// is rated high risk. The text report goes in the failure, or in
+/// `system-out` when the case passes, so CI shows it either way.
+pub fn junit(result: &AnalysisResult) -> String {
+    let report = text(result, Style::PLAIN);
+    let failed = risk_rank(&result.risk_level) == 2;
+    let failures = u8::from(failed);
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(out, "<testsuites name=\"senior\" tests=\"1\" failures=\"{}\" errors=\"0\">", failures);
+    let _ = writeln!(out, "  <testsuite name=\"senior\" tests=\"1\" failures=\"{}\" errors=\"0\" skipped=\"0\">", failures);
+    let _ = writeln!(out, "    <properties>");
+    let _ = writeln!(out, "      <property name=\"risk_level\" value=\"{}\"/>", escape(&result.risk_level));
+    let _ = writeln!(out, "      <property name=\"confidence\" value=\"{:.2}\"/>", result.confidence);
+    let _ = writeln!(out, "    </properties>");
+    let _ = writeln!(out, "    <testcase classname=\"senior\" name=\"risk\">");
+    if failed {
+        let mut message = format!("{} risk", result.risk_level);
+        if !result.risk_reasons.is_empty() {
+            message.push_str(&format!(": {}", result.risk_reasons.join("; ")));
+        }
+        let _ = writeln!(out, "      <failure message=\"{}\" type=\"high_risk\">{}</failure>", escape(&message), escape(&report));
+    } else {
+        let _ = writeln!(out, "      <system-out>{}</system-out>", escape(&report));
+    }
+    let _ = writeln!(out, "    </testcase>");
+    let _ = writeln!(out, "  </testsuite>");
+    let _ = writeln!(out, "</testsuites>");
+    out
+}
+
+/// `text` safe in XML content and attribute values. Control characters XML
+/// 1.0 cannot carry at all are dropped.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::tests::result;
+
+    #[test]
+    fn high_risk_is_a_failure() {
+        let xml = junit(&result());
+        assert!(xml.contains("<testsuite name=\"senior\" tests=\"1\" failures=\"1\""), "got:\n{}", xml);
+        assert!(xml.contains("<failure message=\"high risk: public API changed\" type=\"high_risk\">HIGH risk"), "got:\n{}", xml);
+        assert!(!xml.contains("<system-out>"));
+    }
+
+    #[test]
+    fn lower_risk_passes_with_the_report_as_output() {
+        let result = AnalysisResult { risk_level: "med".into(), ..result() };
+        let xml = junit(&result);
+        assert!(xml.contains("failures=\"0\""));
+        assert!(xml.contains("<system-out>MED risk"), "got:\n{}", xml);
+        assert!(!xml.contains("<failure"));
+    }
+
+    #[test]
+    fn markup_and_control_characters_are_escaped() {
+        assert_eq!(escape("<a href=\"x\">&'\u{1b}[0m\n"), "&lt;a href=&quot;x&quot;&gt;&amp;&apos;[0m\n");
+    }
+}
@@ -0,0 +1,91 @@
+use crate::protocol::AnalysisResult;
+use std::fmt::Write;
+
+/// A report to paste into a pull request: a heading with the risk, the
+/// summary, then reasons, files and symbols as tables, and suggested actions.
+/// Sections with nothing in them are left out.
+pub fn markdown(result: &AnalysisResult) -> String {
+    let mut out = String::new();
+    let level = result.risk_level.trim();
+    let mut title = level.to_lowercase();
+    if let Some(first) = title.get_mut(..1) {
+        first.make_ascii_uppercase();
+    }
+    let _ = writeln!(out, "### Senior review: {} risk\n", title);
+    for line in &result.summary {
+        let _ = writeln!(out, "{}  ", inline(line));
+    }
+    let cached = if result.cached { ", from cache" } else { "" };
+    let _ = writeln!(out, "_Confidence {:.2}{}_", result.confidence, cached);
+    if !result.risk_reasons.is_empty() {
+        let _ = writeln!(out, "\n#### Why\n");
+        for reason in &result.risk_reasons {
+            let _ = writeln!(out, "- {}", inline(reason));
+        }
+    }
+    if !result.impacted_files.is_empty() {
+        let _ = writeln!(out, "\n#### Impacted files\n\n| File | Score | Change | Why |\n| --- | ---: | --- | --- |");
+        for file in &result.impacted_files {
+            let change = if file.change.is_empty() { "caller" } else { file.change.as_str() };
+            let _ = writeln!(out, "| {} | {:.2} | {} | {} |", code(&file.path), file.score, change, cell(&file.why.join("; ")));
+        }
+    }
+    if !result.impacted_symbols.is_empty() {
+        let _ = writeln!(out, "\n#### Symbols\n\n| Symbol | Kind | Change | File |\n| --- | --- | --- | --- |");
+        for symbol in &result.impacted_symbols {
+            let _ = writeln!(out, "| {} | {} | {} | {} |", code(&symbol.name), symbol.kind, symbol.change, code(&symbol.file));
+        }
+    }
+    if !result.suggested_actions.is_empty() {
+        let _ = writeln!(out, "\n#### Suggested actions\n");
+        for action in &result.suggested_actions {
+            let _ = writeln!(out, "- **{}**: {}", inline(&action.label), inline(&action.explanation));
+        }
+    }
+    out
+}
+
+/// `text` on one line, so a model's stray newline cannot break a list item.
+fn inline(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// `text` fit for a table cell: one line, with pipes escaped.
+fn cell(text: &str) -> String {
+    inline(text).replace('|', "\\|")
+}
+
+/// `text` as inline code in a table cell, fenced with enough backticks to
+/// hold any it contains.
+fn code(text: &str) -> String {
+    let mut fence = "`".to_string();
+    while text.contains(fence.as_str()) {
+        fence.push('`');
+    }
+    let pad = if text.starts_with('`') || text.ends_with('`') { " " } else { "" };
+    format!("{}{}{}{}{}", fence, pad, cell(text), pad, fence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::tests::result;
+
+    #[test]
+    fn markdown_has_a_heading_and_tables() {
+        let md = markdown(&result());
+        assert!(md.starts_with("### Senior review: High risk\n\nrenamed the cache  \n_Confidence 0.80, from cache_\n"), "got:\n{}", md);
+        assert!(md.contains("\n#### Why\n\n- public API changed\n"));
+        assert!(md.contains("| File | Score | Change | Why |\n| --- | ---: | --- | --- |\n| `src/cache.rs` | 0.90 | modified | modified |\n"), "got:\n{}", md);
+        assert!(md.contains("| `get_cache` | function | modified | `src/cache.rs` |\n"));
+        assert!(md.contains("\n#### Suggested actions\n\n- **Update callers**: Renamed\n"));
+    }
+
+    #[test]
+    fn cells_cannot_break_the_table() {
+        assert_eq!(cell("a | b\nc"), "a \\| b c");
+        assert_eq!(code("x`y"), "``x`y``");
+        let bare = AnalysisResult { risk_level: "med".into(), ..Default::default() };
+        assert_eq!(markdown(&bare), "### Senior review: Med risk\n\n_Confidence 0.00_\n");
+    }
+}
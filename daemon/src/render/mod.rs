@@ -0,0 +1,55 @@
+//! An `AnalysisResult` as a report for people or tools: terminal text,
+//! Markdown for pull requests, SARIF for code-scanning viewers and JUnit XML
+//! for CI.
+
+mod junit;
+mod markdown;
+mod sarif;
+mod text;
+
+pub use junit::junit;
+pub use markdown::markdown;
+pub use sarif::sarif;
+pub use text::{clean, text, Style};
+
+use crate::protocol::{AnalysisResult, RenderFormat};
+
+/// `result` in `format`; text comes without colour.
+pub fn render(result: &AnalysisResult, format: RenderFormat) -> String {
+    match format {
+        RenderFormat::Text => text(result, Style::PLAIN),
+        RenderFormat::Markdown => markdown(result),
+        RenderFormat::Sarif => sarif(result),
+        RenderFormat::Junit => junit(result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::{AnalysisResult, ImpactedFile, ImpactedSymbol, SuggestedAction};
+
+    /// A high-risk result with one entry in every section.
+    pub fn result() -> AnalysisResult {
+        AnalysisResult {
+            summary: vec!["renamed the cache".to_string()],
+            risk_level: "high".to_string(),
+            risk_reasons: vec!["public API changed".to_string()],
+            impacted_files: vec![ImpactedFile {
+                path: "src/cache.rs".to_string(),
+                score: 0.9,
+                why: vec!["modified".to_string()],
+                change: "modified".to_string(),
+            }],
+            impacted_symbols: vec![ImpactedSymbol {
+                name: "get_cache".to_string(),
+                kind: "function".to_string(),
+                file: "src/cache.rs".to_string(),
+                score: 0.8,
+                change: "modified".to_string(),
+            }],
+            suggested_actions: vec![SuggestedAction { label: "Update callers".into(), explanation: "Renamed".into() }],
+            confidence: 0.8,
+            cached: true,
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::protocol::{risk_rank, AnalysisResult};
+use serde_json::{json, Value};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const FILE_RULE: &str = "senior/impacted-file";
+const SYMBOL_RULE: &str = "senior/impacted-symbol";
+
+/// A SARIF 2.1.0 log with one run: a result per impacted file and per
+/// impacted symbol, all at the level of the overall risk (`error` for high,
+/// `warning` for med, `note` otherwise) and ranked by their score. The
+/// summary, reasons and suggested actions go in the run's properties.
+pub fn sarif(result: &AnalysisResult) -> String {
+    let level = match risk_rank(&result.risk_level) {
+        2 => "error",
+        1 => "warning",
+        _ => "note",
+    };
+    let mut results: Vec<Value> = Vec::new();
+    for file in &result.impacted_files {
+        let change = if file.change.is_empty() { "caller" } else { file.change.as_str() };
+        let mut text = format!("{} file in a {} risk change", change, result.risk_level);
+        if !file.why.is_empty() {
+            text.push_str(&format!(" ({})", file.why.join("; ")));
+        }
+        results.push(json!({
+            "ruleId": FILE_RULE,
+            "level": level,
+            "rank": rank(file.score),
+            "message": { "text": text },
+            "locations": [{ "physicalLocation": { "artifactLocation": { "uri": uri(&file.path) } } }],
+        }));
+    }
+    for symbol in &result.impacted_symbols {
+        results.push(json!({
+            "ruleId": SYMBOL_RULE,
+            "level": level,
+            "rank": rank(symbol.score),
+            "message": { "text": format!("{} {} `{}` in a {} risk change", symbol.change, symbol.kind, symbol.name, result.risk_level) },
+            "locations": [{
+                "physicalLocation": { "artifactLocation": { "uri": uri(&symbol.file) } },
+                "logicalLocations": [{ "name": symbol.name, "kind": logical_kind(&symbol.kind) }],
+            }],
+        }));
+    }
+    let log = json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": { "driver": {
+                "name": "senior",
+                "version": env!("CARGO_PKG_VERSION"),
+                "rules": [
+                    {
+                        "id": FILE_RULE,
+                        "shortDescription": { "text": "File affected by the change" },
+                        "fullDescription": { "text": "A changed file, or one that depends on what changed." },
+                    },
+                    {
+                        "id": SYMBOL_RULE,
+                        "shortDescription": { "text": "Symbol affected by the change" },
+                        "fullDescription": { "text": "A function, method, type or constant the change adds, modifies or deletes." },
+                    },
+                ],
+            } },
+            "results": results,
+            "properties": {
+                "riskLevel": result.risk_level,
+                "summary": result.summary,
+                "riskReasons": result.risk_reasons,
+                "suggestedActions": result.suggested_actions,
+                "confidence": result.confidence,
+            },
+        }],
+    });
+    let mut out = serde_json::to_string_pretty(&log).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+/// A 0–1 score as SARIF's 0–100 rank.
+fn rank(score: f32) -> f64 {
+    (f64::from(score.clamp(0.0, 1.0)) * 1000.0).round() / 10.0
+}
+
+/// A repo-relative path as a relative URI reference, percent-encoding all
+/// but unreserved characters and `/`.
+fn uri(path: &str) -> String {
+    let mut out = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// SARIF's name for a symbol kind.
+fn logical_kind(kind: &str) -> &'static str {
+    match kind {
+        "method" => "member",
+        "constant" => "variable",
+        "type" => "type",
+        _ => "function",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::tests::result;
+
+    #[test]
+    fn sarif_has_a_result_per_file_and_symbol() {
+        let log: Value = serde_json::from_str(&sarif(&result())).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "senior");
+        assert_eq!(run["properties"]["riskLevel"], "high");
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["ruleId"], FILE_RULE);
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(results[0]["rank"], 90.0);
+        assert_eq!(results[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"], "src/cache.rs");
+        assert_eq!(results[0]["message"]["text"], "modified file in a high risk change (modified)");
+        assert_eq!(results[1]["ruleId"], SYMBOL_RULE);
+        assert_eq!(results[1]["message"]["text"], "modified function `get_cache` in a high risk change");
+        assert_eq!(results[1]["locations"][0]["logicalLocations"][0]["name"], "get_cache");
+        assert_eq!(results[1]["locations"][0]["logicalLocations"][0]["kind"], "function");
+    }
+
+    #[test]
+    fn levels_follow_the_overall_risk() {
+        let level = |risk: &str| {
+            let result = AnalysisResult { risk_level: risk.into(), ..result() };
+            let log: Value = serde_json::from_str(&sarif(&result)).unwrap();
+            log["runs"][0]["results"][0]["level"].as_str().unwrap().to_string()
+        };
+        assert_eq!(level("med"), "warning");
+        assert_eq!(level("low"), "note");
+    }
+
+    #[test]
+    fn paths_become_uri_references() {
+        assert_eq!(uri("docs/read me#1.md"), "docs/read%20me%231.md");
+        assert_eq!(uri("src/ünï.rs"), "src/%C3%BCn%C3%AF.rs");
+    }
+}
@@ -0,0 +1,143 @@
+use crate::protocol::{risk_rank, AnalysisResult};
+use std::fmt::Write;
+
+/// ANSI styling, or none at all when `color` is off.
+#[derive(Debug, Clone, Copy)]
+pub struct Style {
+    pub color: bool,
+}
+
+impl Style {
+    pub const PLAIN: Style = Style { color: false };
+
+    pub fn paint(self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn bold(self, text: &str) -> String {
+        self.paint("1", text)
+    }
+
+    pub fn dim(self, text: &str) -> String {
+        self.paint("2", text)
+    }
+
+    /// `level` upper-cased, red for high, yellow for med, green otherwise.
+    pub fn risk(self, level: &str) -> String {
+        let code = match risk_rank(level) {
+            2 => "1;31",
+            1 => "1;33",
+            _ => "1;32",
+        };
+        self.paint(code, &clean(level.trim()).to_uppercase())
+    }
+}
+
+/// An analysis as a short report: risk, summary, reasons, files, symbols and
+/// suggested actions, each section left out when empty. Everything the model
+/// wrote goes through `clean`, so it cannot move the cursor or recolour the
+/// terminal.
+pub fn text(result: &AnalysisResult, style: Style) -> String {
+    let mut out = String::new();
+    let mut notes = vec![format!("confidence {:.2}", result.confidence)];
+    if result.cached {
+        notes.push("cached".to_string());
+    }
+    let _ = writeln!(out, "{} risk  {}", style.risk(&result.risk_level), style.dim(&format!("({})", notes.join(", "))));
+    for line in &result.summary {
+        let _ = writeln!(out, "  {}", clean(line));
+    }
+    if !result.risk_reasons.is_empty() {
+        let _ = writeln!(out, "\n{}", style.bold("Why"));
+        for reason in &result.risk_reasons {
+            let _ = writeln!(out, "  - {}", clean(reason));
+        }
+    }
+    if !result.impacted_files.is_empty() {
+        let _ = writeln!(out, "\n{}", style.bold("Impacted files"));
+        let paths: Vec<String> = result.impacted_files.iter().map(|f| clean(&f.path)).collect();
+        let width = paths.iter().map(|p| p.chars().count()).max().unwrap_or(0);
+        for (file, path) in result.impacted_files.iter().zip(&paths) {
+            let change = if file.change.is_empty() { "caller".to_string() } else { clean(&file.change) };
+            let why = if file.why.is_empty() {
+                String::new()
+            } else {
+                format!("  {}", style.dim(&clean(&file.why.join("; "))))
+            };
+            let score = style.dim(&format!("{:.2}", file.score));
+            let row = format!("  {:<width$}  {}  {:<8}{}", path, score, change, why);
+            let _ = writeln!(out, "{}", row.trim_end());
+        }
+    }
+    if !result.impacted_symbols.is_empty() {
+        let _ = writeln!(out, "\n{}", style.bold("Symbols"));
+        for symbol in &result.impacted_symbols {
+            let (change, kind, name) = (clean(&symbol.change), clean(&symbol.kind), clean(&symbol.name));
+            let _ = writeln!(out, "  {} {} {} {}", change, kind, name, style.dim(&format!("({})", clean(&symbol.file))));
+        }
+    }
+    if !result.suggested_actions.is_empty() {
+        let _ = writeln!(out, "\n{}", style.bold("Suggested"));
+        for action in &result.suggested_actions {
+            let _ = writeln!(out, "  → {}: {}", clean(&action.label), clean(&action.explanation));
+        }
+    }
+    out
+}
+
+/// `text` on one line: tabs and line breaks become spaces and other control
+/// characters, escape sequences included, are dropped. For anything a model
+/// wrote that goes to a terminal.
+pub fn clean(text: &str) -> String {
+    text.chars()
+        .filter_map(|c| match c {
+            '\t' | '\n' | '\r' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ImpactedFile;
+    use crate::render::tests::result;
+
+    #[test]
+    fn text_lists_every_nonempty_section() {
+        let text = text(&result(), Style::PLAIN);
+        assert!(text.starts_with("HIGH risk  (confidence 0.80, cached)\n  renamed the cache\n"), "got:\n{}", text);
+        assert!(text.contains("\nWhy\n  - public API changed\n"));
+        assert!(text.contains("\nImpacted files\n  src/cache.rs  0.90  modified  modified\n"));
+        assert!(text.contains("\nSymbols\n  modified function get_cache (src/cache.rs)\n"));
+        assert!(text.contains("\nSuggested\n  → Update callers: Renamed\n"));
+        assert!(!text.contains('\x1b'));
+
+        let bare = AnalysisResult { risk_level: "low".into(), ..Default::default() };
+        assert_eq!(super::text(&bare, Style::PLAIN), "LOW risk  (confidence 0.00)\n");
+    }
+
+    #[test]
+    fn model_text_cannot_restyle_the_terminal_or_misalign_files() {
+        let mut result = result();
+        result.summary = vec!["fine\x1b[2J\x1b[0;32m really\nfine".into()];
+        result.impacted_files[0].path = "src/café.rs".into();
+        result.impacted_files.push(ImpactedFile { path: "src/b.rs".into(), score: 0.5, why: vec![], change: "added".into() });
+        let text = text(&result, Style::PLAIN);
+        assert!(!text.contains('\x1b'), "got: {:?}", text);
+        assert!(text.contains("\n  fine[2J[0;32m really fine\n"), "got:\n{}", text);
+        assert!(text.contains("\n  src/café.rs  0.90  modified  modified\n  src/b.rs     0.50  added\n"), "got:\n{}", text);
+    }
+
+    #[test]
+    fn color_marks_the_risk_level() {
+        let text = text(&result(), Style { color: true });
+        assert!(text.starts_with("\x1b[1;31mHIGH\x1b[0m risk"), "got: {:?}", text);
+        assert_eq!(Style { color: true }.risk("Medium"), "\x1b[1;33mMEDIUM\x1b[0m");
+    }
+}
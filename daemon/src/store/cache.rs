@@ -0,0 +1,127 @@
+use super::AuditLog;
+use crate::protocol::AnalysisResult;
+use anyhow::{anyhow, Result};
+use rusqlite::{params, OptionalExtension};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long a cached analysis is reused unless `analysis.cache_ttl_secs` or
+/// `SENIOR_CACHE_TTL_SECS` says otherwise.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A `SENIOR_CACHE_TTL_SECS` value. Zero turns the cache off.
+pub fn parse_ttl(raw: &str) -> Result<Duration> {
+    raw.trim()
+        .parse()
+        .map(Duration::from_secs)
+        .map_err(|_| anyhow!("SENIOR_CACHE_TTL_SECS must be a whole number of seconds, got '{}'", raw))
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Entries created at or before this Unix time are older than `ttl`. A TTL
+/// too long to subtract keeps everything rather than wrapping around.
+fn cutoff(now: i64, ttl: Duration) -> i64 {
+    now.saturating_sub(i64::try_from(ttl.as_secs()).unwrap_or(i64::MAX))
+}
+
+impl AuditLog {
+    /// The result stored under `key` if it is younger than `ttl`, marked as
+    /// `cached`.
+    pub fn cached_result(&self, key: &str, ttl: Duration) -> Result<Option<AnalysisResult>> {
+        let conn = self.conn.lock().expect("audit mutex poisoned");
+        let raw: Option<String> = conn
+            .query_row(
+                "SELECT result FROM analysis_cache WHERE key = ?1 AND created_at > ?2",
+                params![key, cutoff(now_secs(), ttl)],
+                |r| r.get(0),
+            )
+            .optional()?;
+        let Some(raw) = raw else { return Ok(None) };
+        let mut result: AnalysisResult = serde_json::from_str(&raw)?;
+        result.cached = true;
+        Ok(Some(result))
+    }
+
+    /// Store `result` under `key`, replacing what was there, and drop entries
+    /// older than `ttl` while at it.
+    pub fn cache_result(&self, key: &str, result: &AnalysisResult, ttl: Duration) -> Result<()> {
+        let raw = serde_json::to_string(&AnalysisResult { cached: false, ..result.clone() })?;
+        let now = now_secs();
+        let conn = self.conn.lock().expect("audit mutex poisoned");
+        conn.execute(
+            "DELETE FROM analysis_cache WHERE created_at <= ?1",
+            params![cutoff(now, ttl)],
+        )?;
+        conn.execute(
+            "INSERT OR REPLACE INTO analysis_cache (key, result, created_at) VALUES (?1, ?2, ?3)",
+            params![key, raw, now],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(86_400);
+
+    fn result(summary: &str) -> AnalysisResult {
+        AnalysisResult { summary: vec![summary.to_string()], risk_level: "med".into(), confidence: 0.85, ..Default::default() }
+    }
+
+    #[test]
+    fn stored_result_comes_back_marked_cached() {
+        let db = AuditLog::open(":memory:").unwrap();
+        assert!(db.cached_result("k", DAY).unwrap().is_none());
+
+        db.cache_result("k", &result("first"), DAY).unwrap();
+        let hit = db.cached_result("k", DAY).unwrap().unwrap();
+        assert!(hit.cached);
+        assert_eq!(hit.summary, vec!["first"]);
+        assert_eq!(hit.risk_level, "med");
+    }
+
+    #[test]
+    fn newer_result_replaces_older_one() {
+        let db = AuditLog::open(":memory:").unwrap();
+        db.cache_result("k", &result("first"), DAY).unwrap();
+        db.cache_result("k", &result("second"), DAY).unwrap();
+        assert_eq!(db.cached_result("k", DAY).unwrap().unwrap().summary, vec!["second"]);
+    }
+
+    #[test]
+    fn parse_ttl_accepts_zero_and_rejects_junk() {
+        assert_eq!(parse_ttl(" 60 ").unwrap(), Duration::from_secs(60));
+        assert_eq!(parse_ttl("0").unwrap(), Duration::ZERO);
+        assert!(parse_ttl("-1").is_err());
+        assert!(parse_ttl("a day").is_err());
+    }
+
+    #[test]
+    fn huge_ttl_keeps_results_instead_of_overflowing() {
+        let db = AuditLog::open(":memory:").unwrap();
+        db.cache_result("k", &result("kept"), Duration::MAX).unwrap();
+        db.cache_result("other", &result("also kept"), Duration::from_secs(u64::MAX)).unwrap();
+        assert_eq!(db.cached_result("k", Duration::MAX).unwrap().unwrap().summary, vec!["kept"]);
+        assert_eq!(cutoff(0, Duration::MAX), -i64::MAX);
+    }
+
+    #[test]
+    fn expired_results_are_missed_and_pruned() {
+        let db = AuditLog::open(":memory:").unwrap();
+        db.cache_result("old", &result("stale"), DAY).unwrap();
+        assert!(db.cached_result("old", Duration::ZERO).unwrap().is_none());
+
+        db.cache_result("new", &result("fresh"), Duration::ZERO).unwrap();
+        let rows: i64 = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM analysis_cache WHERE key = 'old'", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(rows, 0);
+    }
+}
@@ -1,22 +1,134 @@
-use anyhow::Result;
-use rusqlite::{Connection, params};
+use anyhow::{anyhow, Result};
+use rusqlite::{Connection, TransactionBehavior, params};
 use std::sync::Mutex;
 
+/// The audit log as the baseline daemon created it.
+const EVENTS_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        ts TEXT NOT NULL DEFAULT (datetime('now')),
+        event_type TEXT NOT NULL,
+        payload TEXT NOT NULL
+    );
+";
+
+/// Forward migrations: `MIGRATIONS[n]` takes a database at `user_version` n to
+/// n + 1. A shipped step never changes; a schema change is a new step at the end.
+const MIGRATIONS: &[fn(&Connection) -> rusqlite::Result<()>] = &[adopt_unversioned, index_analysis_files];
+
+/// Schema version this binary writes.
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// The tables version 1 creates, as it shipped: the workspace index
+/// (`store::index`), finished analyses keyed by a hash of what was reviewed
+/// and by what model (`store::cache`), and every finished analysis with the
+/// paths it touched (`store::history`).
+const V1_TABLES: &str = "
+    CREATE TABLE IF NOT EXISTS index_files (
+        repo TEXT NOT NULL,
+        path TEXT NOT NULL,
+        hash TEXT NOT NULL,
+        mtime_ns INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        PRIMARY KEY (repo, path)
+    );
+    CREATE TABLE IF NOT EXISTS index_refs (
+        repo TEXT NOT NULL,
+        path TEXT NOT NULL,
+        name TEXT NOT NULL,
+        count INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS index_refs_by_name ON index_refs (repo, name);
+    CREATE INDEX IF NOT EXISTS index_refs_by_path ON index_refs (repo, path);
+    CREATE TABLE IF NOT EXISTS index_imports (
+        repo TEXT NOT NULL,
+        path TEXT NOT NULL,
+        spec TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS index_imports_by_path ON index_imports (repo, path);
+
+    CREATE TABLE IF NOT EXISTS analysis_cache (
+        key TEXT PRIMARY KEY,
+        result TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS analyses (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        ts INTEGER NOT NULL,
+        repo TEXT NOT NULL,
+        branch TEXT NOT NULL,
+        head TEXT NOT NULL,
+        trigger TEXT NOT NULL,
+        diff_hash TEXT NOT NULL,
+        files TEXT NOT NULL,
+        model TEXT NOT NULL,
+        latency_ms INTEGER NOT NULL,
+        result TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS analyses_by_repo ON analyses (repo, ts);
+    CREATE TABLE IF NOT EXISTS analysis_files (
+        analysis_id INTEGER NOT NULL,
+        path TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS analysis_files_by_path ON analysis_files (path);
+";
+
+/// Version 1. Databases from before versioning are all at `user_version` 0 but
+/// may hold any subset of the tables, so every table is created if missing.
+/// The workspace index is emptied: files indexed before `index_imports`
+/// existed are never re-read while unchanged, so their imports would stay
+/// missing. The next analysis of each workspace rebuilds it.
+fn adopt_unversioned(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(EVENTS_SCHEMA)?;
+    conn.execute_batch(V1_TABLES)?;
+    conn.execute_batch("DELETE FROM index_files; DELETE FROM index_refs; DELETE FROM index_imports;")
+}
+
+/// Version 2. `record_analysis` prunes old analyses, which deletes their
+/// `analysis_files` rows by analysis id.
+fn index_analysis_files(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS analysis_files_by_analysis ON analysis_files (analysis_id);")
+}
+
+/// Bring the database at `db_path` up to `SCHEMA_VERSION`, one step and one
+/// transaction at a time. Refuses a database written by a newer binary.
+fn migrate(conn: &mut Connection, db_path: &str) -> Result<()> {
+    loop {
+        // IMMEDIATE takes the write lock before reading the version, so two
+        // daemons opening the same file cannot both run a step.
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let version: u32 = tx.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(anyhow!(
+                "{} is at schema version {}, but this senior-daemon only knows up to version {}; \
+                 upgrade senior-daemon or point it at another database",
+                db_path,
+                version,
+                SCHEMA_VERSION
+            ));
+        }
+        if version == SCHEMA_VERSION {
+            return Ok(());
+        }
+        MIGRATIONS[version as usize](&tx)
+            .map_err(|e| anyhow!("migrating {} to schema version {} failed: {}", db_path, version + 1, e))?;
+        tx.pragma_update(None, "user_version", version + 1)?;
+        tx.commit()?;
+        tracing::info!("migrated {} to schema version {}", db_path, version + 1);
+    }
+}
+
 pub struct AuditLog {
     pub(crate) conn: Mutex<Connection>,
 }
 
 impl AuditLog {
     pub fn open(db_path: &str) -> Result<Self> {
-        let conn = Connection::open(db_path)?;
-        conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS events (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                ts TEXT NOT NULL DEFAULT (datetime('now')),
-                event_type TEXT NOT NULL,
-                payload TEXT NOT NULL
-            );"
-        )?;
+        let mut conn = Connection::open(db_path)?;
+        // Several daemons (e.g. one per test) may share the file; wait rather than fail.
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        migrate(&mut conn, db_path)?;
         Ok(Self { conn: Mutex::new(conn) })
     }
 
@@ -33,6 +145,7 @@ impl AuditLog {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::TempDir;
 
     fn count_rows(log: &AuditLog) -> i64 {
         log.conn
@@ -90,6 +203,161 @@ mod tests {
         assert_eq!(ids, vec![1, 2]);
     }
 
+    /// The reference index as it shipped before `index_imports` was added.
+    const INDEX_WITHOUT_IMPORTS: &str = "
+        CREATE TABLE index_files (
+            repo TEXT NOT NULL, path TEXT NOT NULL, hash TEXT NOT NULL,
+            mtime_ns INTEGER NOT NULL, size INTEGER NOT NULL,
+            PRIMARY KEY (repo, path)
+        );
+        CREATE TABLE index_refs (repo TEXT NOT NULL, path TEXT NOT NULL, name TEXT NOT NULL, count INTEGER NOT NULL);
+    ";
+
+    /// The workspace index once `index_imports` was added.
+    const INDEX_WITH_IMPORTS: &str = "
+        CREATE TABLE index_files (
+            repo TEXT NOT NULL, path TEXT NOT NULL, hash TEXT NOT NULL,
+            mtime_ns INTEGER NOT NULL, size INTEGER NOT NULL,
+            PRIMARY KEY (repo, path)
+        );
+        CREATE TABLE index_refs (repo TEXT NOT NULL, path TEXT NOT NULL, name TEXT NOT NULL, count INTEGER NOT NULL);
+        CREATE INDEX index_refs_by_name ON index_refs (repo, name);
+        CREATE INDEX index_refs_by_path ON index_refs (repo, path);
+        CREATE TABLE index_imports (repo TEXT NOT NULL, path TEXT NOT NULL, spec TEXT NOT NULL);
+        CREATE INDEX index_imports_by_path ON index_imports (repo, path);
+    ";
+
+    /// The analysis cache as it first shipped.
+    const CACHE: &str = "
+        CREATE TABLE analysis_cache (key TEXT PRIMARY KEY, result TEXT NOT NULL, created_at INTEGER NOT NULL);
+    ";
+
+    /// The analysis history as it first shipped.
+    const HISTORY: &str = "
+        CREATE TABLE analyses (
+            id INTEGER PRIMARY KEY AUTOINCREMENT, ts INTEGER NOT NULL, repo TEXT NOT NULL,
+            branch TEXT NOT NULL, head TEXT NOT NULL, trigger TEXT NOT NULL, diff_hash TEXT NOT NULL,
+            files TEXT NOT NULL, model TEXT NOT NULL, latency_ms INTEGER NOT NULL, result TEXT NOT NULL
+        );
+        CREATE INDEX analyses_by_repo ON analyses (repo, ts);
+        CREATE TABLE analysis_files (analysis_id INTEGER NOT NULL, path TEXT NOT NULL);
+        CREATE INDEX analysis_files_by_path ON analysis_files (path);
+    ";
+
+    /// Every schema a released daemon has written, oldest first, all at
+    /// `user_version` 0. Frozen copies, so changing the live schema cannot
+    /// change what is tested here.
+    fn unversioned_schemas() -> Vec<(&'static str, String)> {
+        vec![
+            ("events only", EVENTS_SCHEMA.to_string()),
+            ("reference index", format!("{}{}", EVENTS_SCHEMA, INDEX_WITHOUT_IMPORTS)),
+            ("import graph", format!("{}{}", EVENTS_SCHEMA, INDEX_WITH_IMPORTS)),
+            ("analysis cache", format!("{}{}{}", EVENTS_SCHEMA, INDEX_WITH_IMPORTS, CACHE)),
+            ("analysis history", format!("{}{}{}{}", EVENTS_SCHEMA, INDEX_WITH_IMPORTS, CACHE, HISTORY)),
+        ]
+    }
+
+    /// A database path in a directory of its own, removed with the `TempDir`.
+    fn temp_db(name: &str) -> (TempDir, String) {
+        let dir = TempDir::new(&format!("migrate-{}", name));
+        let path = dir.root.join("senior.db").to_str().unwrap().to_string();
+        (dir, path)
+    }
+
+    fn user_version(log: &AuditLog) -> u32 {
+        log.conn.lock().unwrap().query_row("PRAGMA user_version", [], |r| r.get(0)).unwrap()
+    }
+
+    fn count(log: &AuditLog, table: &str) -> i64 {
+        log.conn
+            .lock()
+            .unwrap()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |r| r.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_migrates_every_unversioned_schema() {
+        for (i, (name, schema)) in unversioned_schemas().into_iter().enumerate() {
+            let (_dir, path) = temp_db(&format!("v0-{}", i));
+            {
+                let conn = Connection::open(&path).unwrap();
+                conn.execute_batch(&schema).unwrap();
+                conn.execute("INSERT INTO events (event_type, payload) VALUES ('ping', '{}')", []).unwrap();
+                if schema.contains("index_files") {
+                    conn.execute("INSERT INTO index_files VALUES ('/w', 'a.rs', 'h', 1, 2)", []).unwrap();
+                    conn.execute("INSERT INTO index_refs VALUES ('/w', 'a.rs', 'name', 1)", []).unwrap();
+                }
+            }
+
+            let log = AuditLog::open(&path).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(user_version(&log), SCHEMA_VERSION, "{}", name);
+            assert_eq!(count(&log, "events"), 1, "{}: events are kept", name);
+            for table in ["index_files", "index_refs", "index_imports"] {
+                assert_eq!(count(&log, table), 0, "{}: {} is rebuilt from scratch", name, table);
+            }
+            for table in ["analysis_cache", "analyses", "analysis_files"] {
+                assert_eq!(count(&log, table), 0, "{}: {} exists", name, table);
+            }
+        }
+    }
+
+    #[test]
+    fn test_migrates_version_1_keeping_history() {
+        let (_dir, path) = temp_db("v1");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(&format!("{}{}{}{}", EVENTS_SCHEMA, INDEX_WITH_IMPORTS, CACHE, HISTORY)).unwrap();
+            conn.execute_batch(
+                "INSERT INTO analyses (ts, repo, branch, head, trigger, diff_hash, files, model, latency_ms, result)
+                     VALUES (1, '/w', 'main', 'abc', 'save', 'h', '[\"a.rs\"]', '', 5, '{}');
+                 INSERT INTO analysis_files VALUES (1, 'a.rs');
+                 INSERT INTO index_refs VALUES ('/w', 'a.rs', 'name', 1);
+                 PRAGMA user_version = 1;",
+            )
+            .unwrap();
+        }
+
+        let log = AuditLog::open(&path).unwrap();
+        assert_eq!(user_version(&log), SCHEMA_VERSION);
+        assert_eq!(count(&log, "analyses"), 1);
+        assert_eq!(count(&log, "analysis_files"), 1);
+        assert_eq!(count(&log, "index_refs"), 1, "only the step from version 0 empties the index");
+        let indexed: i64 = log
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'analysis_files_by_analysis'", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(indexed, 1);
+    }
+
+    #[test]
+    fn test_reopening_current_schema_changes_nothing() {
+        let (_dir, path) = temp_db("current");
+        AuditLog::open(&path).unwrap().log("e", "p").unwrap();
+        {
+            let log = AuditLog::open(&path).unwrap();
+            log.conn.lock().unwrap().execute("INSERT INTO index_refs VALUES ('/w', 'a.rs', 'name', 1)", []).unwrap();
+        }
+        let log = AuditLog::open(&path).unwrap();
+        assert_eq!(user_version(&log), SCHEMA_VERSION);
+        assert_eq!(count(&log, "events"), 1);
+        assert_eq!(count(&log, "index_refs"), 1, "only the first migration empties the index");
+    }
+
+    #[test]
+    fn test_newer_database_is_refused() {
+        let (_dir, path) = temp_db("newer");
+        Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+        let err = AuditLog::open(&path).err().expect("a newer schema must not open").to_string();
+        assert!(err.contains(&format!("schema version {}", SCHEMA_VERSION + 1)), "got: {}", err);
+        assert!(err.contains("upgrade senior-daemon"), "got: {}", err);
+    }
+
     #[test]
     fn test_audit_log_two_in_memory_dbs_are_isolated() {
         // Two separate :memory: connections should not share data.
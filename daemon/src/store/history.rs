@@ -0,0 +1,215 @@
+use super::AuditLog;
+use crate::protocol::{AnalysisResult, HistoryEntry, HistoryPayload};
+use anyhow::Result;
+use rusqlite::params;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Most entries one `history` query returns, whatever limit it asks for.
+pub const MAX_HISTORY_ENTRIES: usize = 500;
+
+/// Analyses kept per workspace; recording one more drops the oldest.
+pub const MAX_ANALYSES_KEPT: usize = 1_000;
+
+/// A finished analysis about to be recorded; `record_analysis` adds the id
+/// and timestamp.
+#[derive(Debug, Default)]
+pub struct AnalysisRecord {
+    pub repo: String,
+    pub branch: String,
+    pub head: String,
+    pub trigger: String,
+    pub diff_hash: String,
+    pub files: Vec<String>,
+    pub model: String,
+    pub latency_ms: u64,
+    pub result: AnalysisResult,
+}
+
+/// Workspace roots are stored and matched without a trailing slash.
+fn repo_key(repo: &str) -> &str {
+    match repo.trim_end_matches('/') {
+        "" => repo,
+        trimmed => trimmed,
+    }
+}
+
+impl AuditLog {
+    /// Store `record` and return its id, dropping the workspace's analyses
+    /// beyond the newest `MAX_ANALYSES_KEPT`.
+    pub fn record_analysis(&self, record: &AnalysisRecord) -> Result<i64> {
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+        let mut conn = self.conn.lock().expect("audit mutex poisoned");
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO analyses
+                (ts, repo, branch, head, trigger, diff_hash, files, model, latency_ms, result)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                ts,
+                repo_key(&record.repo),
+                record.branch,
+                record.head,
+                record.trigger,
+                record.diff_hash,
+                serde_json::to_string(&record.files)?,
+                record.model,
+                record.latency_ms as i64,
+                serde_json::to_string(&record.result)?,
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        {
+            let mut insert = tx.prepare_cached("INSERT INTO analysis_files (analysis_id, path) VALUES (?1, ?2)")?;
+            for path in &record.files {
+                insert.execute(params![id, path])?;
+            }
+        }
+        let stale = "SELECT id FROM analyses WHERE repo = ?1 ORDER BY id DESC LIMIT -1 OFFSET ?2";
+        let keep = params![repo_key(&record.repo), MAX_ANALYSES_KEPT as i64];
+        tx.execute(&format!("DELETE FROM analysis_files WHERE analysis_id IN ({})", stale), keep)?;
+        tx.execute(&format!("DELETE FROM analyses WHERE id IN ({})", stale), keep)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Recorded analyses matching `query`, newest first. A relative `file`
+    /// matches a touched path exactly; an absolute one, the analysis's
+    /// workspace root joined with a touched path.
+    pub fn history(&self, query: &HistoryPayload) -> Result<Vec<HistoryEntry>> {
+        let conn = self.conn.lock().expect("audit mutex poisoned");
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, ts, repo, branch, head, trigger, diff_hash, files, model, latency_ms, result
+             FROM analyses
+             WHERE (?1 IS NULL OR repo = ?1)
+               AND (?2 IS NULL OR EXISTS (
+                    SELECT 1 FROM analysis_files f
+                    WHERE f.analysis_id = analyses.id
+                      AND (f.path = ?2 OR analyses.repo || '/' || f.path = ?2)))
+               AND (?3 IS NULL OR ts >= ?3)
+               AND (?4 IS NULL OR ts <= ?4)
+             ORDER BY id DESC
+             LIMIT ?5",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                query.repo.as_deref().map(repo_key),
+                query.file,
+                query.since,
+                query.until,
+                query.limit.min(MAX_HISTORY_ENTRIES) as i64,
+            ],
+            |r| {
+                Ok((
+                    HistoryEntry {
+                        id: r.get(0)?,
+                        ts: r.get(1)?,
+                        repo: r.get(2)?,
+                        branch: r.get(3)?,
+                        head: r.get(4)?,
+                        trigger: r.get(5)?,
+                        diff_hash: r.get(6)?,
+                        files: Vec::new(),
+                        model: r.get(8)?,
+                        latency_ms: r.get::<_, i64>(9)? as u64,
+                        result: AnalysisResult::default(),
+                    },
+                    r.get::<_, String>(7)?,
+                    r.get::<_, String>(10)?,
+                ))
+            },
+        )?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (mut entry, files, result) = row?;
+            entry.files = serde_json::from_str(&files)?;
+            entry.result = serde_json::from_str(&result)?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(repo: &str, files: &[&str], summary: &str) -> AnalysisRecord {
+        AnalysisRecord {
+            repo: repo.to_string(),
+            branch: "main".to_string(),
+            head: "abc123".to_string(),
+            trigger: "save".to_string(),
+            diff_hash: "d1".to_string(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+            model: "Scripted:fixture.json".to_string(),
+            latency_ms: 42,
+            result: AnalysisResult { summary: vec![summary.to_string()], ..AnalysisResult::default() },
+        }
+    }
+
+    fn query() -> HistoryPayload {
+        HistoryPayload { repo: None, file: None, since: None, until: None, limit: 50 }
+    }
+
+    fn summaries(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.result.summary[0].as_str()).collect()
+    }
+
+    #[test]
+    fn recorded_analysis_round_trips_newest_first() {
+        let db = AuditLog::open(":memory:").unwrap();
+        db.record_analysis(&record("/w/app/", &["src/a.rs"], "first")).unwrap();
+        let id = db.record_analysis(&record("/w/app", &["src/b.rs"], "second")).unwrap();
+
+        let entries = db.history(&query()).unwrap();
+        assert_eq!(summaries(&entries), vec!["second", "first"]);
+        let newest = &entries[0];
+        assert_eq!(newest.id, id);
+        assert_eq!(newest.repo, "/w/app");
+        assert_eq!((newest.branch.as_str(), newest.head.as_str()), ("main", "abc123"));
+        assert_eq!(newest.files, vec!["src/b.rs"]);
+        assert_eq!(newest.latency_ms, 42);
+        assert!(newest.ts > 0);
+    }
+
+    #[test]
+    fn history_filters_by_repo_file_and_time() {
+        let db = AuditLog::open(":memory:").unwrap();
+        db.record_analysis(&record("/w/app", &["src/a.rs", "src/b.rs"], "app ab")).unwrap();
+        db.record_analysis(&record("/w/app", &["src/c.rs"], "app c")).unwrap();
+        db.record_analysis(&record("/w/other", &["src/a.rs"], "other a")).unwrap();
+
+        let by_repo = HistoryPayload { repo: Some("/w/app/".to_string()), ..query() };
+        assert_eq!(summaries(&db.history(&by_repo).unwrap()), vec!["app c", "app ab"]);
+
+        let by_file = HistoryPayload { file: Some("/w/app/src/a.rs".to_string()), ..query() };
+        assert_eq!(summaries(&db.history(&by_file).unwrap()), vec!["app ab"]);
+        let by_relative_file = HistoryPayload { file: Some("src/a.rs".to_string()), ..query() };
+        assert_eq!(summaries(&db.history(&by_relative_file).unwrap()), vec!["other a", "app ab"]);
+        let not_a_suffix = HistoryPayload { file: Some("/w/app/xsrc/a.rs".to_string()), ..query() };
+        assert!(db.history(&not_a_suffix).unwrap().is_empty());
+
+        let future = HistoryPayload { since: Some(i64::MAX), ..query() };
+        assert!(db.history(&future).unwrap().is_empty());
+        let past = HistoryPayload { until: Some(0), ..query() };
+        assert!(db.history(&past).unwrap().is_empty());
+
+        let one = HistoryPayload { limit: 1, ..query() };
+        assert_eq!(summaries(&db.history(&one).unwrap()), vec!["other a"]);
+    }
+
+    #[test]
+    fn only_the_newest_analyses_of_a_workspace_are_kept() {
+        let db = AuditLog::open(":memory:").unwrap();
+        db.record_analysis(&record("/w/other", &["src/a.rs"], "other")).unwrap();
+        let first = db.record_analysis(&record("/w/app", &["src/a.rs"], "oldest")).unwrap();
+        for _ in 0..MAX_ANALYSES_KEPT {
+            db.record_analysis(&record("/w/app", &["src/a.rs"], "newer")).unwrap();
+        }
+
+        let count = |sql: &str| -> i64 { db.conn.lock().unwrap().query_row(sql, [], |r| r.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM analyses WHERE repo = '/w/app'"), MAX_ANALYSES_KEPT as i64);
+        assert_eq!(count(&format!("SELECT COUNT(*) FROM analysis_files WHERE analysis_id = {}", first)), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM analyses WHERE repo = '/w/other'"), 1, "other workspaces keep theirs");
+    }
+}
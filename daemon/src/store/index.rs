@@ -0,0 +1,331 @@
+use super::AuditLog;
+use anyhow::Result;
+use ignore::WalkBuilder;
+use rusqlite::params;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Stop walking after this many files so a huge monorepo cannot stall an analysis.
+const MAX_FILES_INDEXED: usize = 50_000;
+/// Generated bundles and fixtures are not worth tokenizing.
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+/// Shorter identifiers (`i`, `ok`, `new`) are never looked up, so never stored.
+pub const MIN_NAME_LEN: usize = 4;
+
+/// What one `refresh_index` pass did.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IndexStats {
+    pub scanned: usize,
+    pub reindexed: usize,
+    pub removed: usize,
+}
+
+/// A file whose content hash changed since the last refresh.
+struct Update {
+    path: String,
+    hash: String,
+    mtime_ns: i64,
+    size: i64,
+    /// None when only the mtime moved and the content is unchanged.
+    names: Option<HashMap<String, i64>>,
+    imports: Vec<String>,
+}
+
+impl AuditLog {
+    /// Bring the index of the workspace at `root` up to date: for each file,
+    /// how often every identifier of at least `MIN_NAME_LEN` characters
+    /// appears in it, and its import specifiers. It is a token count, not a
+    /// symbol table: a name in a comment or string counts too. Files are
+    /// re-read only when their size or mtime moved, and re-tokenized only when
+    /// their content hash changed. Honours .gitignore; only files accepted by
+    /// `is_source` are indexed, and `imports` extracts the raw import
+    /// specifiers of one file from its path and source. Blocking.
+    pub fn refresh_index(
+        &self,
+        root: &Path,
+        is_source: impl Fn(&str) -> bool,
+        imports: impl Fn(&str, &str) -> Vec<String>,
+    ) -> Result<IndexStats> {
+        let repo = repo_key(root);
+        let known: HashMap<String, (String, i64, i64)> = {
+            let conn = self.conn.lock().expect("audit mutex poisoned");
+            let mut stmt = conn.prepare("SELECT path, hash, mtime_ns, size FROM index_files WHERE repo = ?1")?;
+            let rows = stmt.query_map([&repo], |r| Ok((r.get(0)?, (r.get(1)?, r.get(2)?, r.get(3)?))))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        let mut stats = IndexStats::default();
+        let mut seen = Vec::new();
+        let mut updates = Vec::new();
+        let mut truncated = false;
+        for entry in WalkBuilder::new(root).build().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let Some(path) = relative(root, entry.path()) else { continue };
+            if !is_source(&path) {
+                continue;
+            }
+            let Ok(meta) = entry.metadata() else { continue };
+            if meta.len() > MAX_FILE_BYTES {
+                continue;
+            }
+            if stats.scanned == MAX_FILES_INDEXED {
+                truncated = true;
+                break;
+            }
+            stats.scanned += 1;
+            seen.push(path.clone());
+
+            let mtime_ns = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos() as i64)
+                .unwrap_or(0);
+            let size = meta.len() as i64;
+            let previous = known.get(&path);
+            if previous.is_some_and(|(_, m, s)| *m == mtime_ns && *s == size) {
+                continue;
+            }
+            let Ok(source) = std::fs::read_to_string(entry.path()) else { continue };
+            let hash = content_hash(&source);
+            let (names, imports) = match previous {
+                Some((old, _, _)) if *old == hash => (None, Vec::new()),
+                _ => (Some(tokenize(&source)), imports(&path, &source)),
+            };
+            updates.push(Update { path, hash, mtime_ns, size, names, imports });
+        }
+
+        let mut conn = self.conn.lock().expect("audit mutex poisoned");
+        let tx = conn.transaction()?;
+        for update in &updates {
+            tx.execute(
+                "INSERT INTO index_files (repo, path, hash, mtime_ns, size) VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (repo, path) DO UPDATE SET hash = ?3, mtime_ns = ?4, size = ?5",
+                params![repo, update.path, update.hash, update.mtime_ns, update.size],
+            )?;
+            let Some(names) = &update.names else { continue };
+            stats.reindexed += 1;
+            tx.execute("DELETE FROM index_refs WHERE repo = ?1 AND path = ?2", params![repo, update.path])?;
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO index_refs (repo, path, name, count) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for (name, count) in names {
+                insert.execute(params![repo, update.path, name, count])?;
+            }
+            tx.execute("DELETE FROM index_imports WHERE repo = ?1 AND path = ?2", params![repo, update.path])?;
+            let mut insert = tx.prepare_cached("INSERT INTO index_imports (repo, path, spec) VALUES (?1, ?2, ?3)")?;
+            for spec in &update.imports {
+                insert.execute(params![repo, update.path, spec])?;
+            }
+        }
+        // A truncated walk has not seen every file, so it cannot tell which are gone.
+        if !truncated {
+            let seen: std::collections::HashSet<&String> = seen.iter().collect();
+            for path in known.keys().filter(|p| !seen.contains(p)) {
+                tx.execute("DELETE FROM index_files WHERE repo = ?1 AND path = ?2", params![repo, path])?;
+                tx.execute("DELETE FROM index_refs WHERE repo = ?1 AND path = ?2", params![repo, path])?;
+                tx.execute("DELETE FROM index_imports WHERE repo = ?1 AND path = ?2", params![repo, path])?;
+                stats.removed += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(stats)
+    }
+
+    /// Files in the workspace at `root` that mention `name`, with how often.
+    pub fn references(&self, root: &Path, name: &str) -> Result<Vec<(String, usize)>> {
+        let conn = self.conn.lock().expect("audit mutex poisoned");
+        let mut stmt = conn.prepare_cached(
+            "SELECT path, count FROM index_refs WHERE repo = ?1 AND name = ?2 ORDER BY path",
+        )?;
+        let rows = stmt.query_map(params![repo_key(root), name], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)? as usize))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Every file indexed for the workspace at `root`.
+    pub fn indexed_files(&self, root: &Path) -> Result<Vec<String>> {
+        let conn = self.conn.lock().expect("audit mutex poisoned");
+        let mut stmt = conn.prepare_cached("SELECT path FROM index_files WHERE repo = ?1")?;
+        let rows = stmt.query_map([repo_key(root)], |r| r.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// `(importing file, raw import specifier)` for the workspace at `root`.
+    pub fn import_specs(&self, root: &Path) -> Result<Vec<(String, String)>> {
+        let conn = self.conn.lock().expect("audit mutex poisoned");
+        let mut stmt = conn.prepare_cached("SELECT path, spec FROM index_imports WHERE repo = ?1")?;
+        let rows = stmt.query_map([repo_key(root)], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+/// Index rows are keyed by the canonical workspace path, so `/repo` and
+/// `/repo/` (or a symlink to it) share one index.
+fn repo_key(root: &Path) -> String {
+    root.canonicalize()
+        .unwrap_or_else(|_| root.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn relative(root: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?.to_str()?;
+    Some(rel.replace('\\', "/"))
+}
+
+fn content_hash(source: &str) -> String {
+    Sha256::digest(source.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Identifier → occurrence count, ignoring names too short to look up.
+fn tokenize(source: &str) -> HashMap<String, i64> {
+    let mut names = HashMap::new();
+    for word in source.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$')) {
+        if word.chars().count() < MIN_NAME_LEN || word.starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        *names.entry(word.to_string()).or_insert(0) += 1;
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::OptionalExtension;
+    use crate::test_support::TempDir;
+    use std::fs;
+
+    /// Content hash recorded for `path` at the last refresh, if indexed.
+    fn indexed_hash(db: &AuditLog, root: &Path, path: &str) -> Option<String> {
+        db.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT hash FROM index_files WHERE repo = ?1 AND path = ?2",
+                params![repo_key(root), path],
+                |r| r.get(0),
+            )
+            .optional()
+            .unwrap()
+    }
+
+    fn rust_only(path: &str) -> bool {
+        path.ends_with(".rs")
+    }
+
+    fn use_lines(_path: &str, source: &str) -> Vec<String> {
+        source.lines().filter(|l| l.starts_with("use ")).map(String::from).collect()
+    }
+
+    #[test]
+    fn first_refresh_indexes_every_source_file() {
+        let tree = TempDir::new("index-first");
+        tree.write("a.rs", "fn parse_diff() {}\n");
+        tree.write("b.rs", "fn main() { parse_diff(); parse_diff(); }\n");
+        tree.write("notes.md", "parse_diff\n");
+        let db = AuditLog::open(":memory:").unwrap();
+
+        let stats = db.refresh_index(&tree.root, rust_only, use_lines).unwrap();
+
+        assert_eq!(stats, IndexStats { scanned: 2, reindexed: 2, removed: 0 });
+        assert_eq!(
+            db.references(&tree.root, "parse_diff").unwrap(),
+            vec![("a.rs".to_string(), 1), ("b.rs".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn unchanged_files_are_not_reindexed() {
+        let tree = TempDir::new("index-unchanged");
+        tree.write("a.rs", "fn alpha() {}\n");
+        tree.write("b.rs", "fn bravo() {}\n");
+        let db = AuditLog::open(":memory:").unwrap();
+        db.refresh_index(&tree.root, rust_only, use_lines).unwrap();
+
+        tree.write("b.rs", "fn bravo() { alpha(); }\n");
+        let stats = db.refresh_index(&tree.root, rust_only, use_lines).unwrap();
+
+        assert_eq!(stats, IndexStats { scanned: 2, reindexed: 1, removed: 0 });
+        assert_eq!(db.references(&tree.root, "alpha").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn touched_but_identical_file_keeps_its_hash_without_reindexing() {
+        let tree = TempDir::new("index-touched");
+        tree.write("a.rs", "fn alpha() {}\n");
+        let db = AuditLog::open(":memory:").unwrap();
+        db.refresh_index(&tree.root, rust_only, use_lines).unwrap();
+        let before = indexed_hash(&db, &tree.root, "a.rs");
+
+        // Rewrite with the same bytes: the mtime moves, the content does not.
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        tree.write("a.rs", "fn alpha() {}\n");
+        let stats = db.refresh_index(&tree.root, rust_only, use_lines).unwrap();
+
+        assert_eq!(stats.reindexed, 0);
+        assert_eq!(indexed_hash(&db, &tree.root, "a.rs"), before);
+    }
+
+    #[test]
+    fn deleted_files_are_dropped_from_the_index() {
+        let tree = TempDir::new("index-deleted");
+        tree.write("a.rs", "fn alpha() {}\n");
+        tree.write("b.rs", "fn bravo() { alpha(); }\n");
+        let db = AuditLog::open(":memory:").unwrap();
+        db.refresh_index(&tree.root, rust_only, use_lines).unwrap();
+
+        fs::remove_file(tree.root.join("b.rs")).unwrap();
+        let stats = db.refresh_index(&tree.root, rust_only, use_lines).unwrap();
+
+        assert_eq!(stats.removed, 1);
+        assert_eq!(db.references(&tree.root, "alpha").unwrap(), vec![("a.rs".to_string(), 1)]);
+        assert_eq!(indexed_hash(&db, &tree.root, "b.rs"), None);
+    }
+
+    #[test]
+    fn import_specs_follow_file_changes() {
+        let tree = TempDir::new("index-imports");
+        tree.write("a.rs", "use crate::b;\n");
+        tree.write("b.rs", "fn bravo() {}\n");
+        let db = AuditLog::open(":memory:").unwrap();
+        db.refresh_index(&tree.root, rust_only, use_lines).unwrap();
+        assert_eq!(db.import_specs(&tree.root).unwrap(), vec![("a.rs".to_string(), "use crate::b;".to_string())]);
+
+        tree.write("a.rs", "use crate::c;\n");
+        fs::remove_file(tree.root.join("b.rs")).unwrap();
+        db.refresh_index(&tree.root, rust_only, use_lines).unwrap();
+
+        assert_eq!(db.import_specs(&tree.root).unwrap(), vec![("a.rs".to_string(), "use crate::c;".to_string())]);
+        assert_eq!(db.indexed_files(&tree.root).unwrap(), vec!["a.rs".to_string()]);
+    }
+
+    #[test]
+    fn indexes_are_kept_per_repo() {
+        let one = TempDir::new("index-repo-one");
+        let two = TempDir::new("index-repo-two");
+        one.write("a.rs", "fn shared_name() {}\n");
+        two.write("b.rs", "fn other() {}\n");
+        let db = AuditLog::open(":memory:").unwrap();
+        db.refresh_index(&one.root, rust_only, use_lines).unwrap();
+        db.refresh_index(&two.root, rust_only, use_lines).unwrap();
+
+        assert_eq!(db.references(&one.root, "shared_name").unwrap().len(), 1);
+        assert!(db.references(&two.root, "shared_name").unwrap().is_empty());
+    }
+
+    #[test]
+    fn tokenize_skips_short_names_and_numbers() {
+        let names = tokenize("let x = parse_diff(1234, ok); parse_diff(y);");
+        assert_eq!(names.get("parse_diff"), Some(&2));
+        assert!(!names.contains_key("x"));
+        assert!(!names.contains_key("1234"));
+        assert!(!names.contains_key("ok"));
+    }
+}
@@ -1,2 +1,5 @@
+pub mod cache;
 pub mod db;
+pub mod history;
+pub mod index;
 pub use db::AuditLog;
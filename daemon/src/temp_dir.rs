@@ -0,0 +1,34 @@
+//! A throwaway directory for tests, shared by the library and both binaries.
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Throwaway directory under the system temp dir, removed on drop, so a
+/// failing assertion does not leak it. `name` must be unique among the tests
+/// of one binary, since they run in parallel.
+pub struct TempDir {
+    pub root: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("senior-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    /// Write `contents` to `path` under the directory, creating its parents.
+    pub fn write(&self, path: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+        let full = self.root.join(path);
+        fs::create_dir_all(full.parent().unwrap()).unwrap();
+        fs::write(&full, contents).unwrap();
+        full
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
@@ -0,0 +1,33 @@
+//! Fixtures shared by the daemon's unit tests.
+
+use crate::config::Config;
+use crate::protocol::{AnalysisResult, ImpactedFile};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[path = "temp_dir.rs"]
+mod temp_dir;
+pub use temp_dir::TempDir;
+
+/// Load the config file at `path` with `vars` as the whole environment.
+pub fn load_config(path: &Path, vars: &[(&str, &str)]) -> Result<Config> {
+    let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    Config::load(Some(path.to_path_buf()), &|name| vars.get(name).cloned())
+}
+
+/// `toml` loaded as the only config file, with an empty environment.
+pub fn config(name: &str, toml: &str) -> Config {
+    let dir = TempDir::new(name);
+    load_config(&dir.write("senior.toml", toml), &[]).unwrap()
+}
+
+/// A low-risk result at confidence 0.5 naming `files`.
+pub fn result_with(files: Vec<ImpactedFile>) -> AnalysisResult {
+    AnalysisResult { risk_level: "low".into(), impacted_files: files, confidence: 0.5, ..Default::default() }
+}
+
+/// `path` as modified by the diff, at `score`, with no explanation.
+pub fn modified(path: &str, score: f32) -> ImpactedFile {
+    ImpactedFile { path: path.into(), score, why: vec![], change: "modified".into() }
+}
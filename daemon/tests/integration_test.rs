@@ -6,15 +6,18 @@
 ///
 /// Run with:
 ///   cargo test --test integration_test
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::os::unix::fs::PermissionsExt;
 use std::os::unix::net::UnixStream;
 use std::path::PathBuf;
 use std::process::{Child, Command, Stdio};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{fs, thread};
 
 // Locate the compiled daemon binary via Cargo's env var.
 const BIN: &str = env!("CARGO_BIN_EXE_senior-daemon");
+const CLI: &str = env!("CARGO_BIN_EXE_senior");
 
 struct Daemon {
     child: Child,
@@ -24,6 +27,11 @@ struct Daemon {
 impl Daemon {
     /// Spawn the daemon with a unique socket path derived from the test name suffix.
     fn start(suffix: &str) -> Self {
+        Self::start_with_env(suffix, &[])
+    }
+
+    /// Like `start`, with extra environment variables applied after the defaults.
+    fn start_with_env(suffix: &str, env: &[(&str, &str)]) -> Self {
         let sock = PathBuf::from(format!("/tmp/senior-test-{}.sock", suffix));
         if sock.exists() {
             fs::remove_file(&sock).unwrap();
@@ -33,6 +41,10 @@ impl Daemon {
             .env("SENIOR_SOCKET_PATH", &sock)
             .env("CACTUS_MODEL_PATH", "/nonexistent") // forces stub mode — no LLM needed
             .env("RUST_LOG", "error") // silence startup noise
+            .env("SENIOR_DATA_DIR", std::env::temp_dir().join("senior-it-data")) // keep out of ~/.local/share
+            .env("SENIOR_CACHE_TTL_SECS", "0") // daemons share one database; tests opt in
+            .env("SENIOR_CONFIG", "/dev/null") // ignore the developer's own senior.toml
+            .envs(env.iter().copied())
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .spawn()
@@ -77,6 +89,30 @@ impl Daemon {
         reader.read_line(&mut line).expect("read failed");
         serde_json::from_str(line.trim()).expect("daemon returned invalid JSON")
     }
+
+    /// Send one request and collect every frame up to and including the first
+    /// one that is not an intermediate `analysis_progress` / `token` frame.
+    fn send_stream(&self, request: &serde_json::Value) -> Vec<serde_json::Value> {
+        let mut stream = UnixStream::connect(&self.sock).expect("could not connect to daemon socket");
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream
+            .write_all(format!("{}\n", request).as_bytes())
+            .expect("write failed");
+
+        let mut reader = BufReader::new(&stream);
+        let mut frames = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).expect("read failed");
+            let frame: serde_json::Value =
+                serde_json::from_str(line.trim()).expect("daemon returned invalid JSON");
+            let done = !matches!(frame["type"].as_str(), Some("analysis_progress") | Some("token"));
+            frames.push(frame);
+            if done {
+                return frames;
+            }
+        }
+    }
 }
 
 impl Drop for Daemon {
@@ -87,6 +123,38 @@ impl Drop for Daemon {
     }
 }
 
+/// Minimal OpenAI-compatible server: answers every POST with `body` until the
+/// test process exits. Returns the base URL to hand to the daemon.
+fn mock_openai_server(body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                    break;
+                }
+                if let Some(v) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    content_length = v.trim().parse().unwrap_or(0);
+                }
+            }
+            let mut req_body = vec![0; content_length];
+            reader.read_exact(&mut req_body).ok();
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    });
+    format!("http://{}/v1", addr)
+}
+
 // ── Tests ────────────────────────────────────────────────────────────────────
 
 #[test]
@@ -96,6 +164,15 @@ fn daemon_responds_to_ping() {
     assert_eq!(resp["type"], "pong", "expected pong, got: {}", resp);
 }
 
+#[test]
+fn daemon_acknowledges_cancel_of_unknown_request() {
+    let d = Daemon::start("cancel");
+    let resp = d.send(r#"{"id":"c1","type":"cancel","payload":{"id":"gone"}}"#);
+    assert_eq!(resp["id"], "c1", "got: {}", resp);
+    assert_eq!(resp["type"], "cancel_ack", "got: {}", resp);
+    assert_eq!(resp["payload"]["found"], false);
+}
+
 #[test]
 fn daemon_greets_with_no_analysis() {
     let d = Daemon::start("greet-none");
@@ -228,3 +305,870 @@ fn daemon_removes_stale_socket_on_restart() {
     let resp = d2.send(r#"{"type":"ping","payload":null}"#);
     assert_eq!(resp["type"], "pong");
 }
+
+#[test]
+fn daemon_analyzes_diff_via_openai_compatible_backend() {
+    let url = mock_openai_server(
+        r#"{"choices":[{"message":{"role":"assistant","tool_calls":[{"id":"1","type":"function","function":{"name":"submit_review","arguments":"{\"summary\":[\"swapped hash map\"],\"risk_level\":\"high\",\"risk_reasons\":[\"hot path\"],\"suggested_actions\":[]}"}}]}}]}"#,
+    );
+    let d = Daemon::start_with_env(
+        "openai",
+        &[
+            ("SENIOR_LLM_BACKEND", "openai"),
+            ("SENIOR_OPENAI_MODEL", "test-model"),
+            ("SENIOR_OPENAI_BASE_URL", &url),
+        ],
+    );
+    let req = serde_json::json!({
+        "type": "analyze_diff",
+        "payload": {
+            "diff": "diff --git a/src/lib.rs b/src/lib.rs\n@@ -1 +1 @@\n-use std::collections::BTreeMap;\n+use std::collections::HashMap;\n",
+            "files_touched": ["src/lib.rs"],
+            "active_file": "src/lib.rs",
+            "trigger": "manual"
+        }
+    });
+    let resp = d.send(&req.to_string());
+    assert_eq!(resp["type"], "analysis_result", "got: {}", resp);
+    assert_eq!(resp["payload"]["risk_level"], "high");
+    assert_eq!(resp["payload"]["summary"][0], "swapped hash map");
+}
+
+#[test]
+fn flags_place_a_private_socket_and_database() {
+    use std::os::unix::fs::PermissionsExt;
+    let root = std::env::temp_dir().join(format!("senior-it-storage-{}", std::process::id()));
+    fs::remove_dir_all(&root).ok();
+    let sock = root.join("run/senior.sock");
+    let data = root.join("data/senior");
+    let child = Command::new(BIN)
+        .arg("--socket")
+        .arg(&sock)
+        .arg(format!("--data-dir={}", data.display()))
+        .env("SENIOR_SOCKET_PATH", "/nonexistent/ignored.sock")
+        .env("SENIOR_CONFIG", "/dev/null")
+        .env("CACTUS_MODEL_PATH", "/nonexistent")
+        .env("RUST_LOG", "error")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn senior-daemon binary");
+    let d = Daemon { child, sock: sock.clone() };
+    d.wait_ready();
+    let pong = d.send(r#"{"type":"ping","payload":null}"#);
+
+    let mode = |p: &std::path::Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+    let modes = (mode(&sock), mode(&data), mode(&data.join("audit.db")));
+    drop(d);
+    fs::remove_dir_all(&root).ok();
+
+    assert_eq!(pong["type"], "pong");
+    assert_eq!(modes, (0o600, 0o700, 0o600));
+}
+
+#[test]
+fn help_flag_prints_usage_and_exits() {
+    let out = Command::new(BIN).arg("--help").output().unwrap();
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("--data-dir <DIR>"));
+
+    let out = Command::new(BIN).arg("--bogus").env("RUST_LOG", "off").output().unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("unknown argument '--bogus'"));
+}
+
+#[test]
+fn config_file_ignores_files_and_applies_risk_rules() {
+    let dir = std::env::temp_dir().join(format!("senior-it-config-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("senior.toml");
+    fs::write(
+        &config,
+        "[analysis]\nignore = [\"*.lock\"]\n\n[[risk]]\npaths = [\"migrations/**\"]\nlevel = \"high\"\nreason = \"Schema change\"\n",
+    )
+    .unwrap();
+    let d = Daemon::start_with_env("config", &[("SENIOR_CONFIG", config.to_str().unwrap())]);
+    let req = serde_json::json!({
+        "type": "analyze_diff",
+        "payload": {
+            "diff": "diff --git a/Cargo.lock b/Cargo.lock\n@@ -1 +1 @@\n-a\n+b\n\
+                     diff --git a/migrations/001.sql b/migrations/001.sql\n@@ -0,0 +1 @@\n+CREATE TABLE t (id INTEGER);\n",
+            "files_touched": ["Cargo.lock", "migrations/001.sql"],
+            "active_file": "",
+            "trigger": "save"
+        }
+    });
+    let resp = d.send(&req.to_string());
+    drop(d);
+
+    fs::write(&config, "[llm]\ntemperature = 9.0\n").unwrap();
+    let out = Command::new(BIN).arg("--config").arg(&config).env("RUST_LOG", "off").output().unwrap();
+    fs::remove_dir_all(&dir).ok();
+
+    assert_eq!(resp["type"], "analysis_result", "got: {}", resp);
+    let files: Vec<&str> = resp["payload"]["impacted_files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(files, vec!["migrations/001.sql"]);
+    assert_eq!(resp["payload"]["risk_level"], "high");
+    assert!(resp["payload"]["risk_reasons"]
+        .as_array()
+        .unwrap()
+        .contains(&serde_json::json!("Schema change (migrations/001.sql)")));
+
+    assert!(!out.status.success(), "an invalid config must stop startup");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("senior.toml: llm.temperature must be between 0 and 2"), "got: {}", stderr);
+}
+
+#[test]
+fn reload_and_sighup_switch_models_without_restarting() {
+    let dir = std::env::temp_dir().join(format!("senior-it-reload-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("senior.toml");
+    let use_fixture = |fixture: &str| {
+        let script = format!("{}/tests/fixtures/llm/{}", env!("CARGO_MANIFEST_DIR"), fixture);
+        fs::write(&config, format!("[llm]\nbackend = \"scripted\"\nmodel = \"{}\"\n", script)).unwrap();
+        script
+    };
+    fs::write(&config, "[llm]\nbackend = \"scripted\"\n").unwrap();
+    let d = Daemon::start_with_env("reload", &[("SENIOR_CONFIG", config.to_str().unwrap())]);
+    let stub = analyze_lib_rs(&d);
+
+    let script = use_fixture("tool_call.json");
+    let reloaded = d.send(r#"{"type":"reload","payload":null}"#);
+    let scripted = analyze_lib_rs(&d);
+
+    fs::write(&config, "[llm]\nbackend = \"gpt\"\n").unwrap();
+    let failed = d.send(r#"{"type":"reload","payload":null}"#);
+    let kept = analyze_lib_rs(&d);
+
+    use_fixture("tool_miss_fallback.json");
+    let hup = Command::new("kill").arg("-HUP").arg(d.child.id().to_string()).status().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(3);
+    let after_hup = loop {
+        let resp = analyze_lib_rs(&d);
+        if resp["payload"]["summary"] != kept["payload"]["summary"] || Instant::now() > deadline {
+            break resp;
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+    let pong = d.send(r#"{"type":"ping","payload":null}"#);
+    drop(d);
+    fs::remove_dir_all(&dir).ok();
+
+    assert!(stub["payload"]["summary"][0].as_str().unwrap().starts_with("Stub:"), "got: {}", stub);
+    assert_eq!(reloaded["type"], "reloaded", "got: {}", reloaded);
+    assert_eq!(reloaded["payload"]["model_id"], format!("Scripted:{}", script));
+    assert_eq!(reloaded["payload"]["llm_loaded"], true);
+    assert_eq!(scripted["payload"]["summary"][0], "switched the cache to a HashMap");
+
+    assert_eq!(failed["type"], "error");
+    assert!(failed["payload"]["message"].as_str().unwrap().contains("unknown LLM backend 'gpt'"), "got: {}", failed);
+    assert_eq!(kept["payload"]["summary"], scripted["payload"]["summary"], "a failed reload keeps the model");
+
+    assert!(hup.success());
+    assert_ne!(after_hup["payload"]["summary"], kept["payload"]["summary"], "SIGHUP reloads, got: {}", after_hup);
+    assert_eq!(pong["type"], "pong", "the daemon survives SIGHUP");
+}
+
+// ── Scripted backend: exercises the real analyze → parse pipeline ────────────
+
+/// Start a daemon whose LLM replays the named fixture from `tests/fixtures/llm`.
+fn scripted_daemon(suffix: &str, fixture: &str) -> Daemon {
+    let script = format!("{}/tests/fixtures/llm/{}", env!("CARGO_MANIFEST_DIR"), fixture);
+    Daemon::start_with_env(
+        suffix,
+        &[("SENIOR_LLM_BACKEND", "scripted"), ("SENIOR_LLM_SCRIPT", &script)],
+    )
+}
+
+fn analyze_lib_rs(d: &Daemon) -> serde_json::Value {
+    let req = serde_json::json!({
+        "type": "analyze_diff",
+        "payload": {
+            "diff": "diff --git a/src/lib.rs b/src/lib.rs\n@@ -1,2 +1,3 @@\n+use std::collections::HashMap;\n fn main() {}\n-// old\n",
+            "files_touched": ["src/lib.rs"],
+            "active_file": "src/lib.rs",
+            "trigger": "save"
+        }
+    });
+    d.send(&req.to_string())
+}
+
+#[test]
+fn scripted_tool_call_produces_review() {
+    let d = scripted_daemon("scripted-tool", "tool_call.json");
+    let resp = analyze_lib_rs(&d);
+    assert_eq!(resp["type"], "analysis_result", "got: {}", resp);
+    assert_eq!(resp["payload"]["risk_level"], "high");
+    assert_eq!(resp["payload"]["summary"][0], "switched the cache to a HashMap");
+    assert_eq!(resp["payload"]["suggested_actions"][0]["label"], "Run the cache tests");
+    assert_eq!(resp["payload"]["impacted_files"][0]["why"][0], "+1 -1 lines");
+}
+
+#[test]
+fn repeated_diff_is_answered_from_cache() {
+    let script = format!("{}/tests/fixtures/llm/tool_call.json", env!("CARGO_MANIFEST_DIR"));
+    let d = Daemon::start_with_env(
+        "scripted-cache",
+        &[
+            ("SENIOR_LLM_BACKEND", "scripted"),
+            ("SENIOR_LLM_SCRIPT", &script),
+            ("SENIOR_CACHE_TTL_SECS", "60"),
+        ],
+    );
+    // Unique content so a result cached by an earlier run cannot answer the first request.
+    let nonce = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let analyze = |start: u32| {
+        let req = serde_json::json!({
+            "type": "analyze_diff",
+            "payload": {
+                "diff": format!("diff --git a/src/cache.rs b/src/cache.rs\n@@ -{0} +{0} @@\n-// {1}\n+// {1} done\n", start, nonce),
+                "files_touched": ["src/cache.rs"],
+                "active_file": "src/cache.rs",
+                "trigger": "save"
+            }
+        });
+        d.send(&req.to_string())
+    };
+
+    let first = analyze(1);
+    assert_eq!(first["type"], "analysis_result", "got: {}", first);
+    assert_eq!(first["payload"]["cached"], false);
+
+    // Same change at a different line: the hunk moved, the content did not.
+    let second = analyze(40);
+    assert_eq!(second["payload"]["cached"], true, "got: {}", second);
+    assert_eq!(second["payload"]["summary"], first["payload"]["summary"]);
+}
+
+#[test]
+fn unchanged_files_are_not_reviewed_again() {
+    let script = format!("{}/tests/fixtures/llm/tool_call.json", env!("CARGO_MANIFEST_DIR"));
+    let d = Daemon::start_with_env(
+        "scripted-incremental",
+        &[
+            ("SENIOR_LLM_BACKEND", "scripted"),
+            ("SENIOR_LLM_SCRIPT", &script),
+            ("SENIOR_CACHE_TTL_SECS", "60"),
+        ],
+    );
+    let nonce = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let stages = |edit: u32| {
+        let req = serde_json::json!({
+            "type": "analyze_diff",
+            "payload": {
+                "diff": format!(
+                    "diff --git a/src/a.rs b/src/a.rs\n@@ -1 +1 @@\n+// a {0}\ndiff --git a/src/b.rs b/src/b.rs\n@@ -1 +1 @@\n+// b {0} edit {1}\n",
+                    nonce, edit
+                ),
+                "files_touched": ["src/a.rs", "src/b.rs"],
+                "active_file": "src/b.rs",
+                "trigger": "save",
+                "stream": true
+            }
+        });
+        let frames = d.send_stream(&req);
+        assert_eq!(frames.last().unwrap()["type"], "analysis_result", "got: {:?}", frames);
+        frames
+            .iter()
+            .filter(|f| f["type"] == "analysis_progress")
+            .map(|f| format!("{}: {}", f["payload"]["stage"].as_str().unwrap(), f["payload"]["detail"].as_str().unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        stages(1),
+        vec!["reviewing: file 1 of 2: src/a.rs", "reviewing: file 2 of 2: src/b.rs", "merging: 2 fresh and 0 reused review(s)"]
+    );
+    assert_eq!(
+        stages(2),
+        vec![
+            "reused: 1 of 2 file(s) unchanged since last reviewed",
+            "reviewing: file 1 of 1: src/b.rs",
+            "merging: 1 fresh and 1 reused review(s)"
+        ]
+    );
+}
+
+#[test]
+fn scripted_tool_miss_falls_back_to_text_json() {
+    let d = scripted_daemon("scripted-fallback", "tool_miss_fallback.json");
+    let resp = analyze_lib_rs(&d);
+    assert_eq!(resp["type"], "analysis_result", "got: {}", resp);
+    assert_eq!(resp["payload"]["risk_level"], "med");
+    assert_eq!(resp["payload"]["summary"][0], "added an import");
+}
+
+#[test]
+fn scripted_malformed_json_yields_parse_error_summary() {
+    let d = scripted_daemon("scripted-malformed", "malformed_json.json");
+    let resp = analyze_lib_rs(&d);
+    assert_eq!(resp["type"], "analysis_result", "got: {}", resp);
+    assert_eq!(resp["payload"]["summary"][0], "Changes analyzed (LLM parse error)");
+    assert_eq!(resp["payload"]["risk_level"], "low");
+}
+
+#[test]
+fn scripted_engine_failure_is_reported_as_error() {
+    let d = scripted_daemon("scripted-ffi", "ffi_error.json");
+    let resp = analyze_lib_rs(&d);
+    assert_eq!(resp["type"], "error", "got: {}", resp);
+    let msg = resp["payload"]["message"].as_str().unwrap();
+    assert!(msg.contains("out of memory"), "got: {}", msg);
+
+    // Plain completions replay a corrupt envelope — voice turns degrade gracefully.
+    let resp = d.send(r#"{"type":"greet","payload":{"last_analysis":null}}"#);
+    assert_eq!(resp["type"], "voice_answer", "got: {}", resp);
+    let text = resp["payload"]["text"].as_str().unwrap();
+    assert!(text.contains("failed to parse cactus response JSON"), "got: {}", text);
+}
+
+#[test]
+fn scripted_greeting_replays_text() {
+    let d = scripted_daemon("scripted-greet", "tool_call.json");
+    let resp = d.send(r#"{"type":"greet","payload":{"last_analysis":null}}"#);
+    assert_eq!(resp["payload"]["text"], "You swapped the cache map. Risk is high.");
+}
+
+// ── analyze_repo: the daemon computes the diff itself ───────────────────────
+
+fn git(root: &std::path::Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .expect("git must be installed");
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+fn temp_repo(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("senior-it-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&root).ok();
+    fs::create_dir_all(&root).unwrap();
+    git(&root, &["init", "-q"]);
+    git(&root, &["config", "user.email", "test@example.com"]);
+    git(&root, &["config", "user.name", "test"]);
+    fs::write(root.join("lib.rs"), "fn one() {}\n").unwrap();
+    git(&root, &["add", "lib.rs"]);
+    git(&root, &["commit", "-q", "-m", "init"]);
+    root
+}
+
+#[test]
+fn daemon_analyzes_repo_working_tree() {
+    let root = temp_repo("analyze-repo");
+    fs::write(root.join("lib.rs"), "fn one() {}\nfn two() {}\n").unwrap();
+
+    let d = Daemon::start("analyze-repo");
+    let req = serde_json::json!({
+        "type": "analyze_repo",
+        "payload": {"repo_root": root, "selector": {"kind": "working_tree"}, "trigger": "save"}
+    });
+    let resp = d.send(&req.to_string());
+    fs::remove_dir_all(&root).ok();
+
+    assert_eq!(resp["type"], "analysis_result", "got: {}", resp);
+    assert_eq!(resp["payload"]["impacted_files"][0]["path"], "lib.rs");
+    assert_eq!(resp["payload"]["impacted_files"][0]["why"][0], "+1 -0 lines");
+}
+
+#[test]
+fn finished_analyses_are_listed_by_history() {
+    let root = temp_repo("history");
+    fs::write(root.join("lib.rs"), "fn one() {}\nfn two() {}\n").unwrap();
+    let d = Daemon::start("history");
+    let analyze = serde_json::json!({
+        "type": "analyze_repo",
+        "payload": {"repo_root": root, "selector": {"kind": "working_tree"}, "trigger": "save"}
+    });
+    assert_eq!(d.send(&analyze.to_string())["type"], "analysis_result");
+
+    let history = |file: &str| {
+        let req = serde_json::json!({
+            "type": "history",
+            "payload": {"repo": root, "file": file, "limit": 1}
+        });
+        d.send(&req.to_string())
+    };
+    let resp = history("lib.rs");
+    let head = Command::new("git").arg("-C").arg(&root).args(["rev-parse", "HEAD"]).output().unwrap();
+    let missing = history("main.rs");
+    fs::remove_dir_all(&root).ok();
+
+    assert_eq!(resp["type"], "history", "got: {}", resp);
+    let entry = &resp["payload"]["entries"][0];
+    assert_eq!(entry["repo"], root.to_str().unwrap());
+    assert_eq!(entry["head"], String::from_utf8_lossy(&head.stdout).trim());
+    assert_eq!(entry["trigger"], "save");
+    assert_eq!(entry["files"], serde_json::json!(["lib.rs"]));
+    assert_eq!(entry["diff_hash"].as_str().unwrap().len(), 64);
+    assert_eq!(entry["model"], "", "stub mode has no model");
+    assert_eq!(entry["result"]["impacted_files"][0]["path"], "lib.rs");
+    assert_eq!(missing["payload"]["entries"], serde_json::json!([]));
+}
+
+#[test]
+fn analyzing_a_subdirectory_keys_the_analysis_by_the_repository_root() {
+    let root = temp_repo("analyze-subdir");
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::write(root.join("lib.rs"), "fn one() {}\nfn two() {}\n").unwrap();
+    let d = Daemon::start("analyze-subdir");
+    let analyze = serde_json::json!({
+        "type": "analyze_repo",
+        "payload": {"repo_root": root.join("sub"), "selector": {"kind": "working_tree"}, "trigger": "save"}
+    });
+    let resp = d.send(&analyze.to_string());
+    let req = serde_json::json!({"type": "history", "payload": {"repo": root, "limit": 1}});
+    let history = d.send(&req.to_string());
+    fs::remove_dir_all(&root).ok();
+
+    assert_eq!(resp["type"], "analysis_result", "got: {}", resp);
+    assert_eq!(resp["payload"]["impacted_files"][0]["path"], "lib.rs");
+    let entry = &history["payload"]["entries"][0];
+    assert_eq!(entry["repo"], root.to_str().unwrap(), "got: {}", history);
+    assert_eq!(entry["files"], serde_json::json!(["lib.rs"]));
+}
+
+#[test]
+fn daemon_lists_callers_of_changed_symbols() {
+    let root = temp_repo("analyze-callers");
+    fs::write(root.join("main.rs"), "fn main() { one_thing(); }\n").unwrap();
+    fs::write(root.join("lib.rs"), "fn one_thing() {}\n").unwrap();
+    git(&root, &["add", "."]);
+    git(&root, &["commit", "-q", "-m", "callers"]);
+    fs::write(root.join("lib.rs"), "fn one_thing() { todo!() }\n").unwrap();
+
+    let d = Daemon::start("analyze-callers");
+    let req = serde_json::json!({
+        "type": "analyze_repo",
+        "payload": {"repo_root": root, "selector": {"kind": "working_tree"}}
+    });
+    let resp = d.send(&req.to_string());
+    fs::remove_dir_all(&root).ok();
+
+    assert_eq!(resp["type"], "analysis_result", "got: {}", resp);
+    assert_eq!(resp["payload"]["impacted_symbols"][0]["name"], "one_thing");
+    let files = resp["payload"]["impacted_files"].as_array().unwrap();
+    assert_eq!(files.len(), 2, "got: {:?}", files);
+    assert_eq!(files[1]["path"], "main.rs");
+    assert_eq!(files[1]["why"][0], "calls `one_thing` changed in lib.rs");
+}
+
+#[test]
+fn daemon_flags_widely_imported_files() {
+    let root = temp_repo("analyze-blast");
+    fs::create_dir_all(root.join("src")).unwrap();
+    let users: Vec<String> = (0..12).map(|i| format!("user{}", i)).collect();
+    let mods: String = users.iter().map(|u| format!("mod {};\n", u)).collect();
+    fs::write(root.join("src/main.rs"), format!("mod util;\n{}fn main() {{}}\n", mods)).unwrap();
+    fs::write(root.join("src/util.rs"), "pub fn helper() -> u32 { 1 }\n").unwrap();
+    for user in &users {
+        fs::write(root.join(format!("src/{}.rs", user)), "use crate::util::helper;\n").unwrap();
+    }
+    git(&root, &["add", "."]);
+    git(&root, &["commit", "-q", "-m", "blast"]);
+    fs::write(root.join("src/util.rs"), "pub fn helper() -> u32 { 2 }\n").unwrap();
+
+    let d = Daemon::start("analyze-blast");
+    let req = serde_json::json!({
+        "type": "analyze_repo",
+        "payload": {"repo_root": root, "selector": {"kind": "working_tree"}}
+    });
+    let resp = d.send(&req.to_string());
+    fs::remove_dir_all(&root).ok();
+
+    assert_eq!(resp["type"], "analysis_result", "got: {}", resp);
+    let changed = &resp["payload"]["impacted_files"][0];
+    assert_eq!(changed["path"], "src/util.rs");
+    assert_eq!(changed["score"], 0.8, "got: {}", changed);
+    assert_eq!(changed["why"][1], "imported by 13 file(s), 13 transitively");
+    let reasons = resp["payload"]["risk_reasons"].as_array().unwrap();
+    assert!(
+        reasons.iter().any(|r| r.as_str().unwrap().starts_with("High blast radius: src/util.rs")),
+        "got: {:?}",
+        reasons
+    );
+}
+
+#[test]
+fn daemon_flags_deleting_an_imported_file() {
+    let root = temp_repo("analyze-deleted");
+    fs::write(root.join("main.rs"), "mod util;\nfn main() { util::go(); }\n").unwrap();
+    fs::write(root.join("util.rs"), "pub fn go() {}\n").unwrap();
+    git(&root, &["add", "."]);
+    git(&root, &["commit", "-q", "-m", "deleted"]);
+    fs::remove_file(root.join("util.rs")).unwrap();
+
+    let d = Daemon::start("analyze-deleted");
+    let req = serde_json::json!({
+        "type": "analyze_repo",
+        "payload": {"repo_root": root, "selector": {"kind": "working_tree"}}
+    });
+    let resp = d.send(&req.to_string());
+    fs::remove_dir_all(&root).ok();
+
+    assert_eq!(resp["type"], "analysis_result", "got: {}", resp);
+    assert_eq!(resp["payload"]["risk_level"], "high");
+    let files = resp["payload"]["impacted_files"].as_array().unwrap();
+    assert_eq!(files[0]["path"], "util.rs");
+    assert_eq!(files[0]["change"], "deleted");
+    assert_eq!(files[0]["why"][0], "file deleted");
+    assert_eq!(files[1]["path"], "main.rs");
+    assert_eq!(files[1]["why"][0], "imports util.rs, which was deleted");
+}
+
+#[test]
+fn daemon_reports_clean_repo_as_no_changes() {
+    let root = temp_repo("analyze-repo-clean");
+    let d = Daemon::start("analyze-repo-clean");
+    let req = serde_json::json!({
+        "type": "analyze_repo",
+        "payload": {"repo_root": root, "selector": {"kind": "last_change"}}
+    });
+    let resp = d.send(&req.to_string());
+    fs::remove_dir_all(&root).ok();
+
+    assert_eq!(resp["type"], "error", "got: {}", resp);
+    assert_eq!(resp["payload"]["message"], "No changes detected in this repo.");
+}
+
+#[test]
+fn scripted_streaming_emits_progress_and_tokens_before_result() {
+    let d = scripted_daemon("scripted-stream", "tool_miss_fallback.json");
+    let req = serde_json::json!({
+        "id": "req-42",
+        "type": "analyze_diff",
+        "payload": {
+            "diff": "diff --git a/src/lib.rs b/src/lib.rs\n@@ -1 +1 @@\n+use std::fmt;\n",
+            "files_touched": ["src/lib.rs"],
+            "active_file": "src/lib.rs",
+            "trigger": "save",
+            "stream": true
+        }
+    });
+    let frames = d.send_stream(&req);
+
+    assert!(frames.iter().all(|f| f["id"] == "req-42"), "every frame must echo the id: {:?}", frames);
+    let stages: Vec<&str> = frames
+        .iter()
+        .filter(|f| f["type"] == "analysis_progress")
+        .map(|f| f["payload"]["stage"].as_str().unwrap())
+        .collect();
+    assert_eq!(stages, vec!["reviewing", "fallback"]);
+    let streamed: String = frames
+        .iter()
+        .filter(|f| f["type"] == "token")
+        .map(|f| f["payload"]["text"].as_str().unwrap())
+        .collect();
+    assert!(streamed.contains("added an import"), "got: {}", streamed);
+
+    let last = frames.last().unwrap();
+    assert_eq!(last["type"], "analysis_result", "got: {}", last);
+    assert_eq!(last["payload"]["risk_level"], "med");
+}
+
+#[test]
+fn non_streaming_request_gets_single_frame_with_id() {
+    let d = scripted_daemon("scripted-nostream", "tool_call.json");
+    let req = serde_json::json!({
+        "id": "req-1",
+        "type": "analyze_diff",
+        "payload": {"diff": "", "files_touched": [], "active_file": "", "trigger": "save"}
+    });
+    let frames = d.send_stream(&req);
+    assert_eq!(frames.len(), 1, "got: {:?}", frames);
+    assert_eq!(frames[0]["id"], "req-1");
+    assert_eq!(frames[0]["type"], "analysis_result");
+}
+
+// ── senior CLI ───────────────────────────────────────────────────────────────
+
+/// Run `senior --socket <d.sock> args…` with `stdin` piped in.
+fn cli(d: &Daemon, args: &[&str], stdin: &str) -> std::process::Output {
+    let mut child = Command::new(CLI)
+        .arg("--socket")
+        .arg(&d.sock)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn senior binary");
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+const LIB_RS_PATCH: &str = "diff --git a/src/lib.rs b/src/lib.rs\n@@ -1,2 +1,3 @@\n+use std::collections::HashMap;\n fn main() {}\n-// old\n";
+
+#[test]
+fn cli_pings_and_reports_status() {
+    let d = Daemon::start("cli-status");
+    let ping = cli(&d, &["ping"], "");
+    let status = cli(&d, &["status", "--json"], "");
+    let text = cli(&d, &["status"], "");
+    let ask = cli(&d, &["ask", "is", "this", "safe?"], "");
+    drop(d);
+
+    assert!(ping.status.success());
+    assert!(String::from_utf8_lossy(&ping.stdout).starts_with("senior-daemon is running at /tmp/senior-test-cli-status.sock"));
+    let status: serde_json::Value = serde_json::from_slice(&status.stdout).unwrap();
+    assert_eq!(status["type"], "status");
+    assert_eq!(status["payload"]["llm_loaded"], false);
+    assert_eq!(status["payload"]["version"], env!("CARGO_PKG_VERSION"));
+    assert!(String::from_utf8_lossy(&text.stdout).contains("model    none (cactus backend in stub mode)"));
+    assert!(ask.status.success());
+    assert_eq!(String::from_utf8_lossy(&ask.stdout), "The LLM is not loaded so I cannot answer right now.\n");
+}
+
+#[test]
+fn cli_analyze_exits_one_on_high_risk() {
+    let repo = std::env::temp_dir().join(format!("senior-it-cli-{}", std::process::id()));
+    fs::create_dir_all(&repo).unwrap();
+    let patch = repo.join("change.patch");
+    fs::write(&patch, LIB_RS_PATCH).unwrap();
+    let repo_arg = repo.to_str().unwrap();
+
+    let d = scripted_daemon("cli-high", "tool_call.json");
+    let text = cli(&d, &["analyze", "--diff", patch.to_str().unwrap(), "--repo", repo_arg], "");
+    let json = cli(&d, &["--json", "analyze", "--diff", "-", "--repo", repo_arg], LIB_RS_PATCH);
+    let history = cli(&d, &["history", "--repo", repo_arg], "");
+    drop(d);
+    let d = Daemon::start("cli-low");
+    let stub = cli(&d, &["analyze", "--diff", patch.to_str().unwrap(), "--repo", repo_arg], "");
+    let bad = cli(&d, &["analyze", "--diff", "/nonexistent.patch"], "");
+    drop(d);
+    fs::remove_dir_all(&repo).ok();
+
+    assert_eq!(text.status.code(), Some(1), "high risk fails the command");
+    let stdout = String::from_utf8_lossy(&text.stdout);
+    assert!(stdout.starts_with("HIGH risk"), "colour is off when piped, got: {}", stdout);
+    assert!(stdout.contains("switched the cache to a HashMap"));
+
+    assert_eq!(json.status.code(), Some(1));
+    let json: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    assert_eq!(json["type"], "analysis_result");
+    assert_eq!(json["payload"]["risk_level"], "high");
+
+    assert!(history.status.success());
+    let listed = String::from_utf8_lossy(&history.stdout);
+    assert_eq!(listed.lines().count(), 2, "got: {}", listed);
+    assert!(listed.lines().all(|l| l.contains("HIGH") && l.contains("1 file(s)")), "got: {}", listed);
+
+    assert!(stub.status.success(), "low risk exits 0");
+    assert!(String::from_utf8_lossy(&stub.stdout).starts_with("LOW risk"));
+    assert_eq!(bad.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&bad.stderr).contains("cannot read /nonexistent.patch"));
+}
+
+#[test]
+fn cli_finds_the_socket_named_in_the_config_file() {
+    let d = Daemon::start("cli-config");
+    let config = std::env::temp_dir().join(format!("senior-it-cli-config-{}.toml", std::process::id()));
+    fs::write(&config, format!("[llm]\nmodel = \"unused\"\n\n[storage]\nsocket = \"{}\"\n", d.sock.display())).unwrap();
+    let run = |args: &[&str], env: Option<&std::path::Path>| {
+        let mut command = Command::new(CLI);
+        command.env_remove("SENIOR_SOCKET_PATH").env("SENIOR_CONFIG", "/nonexistent/senior.toml");
+        if let Some(path) = env {
+            command.env("SENIOR_CONFIG", path);
+        }
+        command.args(args).output().unwrap()
+    };
+    let from_env = run(&["ping"], Some(&config));
+    let from_flag = run(&["--config", config.to_str().unwrap(), "ping"], None);
+    let missing = run(&["ping"], None);
+    drop(d);
+    fs::remove_file(&config).ok();
+
+    assert!(from_env.status.success(), "stderr: {}", String::from_utf8_lossy(&from_env.stderr));
+    assert!(String::from_utf8_lossy(&from_env.stdout).starts_with("senior-daemon is running at /tmp/senior-test-cli-config.sock"));
+    assert!(from_flag.status.success(), "stderr: {}", String::from_utf8_lossy(&from_flag.stderr));
+    assert_eq!(missing.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("cannot read /nonexistent/senior.toml"));
+}
+
+#[test]
+fn cli_without_a_daemon_exits_two() {
+    let out = Command::new(CLI).args(["--socket", "/nonexistent/senior.sock", "ping"]).output().unwrap();
+    assert_eq!(out.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.starts_with("senior: cannot reach the daemon at /nonexistent/senior.sock"), "got: {}", stderr);
+
+    let out = Command::new(CLI).arg("--help").output().unwrap();
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Exit status: 0 on success, 1 when"));
+}
+
+/// Run git in `root` with `env` set, as a user would from a terminal.
+fn git_with(root: &std::path::Path, args: &[&str], env: &[(&str, &str)]) -> std::process::Output {
+    Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .env_remove("SENIOR_HOOK_OVERRIDE")
+        .env_remove("SENIOR_HOOK_THRESHOLD")
+        .envs(env.iter().copied())
+        .output()
+        .expect("git must be installed")
+}
+
+#[test]
+fn installed_hooks_block_risky_commits_and_pushes() {
+    let root = temp_repo("hooks");
+    let remote = root.with_extension("remote.git");
+    fs::remove_dir_all(&remote).ok();
+    git(&root, &["init", "-q", "--bare", remote.to_str().unwrap()]);
+    git(&root, &["remote", "add", "origin", remote.to_str().unwrap()]);
+    let d = scripted_daemon("hooks", "tool_call.json");
+    let install = |extra: &[&str]| {
+        let mut args = vec!["--repo", root.to_str().unwrap(), "hook", "install"];
+        args.extend_from_slice(extra);
+        cli(&d, &args, "")
+    };
+
+    fs::write(root.join(".git/hooks/pre-commit"), "#!/bin/sh\nexit 0\n").unwrap();
+    let refused = install(&[]);
+    let forced = install(&["--force"]);
+    let script = fs::read_to_string(root.join(".git/hooks/pre-commit")).unwrap();
+    let mode = fs::metadata(root.join(".git/hooks/pre-push")).unwrap().permissions().mode();
+
+    fs::write(root.join("lib.rs"), "fn one() {}\nfn two() {}\n").unwrap();
+    let blocked = git_with(&root, &["commit", "-qam", "two"], &[]);
+    let allowed = git_with(&root, &["commit", "-qam", "two"], &[("SENIOR_HOOK_OVERRIDE", "1")]);
+    let pushed = git_with(&root, &["push", "-q", "origin", "HEAD:refs/heads/main"], &[]);
+    drop(d);
+    fs::write(root.join("lib.rs"), "fn three() {}\n").unwrap();
+    let offline = git_with(&root, &["commit", "-qam", "three"], &[]);
+    fs::remove_dir_all(&root).ok();
+    fs::remove_dir_all(&remote).ok();
+
+    assert_eq!(refused.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("pass --force to replace it"));
+    assert!(forced.status.success(), "{}", String::from_utf8_lossy(&forced.stderr));
+    assert!(String::from_utf8_lossy(&forced.stdout).contains("they block at high risk"));
+    assert!(script.contains("Installed by `senior hook install`") && script.contains("senior-test-hooks.sock"));
+    assert_eq!(mode & 0o111, 0o111, "hooks must be executable");
+
+    assert!(!blocked.status.success(), "a high-risk commit is blocked");
+    let report = String::from_utf8_lossy(&blocked.stdout).to_string() + &String::from_utf8_lossy(&blocked.stderr);
+    assert!(report.contains("pre-commit review of staged changes"), "got: {}", report);
+    assert!(report.contains("switched the cache to a HashMap"), "got: {}", report);
+    assert!(report.contains("commit blocked: high risk reaches the high threshold"), "got: {}", report);
+    assert!(allowed.status.success(), "{}", String::from_utf8_lossy(&allowed.stderr));
+    assert!(String::from_utf8_lossy(&allowed.stderr).contains("SENIOR_HOOK_OVERRIDE is set"));
+
+    assert!(!pushed.status.success(), "a new branch is reviewed from its first commit");
+    let report = String::from_utf8_lossy(&pushed.stdout).to_string() + &String::from_utf8_lossy(&pushed.stderr);
+    assert!(report.contains("pre-push review of refs/heads/main (4b825dc.."), "got: {}", report);
+    assert!(report.contains("push blocked"), "got: {}", report);
+
+    assert!(offline.status.success(), "no daemon, no review: {}", String::from_utf8_lossy(&offline.stderr));
+    assert!(String::from_utf8_lossy(&offline.stderr).contains("skipping the rest of the pre-commit review"));
+}
+
+// ── senior-daemon --once ─────────────────────────────────────────────────────
+
+/// Run `senior-daemon --once args…` with `stdin` piped in and `env` set.
+fn once(args: &[&str], stdin: &str, env: &[(&str, &str)]) -> std::process::Output {
+    let mut child = Command::new(BIN)
+        .arg("--once")
+        .args(args)
+        .env_remove("CACTUS_MODEL_PATH")
+        .env("SENIOR_CONFIG", "/dev/null")
+        .env("SENIOR_SOCKET_PATH", "/nonexistent/senior.sock")
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn senior-daemon binary");
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn once_reviews_a_patch_and_prints_json() {
+    let script = format!("{}/tests/fixtures/llm/tool_call.json", env!("CARGO_MANIFEST_DIR"));
+    let scripted = [("SENIOR_LLM_BACKEND", "scripted"), ("SENIOR_LLM_SCRIPT", script.as_str())];
+    let patch = std::env::temp_dir().join(format!("senior-it-once-{}.patch", std::process::id()));
+    fs::write(&patch, LIB_RS_PATCH).unwrap();
+
+    let piped = once(&[], LIB_RS_PATCH, &scripted);
+    let from_file = once(&["--diff-file", patch.to_str().unwrap()], "", &scripted);
+    let unconfigured = once(&[], LIB_RS_PATCH, &[]);
+    let empty = once(&[], "\n", &scripted);
+    let broken = once(&[], LIB_RS_PATCH, &[("CACTUS_MODEL_PATH", "/nonexistent")]);
+    let missing = once(&["--diff-file", "/nonexistent.patch"], "", &scripted);
+    fs::remove_file(&patch).ok();
+
+    assert!(piped.status.success(), "{}", String::from_utf8_lossy(&piped.stderr));
+    let stdout = String::from_utf8_lossy(&piped.stdout);
+    assert_eq!(stdout.lines().count(), 1, "one JSON document and nothing else, got: {}", stdout);
+    let result: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(result["risk_level"], "high");
+    assert_eq!(result["summary"][0], "switched the cache to a HashMap");
+
+    assert!(from_file.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&from_file.stdout).unwrap();
+    assert_eq!(result["summary"][0], "switched the cache to a HashMap");
+    assert_eq!(result["impacted_files"][0]["path"], "src/lib.rs");
+
+    assert!(!unconfigured.status.success(), "no model is an error, not a stub report");
+    assert!(unconfigured.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&unconfigured.stderr).contains("no model configured"));
+    assert!(!empty.status.success());
+    assert!(String::from_utf8_lossy(&empty.stderr).contains("the diff is empty"));
+
+    assert!(!broken.status.success(), "a model that fails to load is an error, not stub mode");
+    assert!(broken.stdout.is_empty());
+    assert!(!missing.status.success());
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("cannot read /nonexistent.patch"));
+}
+
+// ── render ───────────────────────────────────────────────────────────────────
+
+#[test]
+fn results_render_as_reports_over_the_socket_and_from_the_cli() {
+    let d = scripted_daemon("render", "tool_call.json");
+    let result = analyze_lib_rs(&d);
+    assert_eq!(result["type"], "analysis_result", "got: {}", result);
+    let req = serde_json::json!({"type": "render", "payload": {"result": result["payload"], "format": "markdown"}});
+    let rendered = d.send(&req.to_string());
+    let junit = cli(&d, &["analyze", "--diff", "-", "--format", "junit"], LIB_RS_PATCH);
+    drop(d);
+
+    assert_eq!(rendered["type"], "rendered", "got: {}", rendered);
+    assert_eq!(rendered["payload"]["format"], "markdown");
+    let markdown = rendered["payload"]["text"].as_str().unwrap();
+    assert!(markdown.starts_with("### Senior review: High risk\n\nswitched the cache to a HashMap"), "got:\n{}", markdown);
+    assert!(markdown.contains("| `src/lib.rs` |"), "got:\n{}", markdown);
+
+    assert_eq!(junit.status.code(), Some(1), "high risk still fails the command");
+    let xml = String::from_utf8_lossy(&junit.stdout);
+    assert!(xml.starts_with("<?xml"), "got: {}", xml);
+    assert!(xml.contains("<failure message=\"high risk"), "got: {}", xml);
+
+    // A result saved by `--once` renders without a daemon.
+    let script = format!("{}/tests/fixtures/llm/tool_call.json", env!("CARGO_MANIFEST_DIR"));
+    let saved = once(&[], LIB_RS_PATCH, &[("SENIOR_LLM_BACKEND", "scripted"), ("SENIOR_LLM_SCRIPT", &script)]);
+    let mut child = Command::new(CLI)
+        .args(["--socket", "/nonexistent/senior.sock", "render", "--format", "sarif"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&saved.stdout).unwrap();
+    let sarif = child.wait_with_output().unwrap();
+    assert_eq!(sarif.status.code(), Some(1), "a saved high-risk result fails the command too");
+    let log: serde_json::Value = serde_json::from_slice(&sarif.stdout).unwrap();
+    assert_eq!(log["version"], "2.1.0");
+    assert_eq!(log["runs"][0]["results"][0]["level"], "error");
+    assert_eq!(log["runs"][0]["results"][0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"], "src/lib.rs");
+}
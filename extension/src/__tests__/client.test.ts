@@ -117,3 +117,40 @@ describe('DaemonClient.send', () => {
         expect(mockSocket.destroy).toHaveBeenCalled();
     });
 });
+
+describe('DaemonClient.stream', () => {
+    it('passes intermediate frames to onFrame and resolves with the final frame', async () => {
+        const lines = [
+            { id: 'r1', type: 'analysis_progress', payload: { stage: 'reviewing', detail: '' } },
+            { id: 'r1', type: 'token', payload: { text: 'hi' } },
+            { id: 'r1', type: 'analysis_result', payload: { risk_level: 'med' } },
+        ].map(f => JSON.stringify(f) + '\n').join('');
+        vi.mocked(net.createConnection).mockReturnValue(
+            makeMockSocket({ response: lines }) as any
+        );
+        const client = new DaemonClient('/tmp/senior.sock');
+        const frames: any[] = [];
+        const result = await client.stream('analyze_repo', { stream: true }, f => frames.push(f), 'r1');
+        expect(frames.map(f => f.type)).toEqual(['analysis_progress', 'token']);
+        expect(result.type).toBe('analysis_result');
+        expect((result.payload as any).risk_level).toBe('med');
+    });
+
+    it('sends the request id in the envelope', async () => {
+        const mockSocket = makeMockSocket({ response: '{"id":"r9","type":"pong"}\n' });
+        vi.mocked(net.createConnection).mockReturnValue(mockSocket as any);
+        const client = new DaemonClient('/tmp/senior.sock');
+        await client.stream('ping', null, () => {}, 'r9');
+        const parsed = JSON.parse((mockSocket.write.mock.calls[0][0] as string).trim());
+        expect(parsed.id).toBe('r9');
+        expect(parsed.type).toBe('ping');
+    });
+
+    it('rejects on an invalid JSON frame', async () => {
+        vi.mocked(net.createConnection).mockReturnValue(
+            makeMockSocket({ response: '{"type":"token","payload":{"text":"a"}}\ngarbage\n' }) as any
+        );
+        const client = new DaemonClient('/tmp/senior.sock');
+        await expect(client.stream('analyze_repo', {}, () => {})).rejects.toThrow('invalid JSON');
+    });
+});
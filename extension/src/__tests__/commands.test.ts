@@ -1,15 +1,19 @@
 import { describe, it, expect, vi, beforeEach, afterEach } from 'vitest';
 import * as vscode from 'vscode';
-import * as cp from 'child_process';
 import { parseFilesFromDiff, registerCommands } from '../commands';
 
+const sendMock = vi.hoisted(() => vi.fn());
+
 vi.mock('../daemon/client', () => ({
     DaemonClient: class {
-        send() { return Promise.resolve({ type: 'analysis_result', payload: { summary: 'ok' } }); }
+        stream(type: string, payload: unknown, onFrame: (frame: unknown) => void, id?: string) {
+            return sendMock(type, payload, onFrame, id);
+        }
     },
 }));
 
-vi.mock('child_process');
+const okResult = { type: 'analysis_result', payload: { summary: 'ok' } };
+const noChanges = { type: 'error', payload: { message: 'No changes detected in this repo.' } };
 
 // parseFilesFromDiff is a pure function — no mocking needed.
 
@@ -96,7 +100,7 @@ function makeMockManager() {
 }
 
 function makeMockPanel() {
-    return { show: vi.fn(), setLoading: vi.fn(), setResult: vi.fn(), setError: vi.fn(), isOpen: vi.fn().mockReturnValue(true) };
+    return { show: vi.fn(), setLoading: vi.fn(), setProgress: vi.fn(), setResult: vi.fn(), setError: vi.fn(), isOpen: vi.fn().mockReturnValue(true) };
 }
 
 function makeMockVoice() {
@@ -109,6 +113,7 @@ function makeContext() {
 
 describe('isAnalyzing guard', () => {
     beforeEach(() => {
+        sendMock.mockReset();
         vscode.workspace.workspaceFolders = [
             { uri: { fsPath: '/test-ws' } },
         ] as typeof vscode.workspace.workspaceFolders;
@@ -123,24 +128,18 @@ describe('isAnalyzing guard', () => {
     });
 
     it('second call while first is in-flight does not call panel.show again', async () => {
-        // Hold the git-diff callback so call #1 stays suspended at getGitDiff.
-        let releaseDiff!: () => void;
-        vi.mocked(cp.exec).mockImplementation((cmd: string, _opts: any, cb: any) => {
-            if (cmd.includes('rev-parse')) {
-                cb(new Error('no parent'), '', '');
-            } else {
-                // Store callback without calling it — keeps call #1 suspended.
-                releaseDiff = () => cb(null, '', ''); // empty diff → setError path
-            }
-            return {} as any;
-        });
+        // Hold the daemon reply so call #1 stays suspended at client.send.
+        let releaseSend!: () => void;
+        sendMock.mockImplementation(() => new Promise(resolve => {
+            releaseSend = () => resolve(noChanges);
+        }));
 
         const manager = makeMockManager();
         const panel = makeMockPanel();
         registerCommands(makeContext() as any, manager as any, panel as any, makeMockVoice() as any);
         const handler = (vscode.commands as any)._registry['senior.explainLastChange'];
 
-        // Call #1: runs synchronously past isAnalyzing=true, panel.show(), then suspends at getGitDiff.
+        // Call #1: runs synchronously past isAnalyzing=true, panel.show(), then suspends at client.send.
         const first = handler();
 
         // Call #2: sees isAnalyzing=true immediately — exits without touching the panel.
@@ -149,23 +148,20 @@ describe('isAnalyzing guard', () => {
         // panel.show was called exactly once (by call #1, synchronously).
         expect(panel.show).toHaveBeenCalledTimes(1);
 
-        // Release call #1 — empty diff → 'No changes detected' error path, no DaemonClient needed.
-        releaseDiff();
+        // Release call #1 — the daemon reports a clean repo → setError path.
+        releaseSend();
         await first;
         await second;
 
-        // Still only one show() call total.
+        // Still only one show() call total, and only one request reached the daemon.
         expect(panel.show).toHaveBeenCalledTimes(1);
+        expect(sendMock).toHaveBeenCalledTimes(1);
         expect(panel.setError).toHaveBeenCalledWith('No changes detected in this repo.');
     });
 
     it('after first call completes the guard resets and next call proceeds', async () => {
-        // Both exec calls return immediately so the handler completes quickly.
-        vi.mocked(cp.exec).mockImplementation((cmd: string, _opts: any, cb: any) => {
-            if (cmd.includes('rev-parse')) cb(new Error('no parent'), '', '');
-            else cb(null, '', ''); // empty diff → setError, no socket call
-            return {} as any;
-        });
+        // The daemon answers immediately so the handler completes quickly.
+        sendMock.mockResolvedValue(noChanges);
 
         const manager = makeMockManager();
         const panel = makeMockPanel();
@@ -239,12 +235,7 @@ describe('voice.setLastAnalysis wiring', () => {
     });
 
     it('calls voice.setLastAnalysis() with the result payload after a successful analysis', async () => {
-        // git diff returns a non-empty diff so the daemon is called
-        vi.mocked(cp.exec).mockImplementation((cmd: string, _opts: any, cb: any) => {
-            if (cmd.includes('rev-parse')) cb(new Error('no parent'), '', '');
-            else cb(null, 'diff --git a/foo.ts b/foo.ts\n--- a/foo.ts\n+++ b/foo.ts\n@@ -1 +1 @@\n-old\n+new\n', '');
-            return {} as any;
-        });
+        sendMock.mockResolvedValue(okResult);
 
         const voice = makeMockVoice();
         const panel = makeMockPanel();
@@ -258,6 +249,81 @@ describe('voice.setLastAnalysis wiring', () => {
     });
 });
 
+describe('analyze_repo request', () => {
+    beforeEach(() => {
+        vscode.workspace.workspaceFolders = [
+            { uri: { fsPath: '/test-ws' } },
+        ] as typeof vscode.workspace.workspaceFolders;
+        Object.keys((vscode.commands as any)._registry).forEach(
+            k => delete (vscode.commands as any)._registry[k]
+        );
+        sendMock.mockReset();
+    });
+
+    afterEach(() => { vi.restoreAllMocks(); });
+
+    it('asks the daemon to diff the workspace root itself', async () => {
+        sendMock.mockResolvedValue(okResult);
+        registerCommands(makeContext() as any, makeMockManager() as any, makeMockPanel() as any, makeMockVoice() as any);
+        const handler = (vscode.commands as any)._registry['senior.explainLastChange'];
+
+        await handler('auto');
+
+        expect(sendMock).toHaveBeenCalledWith('analyze_repo', {
+            repo_root: '/test-ws',
+            selector: { kind: 'last_change' },
+            active_file: '',
+            trigger: 'auto',
+            stream: true,
+            supersede: true,
+        }, expect.any(Function), expect.any(String));
+    });
+
+    it('gives every analysis a fresh request id', async () => {
+        sendMock.mockResolvedValue(okResult);
+        registerCommands(makeContext() as any, makeMockManager() as any, makeMockPanel() as any, makeMockVoice() as any);
+        const handler = (vscode.commands as any)._registry['senior.explainLastChange'];
+
+        await handler();
+        await handler();
+
+        const ids = sendMock.mock.calls.map(call => call[3]);
+        expect(ids).toHaveLength(2);
+        expect(ids[0]).not.toEqual(ids[1]);
+    });
+
+    it('renders streamed progress and tokens before the result', async () => {
+        sendMock.mockImplementation((_type: string, _payload: unknown, onFrame: (f: unknown) => void) => {
+            onFrame({ type: 'analysis_progress', payload: { stage: 'reviewing', detail: '1 file(s) changed' } });
+            onFrame({ type: 'token', payload: { text: 'risk ' } });
+            onFrame({ type: 'token', payload: { text: 'is low' } });
+            return Promise.resolve(okResult);
+        });
+        const panel = makeMockPanel();
+        registerCommands(makeContext() as any, makeMockManager() as any, panel as any, makeMockVoice() as any);
+        const handler = (vscode.commands as any)._registry['senior.explainLastChange'];
+
+        await handler();
+
+        expect(panel.setProgress).toHaveBeenNthCalledWith(1, 'reviewing', '');
+        expect(panel.setProgress).toHaveBeenNthCalledWith(2, 'reviewing', 'risk ');
+        expect(panel.setProgress).toHaveBeenNthCalledWith(3, 'reviewing', 'risk is low');
+        expect(panel.setResult).toHaveBeenCalledWith({ summary: 'ok' });
+    });
+
+    it('shows daemon errors in the panel', async () => {
+        sendMock.mockResolvedValue({ type: 'error', payload: { message: 'git diff HEAD failed: not a git repository' } });
+        const panel = makeMockPanel();
+        registerCommands(makeContext() as any, makeMockManager() as any, panel as any, makeMockVoice() as any);
+        const handler = (vscode.commands as any)._registry['senior.explainLastChange'];
+
+        await handler();
+
+        expect(panel.setError).toHaveBeenCalledWith('git diff HEAD failed: not a git repository');
+        expect(panel.setResult).not.toHaveBeenCalled();
+    });
+});
+
 describe('auto-trigger panel auto-open', () => {
     beforeEach(() => {
         vscode.workspace.workspaceFolders = [
@@ -271,11 +337,7 @@ describe('auto-trigger panel auto-open', () => {
     afterEach(() => { vi.restoreAllMocks(); });
 
     it('auto trigger opens panel after first result when panel was not open', async () => {
-        vi.mocked(cp.exec).mockImplementation((cmd: string, _opts: any, cb: any) => {
-            if (cmd.includes('rev-parse')) cb(new Error('no parent'), '', '');
-            else cb(null, 'diff --git a/foo.ts b/foo.ts\n--- a/foo.ts\n+++ b/foo.ts\n@@ -1 +1 @@\n-old\n+new\n', '');
-            return {} as any;
-        });
+        sendMock.mockResolvedValue(okResult);
 
         const panel = makeMockPanel();
         panel.isOpen.mockReturnValue(false);
@@ -290,11 +352,7 @@ describe('auto-trigger panel auto-open', () => {
     });
 
     it('auto trigger does not call show() when panel is already open', async () => {
-        vi.mocked(cp.exec).mockImplementation((cmd: string, _opts: any, cb: any) => {
-            if (cmd.includes('rev-parse')) cb(new Error('no parent'), '', '');
-            else cb(null, 'diff --git a/foo.ts b/foo.ts\n--- a/foo.ts\n+++ b/foo.ts\n@@ -1 +1 @@\n-old\n+new\n', '');
-            return {} as any;
-        });
+        sendMock.mockResolvedValue(okResult);
 
         const panel = makeMockPanel();
         panel.isOpen.mockReturnValue(true);
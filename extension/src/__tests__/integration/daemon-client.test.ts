@@ -8,6 +8,7 @@
 import * as cp from 'child_process';
 import * as fs from 'fs';
 import * as net from 'net';
+import * as os from 'os';
 import * as path from 'path';
 import { afterAll, beforeAll, describe, expect, it } from 'vitest';
 import { DaemonClient } from '../../daemon/client';
@@ -46,6 +47,7 @@ beforeAll(async () => {
         env: {
             ...process.env,
             SENIOR_SOCKET_PATH: TEST_SOCK,
+            SENIOR_DATA_DIR: path.join(os.tmpdir(), 'senior-it-data'), // keep out of ~/.local/share
             CACTUS_MODEL_PATH: '/nonexistent', // stub mode — no real LLM needed
             RUST_LOG: 'error',
         },
@@ -4,7 +4,7 @@ import * as path from 'path';
 import * as cp from 'child_process';
 import * as fs from 'fs';
 import * as net from 'net';
-import { DaemonManager } from '../daemon/manager';
+import { DaemonManager, defaultSocketPath } from '../daemon/manager';
 
 // vscode is aliased to __mocks__/vscode.ts via vitest.config.ts.
 vi.mock('child_process');
@@ -115,10 +115,32 @@ describe('DaemonManager.isRunning', () => {
 });
 
 describe('DaemonManager.getSocketPath', () => {
-    it('returns the senior socket path', () => {
+    it('returns the default socket path', () => {
         mockConfig({});
         const manager = new DaemonManager(makeContext());
-        expect(manager.getSocketPath()).toBe('/tmp/senior.sock');
+        expect(manager.getSocketPath()).toBe(defaultSocketPath());
+    });
+});
+
+describe('defaultSocketPath', () => {
+    it('prefers SENIOR_SOCKET_PATH', () => {
+        expect(defaultSocketPath({ SENIOR_SOCKET_PATH: '/x/s.sock', XDG_RUNTIME_DIR: '/run/user/1' }, '/home/me'))
+            .toBe('/x/s.sock');
+    });
+
+    it('uses XDG_RUNTIME_DIR when set', () => {
+        expect(defaultSocketPath({ XDG_RUNTIME_DIR: '/run/user/1' }, '/home/me')).toBe('/run/user/1/senior.sock');
+    });
+
+    it('falls back to the data directory', () => {
+        expect(defaultSocketPath({}, '/home/me')).toBe('/home/me/.local/share/senior/senior.sock');
+        expect(defaultSocketPath({ XDG_DATA_HOME: '/data' }, '/home/me')).toBe('/data/senior/senior.sock');
+        expect(defaultSocketPath({ SENIOR_DATA_DIR: '/d' }, '/home/me')).toBe('/d/senior.sock');
+    });
+
+    it('ignores relative XDG directories', () => {
+        expect(defaultSocketPath({ XDG_RUNTIME_DIR: 'run', XDG_DATA_HOME: 'data' }, '/home/me'))
+            .toBe('/home/me/.local/share/senior/senior.sock');
     });
 });
 
@@ -173,6 +195,14 @@ describe('DaemonManager.start()', () => {
         expect(env.RUST_LOG).toBe('debug');
     });
 
+    it('passes its socket path to the daemon', async () => {
+        mockConfig({ daemonPath: '/bin/senior-daemon', modelPath: '' });
+        const manager = new DaemonManager(makeContext());
+        await manager.start();
+        const spawnCall = vi.mocked(cp.spawn).mock.calls[0];
+        expect(spawnCall[1]).toEqual(['--socket', manager.getSocketPath()]);
+    });
+
     it('sets CACTUS_MODEL_PATH when model path is configured', async () => {
         mockConfig({ daemonPath: '/bin/senior-daemon', modelPath: '/models/gemma' });
         const manager = new DaemonManager(makeContext());
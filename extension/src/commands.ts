@@ -1,5 +1,4 @@
 import * as vscode from 'vscode';
-import * as cp from 'child_process';
 import { DaemonClient } from './daemon/client';
 import { DaemonManager } from './daemon/manager';
 import { ImpactPanel } from './ui/panel';
@@ -16,42 +15,6 @@ export function parseFilesFromDiff(diff: string): string[] {
         .filter(Boolean);
 }
 
-function hasParentCommit(cwd: string): Promise<boolean> {
-    return new Promise(resolve => {
-        cp.exec('git rev-parse HEAD~1', { cwd }, err => resolve(!err));
-    });
-}
-
-function getGitDiff(workspaceRoot: string): Promise<string> {
-    return new Promise((resolve, reject) => {
-        cp.exec(
-            'git diff HEAD --unified=5',
-            { cwd: workspaceRoot, maxBuffer: 2 * 1024 * 1024 },
-            async (err, stdout, stderr) => {
-                if (err && !stdout) {
-                    reject(new Error(`git diff failed: ${stderr}`));
-                    return;
-                }
-                if (!stdout.trim()) {
-                    // Nothing unstaged — try last commit diff if one exists
-                    const hasParent = await hasParentCommit(workspaceRoot);
-                    if (!hasParent) {
-                        resolve('');
-                        return;
-                    }
-                    cp.exec(
-                        'git diff HEAD~1 HEAD --unified=5',
-                        { cwd: workspaceRoot, maxBuffer: 2 * 1024 * 1024 },
-                        (_err2, stdout2) => resolve(stdout2 || '')
-                    );
-                } else {
-                    resolve(stdout);
-                }
-            }
-        );
-    });
-}
-
 export function registerCommands(
     context: vscode.ExtensionContext,
     manager: DaemonManager,
@@ -59,6 +22,8 @@ export function registerCommands(
     voice: VoiceController,
 ) {
     let isAnalyzing = false;
+    let requestSeq = 0;
+    let lastResult: unknown = null;
 
     context.subscriptions.push(
         vscode.commands.registerCommand('senior.explainLastChange', async (trigger: 'manual' | 'auto' = 'manual') => {
@@ -80,20 +45,30 @@ export function registerCommands(
             voice.setAnalyzing(true);
             panel.setLoading(true);
             try {
-                const diff = await getGitDiff(root);
-                if (!diff.trim()) {
-                    panel.setError('No changes detected in this repo.');
-                    return;
-                }
-                const files_touched = parseFilesFromDiff(diff);
+                // The daemon computes the diff itself: uncommitted changes, or the
+                // last commit when the working tree is clean. Progress and model
+                // output stream into the panel while inference runs. `supersede`
+                // lets the daemon drop any older analysis of this workspace.
                 const client = new DaemonClient(manager.getSocketPath());
-                const response = await client.send('analyze_diff', {
-                    diff,
-                    files_touched,
+                let stage = 'starting';
+                let streamed = '';
+                const response = await client.stream('analyze_repo', {
+                    repo_root: root,
+                    selector: { kind: 'last_change' },
                     active_file: vscode.window.activeTextEditor?.document.fileName ?? '',
                     trigger,
-                });
+                    stream: true,
+                    supersede: true,
+                }, frame => {
+                    if (frame.type === 'analysis_progress') {
+                        stage = frame.payload.stage;
+                    } else if (frame.type === 'token') {
+                        streamed += frame.payload.text;
+                    }
+                    panel.setProgress(stage, streamed);
+                }, `analyze-${++requestSeq}`);
                 if (response.type === 'analysis_result') {
+                    lastResult = response.payload;
                     panel.setResult(response.payload as any);
                     if (trigger === 'auto' && !panel.isOpen()) {
                         panel.show();
@@ -117,5 +92,47 @@ export function registerCommands(
         vscode.commands.registerCommand('senior.voiceToggle', () => voice.toggle()),
         vscode.commands.registerCommand('senior.speakAnalysis', () => voice.speakAnalysis()),
         vscode.commands.registerCommand('senior.toggleMute', () => voice.toggleMute()),
+        vscode.commands.registerCommand('senior.reloadDaemon', async () => {
+            if (!manager.isRunning()) {
+                vscode.window.showInformationMessage('senior: the daemon is not running; it reads its config when started.');
+                return;
+            }
+            // The daemon re-reads senior.toml and swaps in the model it names;
+            // analyses already running finish on the previous model.
+            try {
+                const client = new DaemonClient(manager.getSocketPath());
+                const response = await client.send<null, any>('reload', null);
+                if (response.type === 'reloaded') {
+                    const mode = response.payload.llm_loaded ? '' : ' (stub mode, no model loaded)';
+                    vscode.window.showInformationMessage(`senior: now using ${response.payload.model_id}${mode}`);
+                } else {
+                    vscode.window.showErrorMessage(`senior: ${response.payload.message}`);
+                }
+            } catch (err: any) {
+                vscode.window.showErrorMessage(`senior: reload failed: ${err.message}`);
+            }
+        }),
+        vscode.commands.registerCommand('senior.copyReport', async () => {
+            if (!lastResult) {
+                vscode.window.showInformationMessage('senior: nothing to copy yet; run "Senior: Explain Last Change" first.');
+                return;
+            }
+            if (!manager.isRunning()) {
+                const started = await manager.start();
+                if (!started) return;
+            }
+            try {
+                const client = new DaemonClient(manager.getSocketPath());
+                const response = await client.send<unknown, any>('render', { result: lastResult, format: 'markdown' });
+                if (response.type === 'rendered') {
+                    await vscode.env.clipboard.writeText(response.payload.text);
+                    vscode.window.showInformationMessage('senior: report copied as Markdown.');
+                } else {
+                    vscode.window.showErrorMessage(`senior: ${response.payload.message}`);
+                }
+            } catch (err: any) {
+                vscode.window.showErrorMessage(`senior: copying the report failed: ${err.message}`);
+            }
+        }),
     );
 }
@@ -1,10 +1,14 @@
 import * as net from 'net';
 
-interface Envelope<T> {
+export interface Envelope<T> {
+    id?: string;
     type: string;
     payload: T;
 }
 
+/** Intermediate frames a streaming request emits before its final response. */
+const STREAM_FRAME_TYPES = new Set(['analysis_progress', 'token']);
+
 export class DaemonClient {
     private readonly socketPath: string;
 
@@ -48,6 +52,71 @@ export class DaemonClient {
         });
     }
 
+    /**
+     * Send a request whose payload asked for `stream: true`. Every
+     * `analysis_progress` / `token` frame is passed to `onFrame`; the promise
+     * resolves with the first final frame. The timeout is an inactivity
+     * timeout: it restarts on every frame, so long analyses that keep
+     * streaming are not cut off.
+     */
+    stream<TReq, TRes>(
+        type: string,
+        payload: TReq,
+        onFrame: (frame: Envelope<any>) => void,
+        id?: string,
+    ): Promise<Envelope<TRes>> {
+        return new Promise((resolve, reject) => {
+            const socket = net.createConnection(this.socketPath);
+            let buffer = '';
+            let timeout: NodeJS.Timeout;
+            const armTimeout = () => {
+                clearTimeout(timeout);
+                timeout = setTimeout(() => {
+                    socket.destroy();
+                    reject(new Error('senior: daemon request timed out'));
+                }, 10_000);
+            };
+            armTimeout();
+
+            socket.on('connect', () => {
+                const msg = JSON.stringify(id ? { id, type, payload } : { type, payload }) + '\n';
+                socket.write(msg);
+            });
+
+            socket.on('data', (chunk: Buffer) => {
+                buffer += chunk.toString();
+                let newlineIdx: number;
+                while ((newlineIdx = buffer.indexOf('\n')) !== -1) {
+                    const line = buffer.slice(0, newlineIdx);
+                    buffer = buffer.slice(newlineIdx + 1);
+                    let frame: Envelope<any>;
+                    try {
+                        frame = JSON.parse(line);
+                    } catch {
+                        clearTimeout(timeout);
+                        socket.destroy();
+                        reject(new Error(`senior: invalid JSON from daemon: ${line}`));
+                        return;
+                    }
+                    if (STREAM_FRAME_TYPES.has(frame.type)) {
+                        armTimeout();
+                        onFrame(frame);
+                        continue;
+                    }
+                    clearTimeout(timeout);
+                    socket.destroy();
+                    resolve(frame);
+                    return;
+                }
+            });
+
+            socket.on('error', (err) => {
+                clearTimeout(timeout);
+                reject(err);
+            });
+        });
+    }
+
     async ping(): Promise<boolean> {
         try {
             const res = await this.send('ping', null);
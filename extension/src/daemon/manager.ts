@@ -2,11 +2,32 @@ import * as vscode from 'vscode';
 import * as cp from 'child_process';
 import * as path from 'path';
 import * as fs from 'fs';
+import * as os from 'os';
 import { DaemonClient } from './client';
 
+/**
+ * The socket the daemon would pick on its own: `SENIOR_SOCKET_PATH`, else
+ * `$XDG_RUNTIME_DIR/senior.sock`, else `senior.sock` in its data directory
+ * (`SENIOR_DATA_DIR`, `$XDG_DATA_HOME/senior` or `~/.local/share/senior`).
+ * The manager passes it to the daemon explicitly so both sides agree.
+ */
+export function defaultSocketPath(env: NodeJS.ProcessEnv = process.env, home: string = os.homedir()): string {
+    const absolute = (p?: string) => (p && path.isAbsolute(p) ? p : undefined);
+    if (env.SENIOR_SOCKET_PATH) {
+        return env.SENIOR_SOCKET_PATH;
+    }
+    const runtime = absolute(env.XDG_RUNTIME_DIR);
+    if (runtime) {
+        return path.join(runtime, 'senior.sock');
+    }
+    const dataDir = env.SENIOR_DATA_DIR
+        || path.join(absolute(env.XDG_DATA_HOME) ?? path.join(home, '.local', 'share'), 'senior');
+    return path.join(dataDir, 'senior.sock');
+}
+
 export class DaemonManager implements vscode.Disposable {
     private process: cp.ChildProcess | null = null;
-    private readonly socketPath = '/tmp/senior.sock';
+    private readonly socketPath = defaultSocketPath();
 
     constructor(
         private readonly context: vscode.ExtensionContext,
@@ -58,7 +79,7 @@ export class DaemonManager implements vscode.Disposable {
         };
 
         await new Promise<void>((resolve) => {
-            this.process = cp.spawn(daemonPath, [], { env });
+            this.process = cp.spawn(daemonPath, ['--socket', this.socketPath], { env });
             this.process.stdout?.on('data', (data: Buffer) => {
                 this.output?.appendLine(data.toString().trimEnd());
             });
@@ -45,6 +45,11 @@ export class ImpactPanel {
         this.panel?.webview.postMessage({ type: loading ? 'loading' : 'idle' });
     }
 
+    /** Streaming update while an analysis runs: current stage plus model output so far. */
+    setProgress(stage: string, text: string): void {
+        this.panel?.webview.postMessage({ type: 'progress', stage, text });
+    }
+
     setResult(result: unknown): void {
         this.panel?.webview.postMessage({ type: 'result', result });
     }